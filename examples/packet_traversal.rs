@@ -0,0 +1,73 @@
+// Times the nearest hit search of a BVH with single rays against ray packets.
+// Run with `cargo run --release --example packet_traversal`, add `--features packet8` for eight lane packets
+
+extern crate rtrace;
+
+use std::time::{Duration, Instant};
+
+use rtrace::defs::{FloatType, Point3, Vector3};
+use rtrace::core::{Intersector, Material, Color, Ray, RayPacket, PACKET_SIZE};
+use rtrace::basic::{ModelVec};
+use rtrace::basic::bvh::{BvhIntersector};
+use rtrace::basic::model::{SolidSphere, SolidPlane, SolidTriangle};
+
+const IMAGE_SIZE: usize = 512;
+const ITERATIONS: u32 = 5;
+
+fn create_models() -> ModelVec {
+    let material = Material::new_diffuse(Color::one(), None);
+    let mut models: ModelVec = vec![Box::new(SolidPlane::new_positioned(material, Point3::new(0.0, -3.0, 0.0), Vector3::y_axis()))];
+    for x in 0..32 {
+        for y in 0..24 {
+            let center = Point3::new(x as FloatType * 0.5 - 8.0, y as FloatType * 0.5 - 6.0, 10.0 + ((x * 7 + y * 3) % 5) as FloatType);
+            if (x + y) % 3 == 0 {
+                models.push(Box::new(SolidTriangle::new(material, center + Vector3::new(-0.2, -0.2, 0.0), center + Vector3::new(0.2, -0.2, 0.0), center + Vector3::new(0.0, 0.2, 0.0))));
+            } else {
+                models.push(Box::new(SolidSphere::new_positioned(material, center, 0.2)));
+            }
+        }
+    }
+    models
+}
+
+fn create_camera_rays() -> Vec<Ray> {
+    let mut rays = Vec::with_capacity(IMAGE_SIZE * IMAGE_SIZE);
+    for y in 0..IMAGE_SIZE {
+        for x in 0..IMAGE_SIZE {
+            let direction = Vector3::new(x as FloatType / IMAGE_SIZE as FloatType - 0.5, y as FloatType / IMAGE_SIZE as FloatType - 0.5, 1.0);
+            rays.push(Ray::new(Point3::new(0.0, 0.0, -2.0), direction));
+        }
+    }
+    rays
+}
+
+/// Fastest of the iterations and the hit count of the last one
+fn measure<F: Fn() -> usize>(function: F) -> (Duration, usize) {
+    let mut best = Duration::from_secs(u64::max_value());
+    let mut hits = 0;
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        hits = function();
+        best = best.min(start.elapsed());
+    }
+    (best, hits)
+}
+
+fn get_nanoseconds_per_ray(duration: Duration, ray_count: usize) -> f64 {
+    (duration.as_secs() as f64 * 1e9 + duration.subsec_nanos() as f64) / ray_count as f64
+}
+
+fn main() {
+    let bvh = BvhIntersector::new(create_models());
+    let rays = create_camera_rays();
+
+    let (single_time, single_hits) = measure(|| rays.iter().filter(|ray| bvh.get_nearest_intersection(ray).is_some()).count());
+    let (packet_time, packet_hits) = measure(|| rays.chunks(PACKET_SIZE).map(|chunk| {
+        bvh.get_nearest_intersections_packet(&RayPacket::new(chunk)).iter().filter(|intersection| intersection.is_some()).count()
+    }).sum());
+    assert_eq!(single_hits, packet_hits, "Packet traversal should hit the same rays as single rays");
+
+    println!("{} rays, {} hits, best of {} iterations", rays.len(), single_hits, ITERATIONS);
+    println!("Single rays:        {:8.1} ns/ray", get_nanoseconds_per_ray(single_time, rays.len()));
+    println!("{} lane packets:     {:8.1} ns/ray", PACKET_SIZE, get_nanoseconds_per_ray(packet_time, rays.len()));
+}
@@ -0,0 +1,479 @@
+use std::sync::{Arc};
+use std::path::{Path, PathBuf};
+use std::io;
+
+use defs::{FloatType, IntType, Point3, Vector3, Matrix4};
+use core::{Color, Model, ModelError, ModelViewModelWrapper, View, ViewError, WorldViewTrait, execute_rendering_tasks};
+use basic::{WorldViewTaskProducer};
+use basic::lightsource::{DotLightSource};
+use basic::image;
+use na::{UnitQuaternion, Translation3};
+
+pub type Rotation = UnitQuaternion<FloatType>;
+
+pub trait Interpolate: Clone {
+    fn interpolate_linear(&self, other: &Self, t: FloatType) -> Self;
+
+    /// Uniform Catmull-Rom spline between from and to, evaluated as the Barry-Goldman pyramid of linear interpolations
+    fn interpolate_spline(previous: &Self, from: &Self, to: &Self, next: &Self, t: FloatType) -> Self {
+        let a1 = previous.interpolate_linear(from, t + 1.0);
+        let a2 = from.interpolate_linear(to, t);
+        let a3 = to.interpolate_linear(next, t - 1.0);
+        let b1 = a1.interpolate_linear(&a2, (t + 1.0) * 0.5);
+        let b2 = a2.interpolate_linear(&a3, t * 0.5);
+        b1.interpolate_linear(&b2, t)
+    }
+}
+
+fn catmull_rom(p0: FloatType, p1: FloatType, p2: FloatType, p3: FloatType, t: FloatType) -> FloatType {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * ((2.0 * p1) +
+           (-p0 + p2) * t +
+           (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2 +
+           (-p0 + 3.0 * p1 - 3.0 * p2 + p3) * t3)
+}
+
+impl Interpolate for FloatType {
+    fn interpolate_linear(&self, other: &Self, t: FloatType) -> Self {
+        self + (other - self) * t
+    }
+
+    fn interpolate_spline(previous: &Self, from: &Self, to: &Self, next: &Self, t: FloatType) -> Self {
+        catmull_rom(*previous, *from, *to, *next, t)
+    }
+}
+
+impl Interpolate for Vector3 {
+    fn interpolate_linear(&self, other: &Self, t: FloatType) -> Self {
+        self + (other - self) * t
+    }
+
+    fn interpolate_spline(previous: &Self, from: &Self, to: &Self, next: &Self, t: FloatType) -> Self {
+        Vector3::new(catmull_rom(previous.x, from.x, to.x, next.x, t),
+                     catmull_rom(previous.y, from.y, to.y, next.y, t),
+                     catmull_rom(previous.z, from.z, to.z, next.z, t))
+    }
+}
+
+impl Interpolate for Point3 {
+    fn interpolate_linear(&self, other: &Self, t: FloatType) -> Self {
+        self + (other - self) * t
+    }
+
+    fn interpolate_spline(previous: &Self, from: &Self, to: &Self, next: &Self, t: FloatType) -> Self {
+        Point3::from(Vector3::interpolate_spline(&previous.coords, &from.coords, &to.coords, &next.coords, t))
+    }
+}
+
+impl Interpolate for Color {
+    fn interpolate_linear(&self, other: &Self, t: FloatType) -> Self {
+        *self + (*other - *self).mul_scalar(&t)
+    }
+
+    fn interpolate_spline(previous: &Self, from: &Self, to: &Self, next: &Self, t: FloatType) -> Self {
+        let (p0, p1, p2, p3) = (previous.get(), from.get(), to.get(), next.get());
+        Color::new(catmull_rom(p0.0, p1.0, p2.0, p3.0, t).max(0.0),
+                   catmull_rom(p0.1, p1.1, p2.1, p3.1, t).max(0.0),
+                   catmull_rom(p0.2, p1.2, p2.2, p3.2, t).max(0.0))
+    }
+}
+
+impl Interpolate for Rotation {
+    fn interpolate_linear(&self, other: &Self, t: FloatType) -> Self {
+        self.slerp(other, t)
+    }
+}
+
+
+#[derive(Debug, Clone, Copy)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    Spline
+}
+
+#[derive(Debug, Clone)]
+pub struct Keyframe<T: Interpolate> {
+    pub time: FloatType,
+    pub value: T
+}
+
+impl<T: Interpolate> Keyframe<T> {
+    pub fn new(time: FloatType, value: T) -> Self {
+        Self {
+            time: time,
+            value: value
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyframeTrack<T: Interpolate> {
+    keyframes: Vec<Keyframe<T>>,
+    interpolation: Interpolation
+}
+
+impl<T: Interpolate> KeyframeTrack<T> {
+    pub fn new(interpolation: Interpolation) -> Self {
+        Self {
+            keyframes: Vec::new(),
+            interpolation: interpolation
+        }
+    }
+
+    pub fn new_constant(value: T) -> Self {
+        let mut result = Self::new(Interpolation::Step);
+        result.add_keyframe(0.0, value);
+        result
+    }
+
+    pub fn add_keyframe(&mut self, time: FloatType, value: T) {
+        let position = self.keyframes.iter().position(|keyframe| keyframe.time > time).unwrap_or(self.keyframes.len());
+        self.keyframes.insert(position, Keyframe::new(time, value));
+    }
+
+    pub fn with_keyframe(mut self, time: FloatType, value: T) -> Self {
+        self.add_keyframe(time, value);
+        self
+    }
+
+    pub fn get_keyframes(&self) -> &Vec<Keyframe<T>> {
+        &self.keyframes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keyframes.is_empty()
+    }
+
+    /// Value of the track at a given time, held constant before the first and after the last keyframe
+    pub fn get_value(&self, time: FloatType) -> Option<T> {
+        if self.keyframes.is_empty() {
+            return None;
+        }
+
+        let last_index = self.keyframes.len() - 1;
+        if time <= self.keyframes[0].time {
+            return Some(self.keyframes[0].value.clone());
+        } else if time >= self.keyframes[last_index].time {
+            return Some(self.keyframes[last_index].value.clone());
+        }
+
+        let next_index = self.keyframes.iter().position(|keyframe| keyframe.time > time).unwrap();
+        let index = next_index - 1;
+        let from = &self.keyframes[index];
+        let to = &self.keyframes[next_index];
+        let t = (time - from.time) / (to.time - from.time);
+
+        match self.interpolation {
+            Interpolation::Step => Some(from.value.clone()),
+            Interpolation::Linear => Some(from.value.interpolate_linear(&to.value, t)),
+            Interpolation::Spline => {
+                // Missing neighbours of the first and last segment are extrapolated, so the end tangents follow the segment
+                let previous = if index > 0 { self.keyframes[index - 1].value.clone() } else { to.value.interpolate_linear(&from.value, 2.0) };
+                let next = if next_index < last_index { self.keyframes[next_index + 1].value.clone() } else { from.value.interpolate_linear(&to.value, 2.0) };
+                Some(T::interpolate_spline(&previous, &from.value, &to.value, &next, t))
+            }
+        }
+    }
+}
+
+
+/// Time dependent model transformation, applied as scale, then rotation, then translation
+#[derive(Debug, Clone)]
+pub struct AnimatedTransform {
+    translation: Option<KeyframeTrack<Vector3>>,
+    rotation: Option<KeyframeTrack<Rotation>>,
+    scale: Option<KeyframeTrack<Vector3>>
+}
+
+impl AnimatedTransform {
+    pub fn new() -> Self {
+        Self {
+            translation: None,
+            rotation: None,
+            scale: None
+        }
+    }
+
+    pub fn set_translation_track(&mut self, track: KeyframeTrack<Vector3>) {
+        self.translation = Some(track);
+    }
+
+    pub fn set_rotation_track(&mut self, track: KeyframeTrack<Rotation>) {
+        self.rotation = Some(track);
+    }
+
+    pub fn set_scale_track(&mut self, track: KeyframeTrack<Vector3>) {
+        self.scale = Some(track);
+    }
+
+    pub fn get_matrix(&self, time: FloatType) -> Matrix4 {
+        let scale = match self.scale.as_ref().and_then(|track| track.get_value(time)) {
+            Some(scaling) => Matrix4::new_nonuniform_scaling(&scaling),
+            None => Matrix4::identity()
+        };
+        let rotation = match self.rotation.as_ref().and_then(|track| track.get_value(time)) {
+            Some(rotation) => rotation.to_homogeneous(),
+            None => Matrix4::identity()
+        };
+        let translation = match self.translation.as_ref().and_then(|track| track.get_value(time)) {
+            Some(translation) => Translation3::from(translation).to_homogeneous(),
+            None => Matrix4::identity()
+        };
+
+        translation * rotation * scale
+    }
+
+    pub fn get_model<T: Model>(&self, model: T, time: FloatType) -> Result<ModelViewModelWrapper<T>, ModelError> {
+        ModelViewModelWrapper::new(model, self.get_matrix(time))
+    }
+
+    /// Model moving from its transformation at shutter open to the one at shutter close, for motion blur
+    pub fn get_motion_model<T: Model>(&self, model: T, shutter_open: FloatType, shutter_close: FloatType) -> Result<ModelViewModelWrapper<T>, ModelError> {
+        ModelViewModelWrapper::new_motion(model, self.get_matrix(shutter_open), self.get_matrix(shutter_close), shutter_open, shutter_close)
+    }
+}
+
+
+enum AnimatedViewOrientation {
+    Direction(KeyframeTrack<Vector3>),
+    Target(KeyframeTrack<Point3>)
+}
+
+impl AnimatedViewOrientation {
+    fn is_empty(&self) -> bool {
+        match *self {
+            AnimatedViewOrientation::Direction(ref direction) => direction.is_empty(),
+            AnimatedViewOrientation::Target(ref target) => target.is_empty()
+        }
+    }
+}
+
+pub struct AnimatedView {
+    position: KeyframeTrack<Point3>,
+    orientation: AnimatedViewOrientation,
+    up: Vector3,
+    width_to_height_ratio: FloatType,
+    screen_height: FloatType,
+    vertical_resolution: IntType
+}
+
+impl AnimatedView {
+    /// None if any of the tracks is empty
+    pub fn new(position: KeyframeTrack<Point3>, direction: KeyframeTrack<Vector3>, up: Vector3, width_to_height_ratio: FloatType, screen_height: FloatType, vertical_resolution: IntType) -> Option<Self> {
+        Self::new_oriented(position, AnimatedViewOrientation::Direction(direction), up, width_to_height_ratio, screen_height, vertical_resolution)
+    }
+
+    /// Camera looking from the position track towards a track of target points, both tracks are evaluated at the time of the view. None if any of the tracks is empty
+    pub fn new_look_at(position: KeyframeTrack<Point3>, target: KeyframeTrack<Point3>, up: Vector3, width_to_height_ratio: FloatType, screen_height: FloatType, vertical_resolution: IntType) -> Option<Self> {
+        Self::new_oriented(position, AnimatedViewOrientation::Target(target), up, width_to_height_ratio, screen_height, vertical_resolution)
+    }
+
+    fn new_oriented(position: KeyframeTrack<Point3>, orientation: AnimatedViewOrientation, up: Vector3, width_to_height_ratio: FloatType, screen_height: FloatType, vertical_resolution: IntType) -> Option<Self> {
+        if position.is_empty() || orientation.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            position: position,
+            orientation: orientation,
+            up: up,
+            width_to_height_ratio: width_to_height_ratio,
+            screen_height: screen_height,
+            vertical_resolution: vertical_resolution
+        })
+    }
+
+    pub fn get_view(&self, time: FloatType) -> Result<View, ViewError> {
+        let position = self.position.get_value(time).expect("AnimatedView: Tracks are checked to be non empty when created");
+        let direction = match self.orientation {
+            AnimatedViewOrientation::Direction(ref direction) => direction.get_value(time).expect("AnimatedView: Tracks are checked to be non empty when created"),
+            AnimatedViewOrientation::Target(ref target) => target.get_value(time).expect("AnimatedView: Tracks are checked to be non empty when created") - position
+        };
+        View::new_unit(position,
+                       direction,
+                       self.up,
+                       self.width_to_height_ratio,
+                       self.screen_height,
+                       self.vertical_resolution)
+    }
+}
+
+
+pub struct AnimatedDotLightSource {
+    color: KeyframeTrack<Color>,
+    intensity: KeyframeTrack<FloatType>,
+    position: KeyframeTrack<Point3>
+}
+
+impl AnimatedDotLightSource {
+    /// None if any of the tracks is empty
+    pub fn new(color: KeyframeTrack<Color>, intensity: KeyframeTrack<FloatType>, position: KeyframeTrack<Point3>) -> Option<Self> {
+        if color.is_empty() || intensity.is_empty() || position.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            color: color,
+            intensity: intensity,
+            position: position
+        })
+    }
+
+    pub fn get_light_source(&self, time: FloatType) -> DotLightSource {
+        DotLightSource::new_natural(self.color.get_value(time).expect("AnimatedDotLightSource: Tracks are checked to be non empty when created"),
+                                    self.intensity.get_value(time).expect("AnimatedDotLightSource: Tracks are checked to be non empty when created"),
+                                    self.position.get_value(time).expect("AnimatedDotLightSource: Tracks are checked to be non empty when created"))
+    }
+}
+
+
+pub struct FrameSequenceRenderer {
+    start_time: FloatType,
+    end_time: FloatType,
+    frame_count: u32,
+    thread_count: usize,
+    output_directory: PathBuf,
+    file_prefix: String
+}
+
+impl FrameSequenceRenderer {
+    pub fn new(start_time: FloatType, end_time: FloatType, frame_count: u32, output_directory: &Path, file_prefix: &str) -> Self {
+        Self {
+            start_time: start_time,
+            end_time: end_time,
+            frame_count: frame_count,
+            thread_count: 1,
+            output_directory: output_directory.to_path_buf(),
+            file_prefix: file_prefix.to_string()
+        }
+    }
+
+    pub fn set_thread_count(&mut self, thread_count: usize) {
+        self.thread_count = thread_count.max(1);
+    }
+
+    pub fn get_frame_time(&self, frame: u32) -> FloatType {
+        if self.frame_count > 1 {
+            self.start_time + (self.end_time - self.start_time) * (frame as FloatType / (self.frame_count - 1) as FloatType)
+        } else {
+            self.start_time
+        }
+    }
+
+    pub fn get_frame_path(&self, frame: u32) -> PathBuf {
+        self.output_directory.join(format!("{}{:04}.ppm", self.file_prefix, frame))
+    }
+
+    /// Renders every frame of the sequence with the scene built for its time and writes them as numbered images
+    pub fn render<F>(&self, scene_factory: F) -> io::Result<Vec<PathBuf>>
+        where F: Fn(FloatType) -> Arc<WorldViewTrait>
+    {
+        let mut result = Vec::with_capacity(self.frame_count as usize);
+        for frame in 0..self.frame_count {
+            let worldview = scene_factory(self.get_frame_time(frame));
+            execute_rendering_tasks(WorldViewTaskProducer::new(Arc::clone(&worldview)), self.thread_count);
+
+            let path = self.get_frame_path(frame);
+            image::save_ppm(worldview.get_scene_buffer(), &path)?;
+            result.push(path);
+        }
+        Ok(result)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use defs::{Vector3, float_consts, TEST_TOLERANCE};
+    use na::{Unit};
+
+    #[test]
+    fn keyframe_track_linear() {
+        let track = KeyframeTrack::new(Interpolation::Linear).with_keyframe(1.0, 2.0)
+                                                             .with_keyframe(0.0, 0.0)
+                                                             .with_keyframe(2.0, 6.0);
+
+        assert_relative_eq!(track.get_value(-1.0).unwrap(), 0.0);
+        assert_relative_eq!(track.get_value(0.5).unwrap(), 1.0);
+        assert_relative_eq!(track.get_value(1.5).unwrap(), 4.0);
+        assert_relative_eq!(track.get_value(3.0).unwrap(), 6.0);
+    }
+
+    #[test]
+    fn keyframe_track_step_and_empty() {
+        let track = KeyframeTrack::new(Interpolation::Step).with_keyframe(0.0, 1.0)
+                                                           .with_keyframe(1.0, 2.0);
+        let empty: KeyframeTrack<FloatType> = KeyframeTrack::new(Interpolation::Linear);
+
+        assert_relative_eq!(track.get_value(0.9).unwrap(), 1.0);
+        assert!(empty.get_value(0.0).is_none());
+    }
+
+    #[test]
+    fn keyframe_track_spline_passes_through_keyframes() {
+        let track = KeyframeTrack::new(Interpolation::Spline).with_keyframe(0.0, Vector3::new(0.0, 0.0, 0.0))
+                                                             .with_keyframe(1.0, Vector3::new(1.0, 2.0, 0.0))
+                                                             .with_keyframe(2.0, Vector3::new(2.0, 0.0, 0.0));
+
+        assert_relative_eq!(track.get_value(1.0).unwrap(), Vector3::new(1.0, 2.0, 0.0));
+        assert_relative_eq!(track.get_value(0.5).unwrap().x, 0.5, epsilon = TEST_TOLERANCE);
+        assert_relative_eq!(track.get_value(1.5).unwrap().x, 1.5, epsilon = TEST_TOLERANCE);
+        assert!(track.get_value(0.5).unwrap().y > 1.0);
+    }
+
+    #[test]
+    fn rotation_track_spline_follows_uniform_rotation() {
+        let axis = Unit::new_normalize(Vector3::new(0.0, 0.0, 1.0));
+        let track = KeyframeTrack::new(Interpolation::Spline).with_keyframe(0.0, Rotation::identity())
+                                                             .with_keyframe(1.0, Rotation::from_axis_angle(&axis, float_consts::FRAC_PI_2))
+                                                             .with_keyframe(2.0, Rotation::from_axis_angle(&axis, float_consts::PI));
+
+        assert_relative_eq!(track.get_value(1.0).unwrap().angle(), float_consts::FRAC_PI_2, epsilon = TEST_TOLERANCE);
+        assert_relative_eq!(track.get_value(0.5).unwrap().angle(), float_consts::FRAC_PI_4, epsilon = TEST_TOLERANCE);
+        assert_relative_eq!(track.get_value(1.5).unwrap().angle(), 3.0 * float_consts::FRAC_PI_4, epsilon = TEST_TOLERANCE);
+    }
+
+    #[test]
+    fn animated_look_at_view_follows_moving_target() {
+        let position = KeyframeTrack::new_constant(Point3::new(0.0, 0.0, 0.0));
+        let target = KeyframeTrack::new(Interpolation::Linear).with_keyframe(0.0, Point3::new(-1.0, 0.0, 1.0))
+                                                              .with_keyframe(1.0, Point3::new(0.0, 0.0, 1.0))
+                                                              .with_keyframe(2.0, Point3::new(1.0, 0.0, 1.0));
+        let animated_view = AnimatedView::new_look_at(position, target, Vector3::new(0.0, 1.0, 0.0), 1.0, 1.0, 2).unwrap();
+
+        for &(time, target_point) in [(0.5, Point3::new(-0.5, 0.0, 1.0)), (1.0, Point3::new(0.0, 0.0, 1.0)), (1.5, Point3::new(0.5, 0.0, 1.0))].iter() {
+            let view = animated_view.get_view(time).unwrap();
+            assert_relative_eq!(*view.get_eye().get_direction(), (target_point - Point3::origin()).normalize(), epsilon = TEST_TOLERANCE);
+        }
+    }
+
+    #[test]
+    fn animated_view_and_light_reject_empty_tracks() {
+        let position = KeyframeTrack::new_constant(Point3::origin());
+        let direction = KeyframeTrack::new_constant(Vector3::new(0.0, 0.0, 1.0));
+        let up = Vector3::new(0.0, 1.0, 0.0);
+
+        assert!(AnimatedView::new(position.clone(), direction.clone(), up, 1.0, 1.0, 2).is_some());
+        assert!(AnimatedView::new(KeyframeTrack::new(Interpolation::Linear), direction, up, 1.0, 1.0, 2).is_none());
+        assert!(AnimatedView::new_look_at(position.clone(), KeyframeTrack::new(Interpolation::Linear), up, 1.0, 1.0, 2).is_none());
+        assert!(AnimatedDotLightSource::new(KeyframeTrack::new_constant(Color::one()), KeyframeTrack::new_constant(1.0), position.clone()).is_some());
+        assert!(AnimatedDotLightSource::new(KeyframeTrack::new_constant(Color::one()), KeyframeTrack::new(Interpolation::Step), position).is_none());
+    }
+
+    #[test]
+    fn animated_transform_rotation_then_translation() {
+        let mut transform = AnimatedTransform::new();
+        transform.set_translation_track(KeyframeTrack::new(Interpolation::Linear).with_keyframe(0.0, Vector3::new(0.0, 0.0, 0.0))
+                                                                                 .with_keyframe(1.0, Vector3::new(2.0, 0.0, 0.0)));
+        transform.set_rotation_track(KeyframeTrack::new(Interpolation::Linear).with_keyframe(0.0, Rotation::identity())
+                                                                              .with_keyframe(1.0, Rotation::from_axis_angle(&Unit::new_normalize(Vector3::new(0.0, 0.0, 1.0)), float_consts::FRAC_PI_2)));
+
+        let point = Point3::from_homogeneous(transform.get_matrix(0.5) * Point3::new(1.0, 0.0, 0.0).to_homogeneous()).unwrap();
+        let rotated = float_consts::FRAC_1_SQRT_2;
+
+        assert_relative_eq!(point, Point3::new(1.0 + rotated, rotated, 0.0), epsilon = TEST_TOLERANCE);
+    }
+}
@@ -0,0 +1,341 @@
+use std::sync::{Arc};
+
+use defs::{FloatType, Point3, Vector3, float_consts};
+use core::{Color, Ray, RayIntersection, RayCaster, IlluminationCaster, ColorCalculator, LightSource, Material};
+use basic::scattering::{SurfaceScattering, get_scattering_probabilities, sample_surface_scattering};
+use rand;
+use rand::{Rng};
+
+/// Surface vertex of a subpath, beta is the throughput from the start of the subpath up to the vertex
+struct PathVertex {
+    intersection: RayIntersection,
+    beta: Color,
+    diffuse_probability: FloatType,
+    /// Probability of the lobe the path continued with at a vertex without diffuse reflection
+    lobe_probability: FloatType,
+    is_delta: bool
+}
+
+impl PathVertex {
+    fn new(intersection: RayIntersection, beta: Color) -> Self {
+        let diffuse_probability = get_scattering_probabilities(&intersection)[0];
+        let is_delta = intersection.get_material().get_diffuse_color().is_none();
+        Self {
+            intersection: intersection,
+            beta: beta,
+            diffuse_probability: diffuse_probability,
+            lobe_probability: 1.0,
+            is_delta: is_delta
+        }
+    }
+
+    fn get_point(&self) -> &Point3 {
+        self.intersection.get_intersection_point()
+    }
+
+    fn get_normal(&self) -> &Vector3 {
+        self.intersection.get_normal_vector()
+    }
+
+    /// Whether the direction leaves the vertex on the side the path arrived from
+    fn is_on_arrival_side(&self, direction: &Vector3) -> bool {
+        let normal = self.get_normal();
+        direction.dot(normal) * self.intersection.get_view_direction().dot(normal) > 0.0
+    }
+
+    /// Solid angle density of scattering towards the direction, which is the same whichever side the path arrived from
+    fn get_scattering_pdf(&self, direction: &Vector3) -> FloatType {
+        if self.is_delta {
+            self.lobe_probability
+        } else {
+            self.diffuse_probability * direction.normalize().dot(self.get_normal()).abs() / float_consts::PI
+        }
+    }
+
+    fn get_area_pdf(&self, direction_pdf: FloatType, from: &Point3) -> FloatType {
+        let to_vertex = self.get_point() - from;
+        let distance_squared = to_vertex.norm_squared();
+        if distance_squared > 0.0 {
+            direction_pdf * to_vertex.normalize().dot(self.get_normal()).abs() / distance_squared
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Balance heuristic weight of the strategy taking the first light_vertex_count vertices from the light.
+/// The arrays hold the area densities of every vertex of the path sampled from the light and from the camera side, the first vertex is on the light and the last is the camera intersection.
+/// A strategy is only counted when neither connected vertex is delta and both subpaths fit in their vertex limits.
+pub fn get_balance_heuristic_weight(light_vertex_count: usize,
+                                    light_pdfs: &[FloatType],
+                                    camera_pdfs: &[FloatType],
+                                    deltas: &[bool],
+                                    light_selection_probability: FloatType,
+                                    max_light_vertices: usize,
+                                    max_camera_vertices: usize) -> FloatType {
+    let last = light_pdfs.len() - 1;
+    let is_valid = |strategy: usize| {
+        !deltas[strategy] && (strategy == 1 || !deltas[strategy - 1]) &&
+        strategy - 1 <= max_light_vertices && last + 1 - strategy <= max_camera_vertices
+    };
+    let get_pdf = |strategy: usize| {
+        let selection = if strategy == 1 { 1.0 } else { light_selection_probability };
+        let light_product: FloatType = light_pdfs[1..strategy].iter().product();
+        let camera_product: FloatType = camera_pdfs[strategy..last].iter().product();
+        selection * light_product * camera_product
+    };
+
+    let strategy_pdf = get_pdf(light_vertex_count);
+    let pdf_sum: FloatType = (1..last + 1).filter(|strategy| is_valid(*strategy)).map(|strategy| get_pdf(strategy)).sum();
+    if pdf_sum > 0.0 {
+        strategy_pdf / pdf_sum
+    } else {
+        0.0
+    }
+}
+
+/// Light tracing subpath, the origin is the point emitting it
+struct LightSubpath {
+    light_index: usize,
+    origin: Point3,
+    vertices: Vec<PathVertex>
+}
+
+/// Bidirectional path tracer connecting a camera subpath starting at the intersection with a light subpath emitted by one of the lights.
+/// Strategies are combined with the balance heuristic. Only the diffuse and fresnel lobes of materials are transported, lights without emission densities are only reached by direct connections
+pub struct BidirectionalColorCalculator {
+    lights: Vec<Arc<LightSource>>,
+    max_camera_vertices: usize,
+    max_light_vertices: usize
+}
+
+impl BidirectionalColorCalculator {
+    /// Lights shared with the illuminator can be passed wrapped into Arc to both
+    pub fn new(lights: Vec<Arc<LightSource>>, max_camera_vertices: usize, max_light_vertices: usize) -> Self {
+        Self {
+            lights: lights,
+            max_camera_vertices: max_camera_vertices.max(1),
+            max_light_vertices: max_light_vertices
+        }
+    }
+
+    pub fn get_max_camera_vertices(&self) -> usize {
+        self.max_camera_vertices
+    }
+
+    pub fn get_max_light_vertices(&self) -> usize {
+        self.max_light_vertices
+    }
+
+    fn extend_subpath(vertices: &mut Vec<PathVertex>, max_vertices: usize, ray_caster: &RayCaster) {
+        let mut random_generator = rand::thread_rng();
+        while vertices.len() < max_vertices {
+            let (ray, beta) = {
+                let last = match vertices.last_mut() {
+                    Some(last) => last,
+                    None => return
+                };
+                match sample_surface_scattering(&last.intersection, random_generator.gen::<FloatType>(), ray_caster) {
+                    SurfaceScattering::Diffuse(ray, weight, _) => (ray, last.beta * weight),
+                    SurfaceScattering::Specular(ray, weight, probability) => {
+                        last.lobe_probability = probability;
+                        (ray, last.beta * weight)
+                    },
+                    SurfaceScattering::Absorbed => return
+                }
+            };
+
+            match ray_caster.cast_model_ray(&ray) {
+                Some(intersection) => vertices.push(PathVertex::new(intersection, beta)),
+                None => return
+            }
+        }
+    }
+
+    fn trace_camera_subpath(&self, intersection: &RayIntersection, ray_caster: &RayCaster) -> Vec<PathVertex> {
+        let mut vertices = vec![PathVertex::new(intersection.clone(), Color::one())];
+        Self::extend_subpath(&mut vertices, self.max_camera_vertices, ray_caster);
+        vertices
+    }
+
+    fn trace_light_subpath(&self, ray_caster: &RayCaster) -> Option<LightSubpath> {
+        if self.lights.is_empty() || self.max_light_vertices == 0 {
+            return None;
+        }
+
+        let mut random_generator = rand::thread_rng();
+        let light_count = self.lights.len();
+        let light_index = ((random_generator.gen::<FloatType>() * light_count as FloatType) as usize).min(light_count - 1);
+        let light = &self.lights[light_index];
+        let emission = light.sample_emission(random_generator.gen::<FloatType>(), random_generator.gen::<FloatType>())?;
+        light.get_emission_direction_pdf(emission.ray.get_direction())?;
+        let beta = emission.power.mul_scalar(&(light_count as FloatType));
+
+        let mut vertices = match ray_caster.cast_model_ray(&emission.ray) {
+            Some(intersection) => vec![PathVertex::new(intersection, beta)],
+            None => Vec::new()
+        };
+        Self::extend_subpath(&mut vertices, self.max_light_vertices, ray_caster);
+
+        Some(LightSubpath {
+            light_index: light_index,
+            origin: *emission.ray.get_origin(),
+            vertices: vertices
+        })
+    }
+
+    /// Weight of the path made of the light vertices followed by the camera vertices in reverse order
+    fn get_path_weight(&self, light: &LightSource, origin: &Point3, light_vertices: &[PathVertex], camera_vertices: &[PathVertex]) -> FloatType {
+        let path: Vec<&PathVertex> = light_vertices.iter().chain(camera_vertices.iter().rev()).collect();
+        let first_vertex = path[0];
+        let emission_pdf = match light.get_emission_direction_pdf(&(first_vertex.get_point() - origin)) {
+            Some(pdf) => pdf,
+            None => return 1.0
+        };
+
+        let length = path.len();
+        let mut light_pdfs = vec![1.0; length + 1];
+        let mut camera_pdfs = vec![1.0; length + 1];
+        let mut deltas = vec![false; length + 1];
+        light_pdfs[1] = first_vertex.get_area_pdf(emission_pdf, origin);
+        for index in 1..length + 1 {
+            let vertex = path[index - 1];
+            deltas[index] = vertex.is_delta;
+            if index >= 2 {
+                let previous = path[index - 2];
+                light_pdfs[index] = vertex.get_area_pdf(previous.get_scattering_pdf(&(vertex.get_point() - previous.get_point())), previous.get_point());
+            }
+            if index < length {
+                let next = path[index];
+                camera_pdfs[index] = vertex.get_area_pdf(next.get_scattering_pdf(&(vertex.get_point() - next.get_point())), next.get_point());
+            }
+        }
+
+        get_balance_heuristic_weight(light_vertices.len() + 1, &light_pdfs, &camera_pdfs, &deltas,
+                                     (self.lights.len() as FloatType).recip(), self.max_light_vertices, self.max_camera_vertices)
+    }
+
+    /// Light arriving at the camera vertex directly from the lights
+    fn get_direct_contribution(&self, camera_vertices: &[PathVertex], ray_caster: &RayCaster) -> Color {
+        let vertex = camera_vertices.last().unwrap();
+        let mut result = Color::zero();
+
+        for light in self.lights.iter() {
+            for (ray, illumination) in light.get_illumination_samples(&vertex.intersection) {
+                if let Some(transmittance) = ray_caster.cast_colored_light_ray(&ray, &vertex.intersection) {
+                    if let Some(color) = Material::get_diffuse_illumination(&vertex.intersection, &illumination.get_shadowed(&transmittance)) {
+                        let weight = self.get_path_weight(&**light, ray.get_origin(), &[], camera_vertices);
+                        result += (vertex.beta * color).mul_scalar(&weight);
+                    }
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Throughput of the edge joining the last light vertex with the last camera vertex, without the subpath weights
+    fn get_connection_contribution(light_vertex: &PathVertex, camera_vertex: &PathVertex, ray_caster: &RayCaster) -> Option<Color> {
+        let connection = camera_vertex.get_point() - light_vertex.get_point();
+        let distance_squared = connection.norm_squared();
+        if distance_squared <= 0.0 || !light_vertex.is_on_arrival_side(&connection) || !camera_vertex.is_on_arrival_side(&-connection) {
+            return None;
+        }
+
+        let direction = connection.normalize();
+        let geometry = direction.dot(light_vertex.get_normal()).abs() * direction.dot(camera_vertex.get_normal()).abs() / distance_squared;
+        let light_diffuse = light_vertex.intersection.get_material().get_diffuse_color()?;
+        let camera_diffuse = camera_vertex.intersection.get_material().get_diffuse_color()?;
+
+        let ray = Ray::new_shadow(*light_vertex.get_point(), connection, &camera_vertex.intersection);
+        let transmittance = ray_caster.cast_colored_light_ray(&ray, &camera_vertex.intersection)?;
+
+        Some((*light_diffuse * *camera_diffuse * transmittance).mul_scalar(&(geometry / (float_consts::PI * float_consts::PI))))
+    }
+}
+
+impl ColorCalculator for BidirectionalColorCalculator {
+    fn get_color(&self, intersection: &RayIntersection, ray_caster: &RayCaster, _illumination_caster: &IlluminationCaster) -> Option<Color> {
+        let camera_vertices = self.trace_camera_subpath(intersection, ray_caster);
+        let light_subpath = self.trace_light_subpath(ray_caster);
+        let mut result = match intersection.get_material().get_emission() {
+            Some(emission) if !intersection.was_inside() => *emission,
+            _ => Color::zero()
+        };
+
+        for camera_count in 1..camera_vertices.len() + 1 {
+            let camera_subpath = &camera_vertices[..camera_count];
+            let camera_vertex = &camera_subpath[camera_count - 1];
+            if camera_vertex.is_delta {
+                continue;
+            }
+
+            result += self.get_direct_contribution(camera_subpath, ray_caster);
+
+            if let Some(ref light_subpath) = light_subpath {
+                let light = &*self.lights[light_subpath.light_index];
+                for light_count in 1..light_subpath.vertices.len() + 1 {
+                    let light_vertex = &light_subpath.vertices[light_count - 1];
+                    if light_vertex.is_delta {
+                        continue;
+                    }
+
+                    if let Some(contribution) = Self::get_connection_contribution(light_vertex, camera_vertex, ray_caster) {
+                        let weight = self.get_path_weight(light, &light_subpath.origin, &light_subpath.vertices[..light_count], camera_subpath);
+                        result += (light_vertex.beta * contribution * camera_vertex.beta).mul_scalar(&weight);
+                    }
+                }
+            }
+        }
+
+        Some(result)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use defs::{TEST_TOLERANCE};
+    use core::{World, Model};
+    use basic::{SimpleIntersector, SimpleColorCalculator, SimpleIlluminator};
+    use basic::model::{SolidPlane};
+    use basic::lightsource::{DotLightSource};
+
+    #[test]
+    fn balance_heuristic_weights_sum_to_one() {
+        let light_pdfs = [1.0, 0.3, 0.7, 0.2, 0.9];
+        let camera_pdfs = [1.0, 0.5, 0.1, 0.8, 1.0];
+        let deltas = [false, false, false, false, false];
+        let weight_sum: FloatType = (1..5).map(|strategy| get_balance_heuristic_weight(strategy, &light_pdfs, &camera_pdfs, &deltas, 0.5, 4, 4)).sum();
+        assert_relative_eq!(weight_sum, 1.0, epsilon = TEST_TOLERANCE);
+
+        let delta_path = [false, false, true, false, false];
+        let valid_sum: FloatType = [1, 4].iter().map(|strategy| get_balance_heuristic_weight(*strategy, &light_pdfs, &camera_pdfs, &delta_path, 0.5, 4, 4)).sum();
+        assert_relative_eq!(valid_sum, 1.0, epsilon = TEST_TOLERANCE);
+        assert_relative_eq!(get_balance_heuristic_weight(1, &light_pdfs, &camera_pdfs, &deltas, 0.5, 0, 4), 1.0, epsilon = TEST_TOLERANCE);
+    }
+
+    #[test]
+    fn bidirectional_floor_matches_direct_illumination() {
+        let floor = Material::new_diffuse(Color::new(0.8, 0.6, 0.4), None);
+        let light: Arc<LightSource> = Arc::new(DotLightSource::new_natural(Color::one(), 3.0, Point3::new(1.0, 0.0, 2.0)));
+        let models: Vec<Box<Model>> = vec![Box::new(SolidPlane::new(floor))];
+        let world = World::new(SimpleIntersector::new(models),
+                               SimpleColorCalculator::new(),
+                               SimpleIlluminator::new(vec![Box::new(Arc::clone(&light))]),
+                               8);
+        let calculator = BidirectionalColorCalculator::new(vec![light], 4, 4);
+
+        let ray = Ray::new(Point3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        let intersection = world.cast_model_ray(&ray).expect("Camera ray should hit the floor");
+        let expected = world.get_illumination_at(&intersection).iter().fold(Color::zero(), |acc, illumination| {
+            acc + Material::get_diffuse_illumination(&intersection, illumination).unwrap()
+        });
+
+        for _ in 0..8 {
+            let color = calculator.get_color(&intersection, &world, &world).unwrap();
+            assert!(color.equal_eps(&expected));
+        }
+    }
+}
@@ -0,0 +1,335 @@
+use core::{Intersector, RayIntersection, Ray, RenderStatistics, Occlusion, BoundingBox, RayPacket, PACKET_SIZE};
+use basic::intersector::{ModelVec};
+use defs::{FloatType};
+use tools::{CompareWithTolerance};
+use std::sync::{Arc};
+
+const MAX_LEAF_MODELS: usize = 2;
+
+enum BvhNodeKind {
+    Leaf(Vec<usize>),
+    Interior(usize, usize)
+}
+
+struct BvhNode {
+    bounding_box: BoundingBox,
+    kind: BvhNodeKind
+}
+
+/// Intersector with a bounding volume hierarchy over the bounded models, unbounded models are tested against every ray
+pub struct BvhIntersector {
+    models: ModelVec,
+    unbounded_models: Vec<usize>,
+    nodes: Vec<BvhNode>,
+    root: Option<usize>,
+    statistics: Option<Arc<RenderStatistics>>,
+}
+
+impl BvhIntersector {
+    pub fn new(models: ModelVec) -> Self {
+        let mut unbounded_models = Vec::new();
+        let mut indexed_boxes = Vec::new();
+        for (index, model) in models.iter().enumerate() {
+            match model.get_bounding_box() {
+                Some(bounding_box) => indexed_boxes.push((index, bounding_box)),
+                None => unbounded_models.push(index)
+            }
+        }
+
+        let mut result = Self {
+            models: models,
+            unbounded_models: unbounded_models,
+            nodes: Vec::with_capacity(indexed_boxes.len() * 2),
+            root: None,
+            statistics: None
+        };
+        result.root = result.build(&mut indexed_boxes);
+        result
+    }
+
+    fn build(&mut self, indexed_boxes: &mut [(usize, BoundingBox)]) -> Option<usize> {
+        if indexed_boxes.is_empty() {
+            return None;
+        }
+
+        let node_index = self.nodes.len();
+        let bounding_box = indexed_boxes[1..].iter().fold(indexed_boxes[0].1, |acc, &(_, ref bounding_box)| acc.get_union(bounding_box));
+        if indexed_boxes.len() <= MAX_LEAF_MODELS {
+            self.nodes.push(BvhNode {
+                bounding_box: bounding_box,
+                kind: BvhNodeKind::Leaf(indexed_boxes.iter().map(|&(index, _)| index).collect())
+            });
+            return Some(node_index);
+        }
+
+        self.nodes.push(BvhNode {
+            bounding_box: bounding_box,
+            kind: BvhNodeKind::Leaf(Vec::new())
+        });
+
+        let axis = bounding_box.get_longest_axis();
+        indexed_boxes.sort_by(|lhs, rhs| {
+            lhs.1.get_center()[axis].partial_cmp(&rhs.1.get_center()[axis]).unwrap_or(::std::cmp::Ordering::Equal)
+        });
+        let (left_boxes, right_boxes) = indexed_boxes.split_at_mut(indexed_boxes.len() / 2);
+        let left = self.build(left_boxes).unwrap();
+        let right = self.build(right_boxes).unwrap();
+        self.nodes[node_index].kind = BvhNodeKind::Interior(left, right);
+
+        Some(node_index)
+    }
+
+    fn record_model_tests(&self, count: usize) {
+        if let Some(ref statistics) = self.statistics {
+            statistics.record_model_tests(count);
+        }
+    }
+
+    /// Calls the visitor with the models of every leaf the ray reaches closer than the distance the visitor returns, stops when the visitor returns None
+    fn traverse<F>(&self, ray: &Ray, max_distance: FloatType, mut visitor: F) where F: FnMut(&[usize]) -> Option<FloatType> {
+        let mut max_distance = max_distance;
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            match node.bounding_box.get_ray_distances(ray) {
+                Some((near, _)) if near <= max_distance => {},
+                _ => continue
+            }
+
+            match node.kind {
+                BvhNodeKind::Leaf(ref model_indices) => {
+                    match visitor(model_indices) {
+                        Some(distance) => max_distance = distance,
+                        None => return
+                    }
+                },
+                BvhNodeKind::Interior(left, right) => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+            }
+        }
+    }
+
+    fn get_nearer(accumulated: Option<RayIntersection>, intersection: Option<RayIntersection>) -> Option<RayIntersection> {
+        match (accumulated, intersection) {
+            (Some(accumulated_intersection), Some(intersection)) => {
+                if intersection.get_distance_to_intersection().less_eps(&accumulated_intersection.get_distance_to_intersection()) {
+                    Some(intersection)
+                } else {
+                    Some(accumulated_intersection)
+                }
+            },
+            (accumulated, intersection) => accumulated.or(intersection)
+        }
+    }
+
+    fn get_distance(intersection: &Option<RayIntersection>) -> FloatType {
+        intersection.as_ref().map_or(FloatType::INFINITY, |intersection| intersection.get_distance_to_intersection())
+    }
+}
+
+impl Intersector for BvhIntersector {
+    fn set_statistics(&mut self, statistics: Option<Arc<RenderStatistics>>) {
+        self.statistics = statistics;
+    }
+
+    fn get_intersections_reverse_ordered(&self, ray: &Ray) -> Vec<RayIntersection> { //Nearest elem is last
+        let mut model_tests = self.unbounded_models.len();
+        let mut result: Vec<RayIntersection> = self.unbounded_models.iter().filter_map(|index| self.models[*index].get_intersection(ray)).collect();
+        self.traverse(ray, FloatType::INFINITY, |model_indices| {
+            model_tests += model_indices.len();
+            result.extend(model_indices.iter().filter_map(|index| self.models[*index].get_intersection(ray)));
+            Some(FloatType::INFINITY)
+        });
+        self.record_model_tests(model_tests);
+
+        result.sort_by(|lhs: &RayIntersection, rhs: &RayIntersection| {
+            lhs.get_distance_to_intersection().compare_eps(&rhs.get_distance_to_intersection()).reverse()
+        });
+
+        result
+    }
+
+    fn get_nearest_intersection(&self, ray: &Ray) -> Option<RayIntersection> {
+        let mut model_tests = self.unbounded_models.len();
+        let mut result = self.unbounded_models.iter().fold(None, |acc, index| Self::get_nearer(acc, self.models[*index].get_intersection(ray)));
+        self.traverse(ray, Self::get_distance(&result), |model_indices| {
+            model_tests += model_indices.len();
+            result = model_indices.iter().fold(result.take(), |acc, index| Self::get_nearer(acc, self.models[*index].get_intersection(ray)));
+            Some(Self::get_distance(&result))
+        });
+        self.record_model_tests(model_tests);
+
+        result
+    }
+
+    fn get_nearest_intersections_packet(&self, packet: &RayPacket) -> Vec<Option<RayIntersection>> {
+        let mut model_tests = self.unbounded_models.len() * packet.len();
+        let mut result: Vec<Option<RayIntersection>> = self.unbounded_models.iter().fold(vec![None; packet.len()], |acc, index| {
+            acc.into_iter().zip(self.models[*index].get_packet_intersections(packet)).map(|(accumulated, intersection)| Self::get_nearer(accumulated, intersection)).collect()
+        });
+
+        let mut best_distances = [FloatType::INFINITY; PACKET_SIZE];
+        for (lane, intersection) in result.iter().enumerate() {
+            best_distances[lane] = Self::get_distance(intersection);
+        }
+
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if !packet.get_box_hits(&node.bounding_box, &best_distances).iter().any(|is_hit| *is_hit) {
+                continue;
+            }
+
+            match node.kind {
+                BvhNodeKind::Leaf(ref model_indices) => {
+                    model_tests += model_indices.len() * packet.len();
+                    for index in model_indices.iter() {
+                        result = result.into_iter().zip(self.models[*index].get_packet_intersections(packet)).map(|(accumulated, intersection)| Self::get_nearer(accumulated, intersection)).collect();
+                    }
+                    for (lane, intersection) in result.iter().enumerate() {
+                        best_distances[lane] = Self::get_distance(intersection);
+                    }
+                },
+                BvhNodeKind::Interior(left, right) => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+            }
+        }
+        self.record_model_tests(model_tests);
+
+        result
+    }
+
+    fn get_occlusion(&self, ray: &Ray, max_distance: FloatType) -> Occlusion {
+        let mut model_tests = 0;
+        let mut result = Occlusion::Unoccluded;
+        let mut test_models = |model_indices: &[usize]| {
+            for index in model_indices.iter() {
+                model_tests += 1;
+                if let Some(intersection) = self.models[*index].get_intersection(ray) {
+                    if intersection.get_distance_to_intersection().less_eps(&max_distance) {
+                        if intersection.get_material().is_opaque() {
+                            return None;
+                        }
+                        result = Occlusion::Transparent;
+                    }
+                }
+            }
+            Some(max_distance)
+        };
+
+        let is_opaque = test_models(&self.unbounded_models).is_none() || {
+            let mut is_opaque = false;
+            self.traverse(ray, max_distance, |model_indices| {
+                let visited = test_models(model_indices);
+                is_opaque = visited.is_none();
+                visited
+            });
+            is_opaque
+        };
+        self.record_model_tests(model_tests);
+
+        if is_opaque {
+            Occlusion::Opaque
+        } else {
+            result
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use defs::{Point3, Vector3};
+    use core::{Material, Color, FresnelIndex, World, RayCaster};
+    use basic::intersector::{SimpleIntersector};
+    use basic::colorcalculator::{SimpleColorCalculator};
+    use basic::illuminator::{SimpleIlluminator};
+    use basic::model::{SolidSphere, SolidPlane, SolidTriangle};
+
+    fn create_test_models() -> ModelVec {
+        let diffuse = Material::new_diffuse(Color::one(), None);
+        let glass = Material::new_refractive(FresnelIndex::new(1.5, 1.5, 1.5), FresnelIndex::zero(), None, None, None);
+        let mut models: ModelVec = vec![Box::new(SolidPlane::new_positioned(diffuse, Point3::new(0.0, -3.0, 0.0), Vector3::y_axis()))];
+        for x in 0..8 {
+            for y in 0..6 {
+                let center = Point3::new(x as FloatType * 2.0 - 8.0, y as FloatType * 2.0 - 6.0, 10.0 + (x + y) as FloatType * 0.5);
+                if (x + y) % 3 == 0 {
+                    models.push(Box::new(SolidTriangle::new(diffuse, center + Vector3::new(-0.8, -0.8, 0.0), center + Vector3::new(0.8, -0.8, 0.0), center + Vector3::new(0.0, 0.8, 0.0))));
+                } else {
+                    models.push(Box::new(SolidSphere::new_positioned(if (x + y) % 3 == 1 { diffuse } else { glass }, center, 0.7)));
+                }
+            }
+        }
+        models
+    }
+
+    fn create_test_rays(width: usize, height: usize) -> Vec<Ray> {
+        let mut rays = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let direction = Vector3::new(x as FloatType / width as FloatType - 0.5, y as FloatType / height as FloatType - 0.5, 1.0);
+                rays.push(Ray::new(Point3::new(0.0, 0.0, -2.0), direction));
+            }
+        }
+        rays
+    }
+
+    fn get_distances(intersections: &[Option<RayIntersection>]) -> Vec<Option<FloatType>> {
+        intersections.iter().map(|intersection| intersection.as_ref().map(|intersection| intersection.get_distance_to_intersection())).collect()
+    }
+
+    #[test]
+    fn bvh_matches_simple_intersector() {
+        let simple = SimpleIntersector::new(create_test_models());
+        let bvh = BvhIntersector::new(create_test_models());
+        let rays = create_test_rays(16, 12);
+
+        for ray in rays.iter() {
+            let expected = simple.get_nearest_intersection(ray);
+            assert_eq!(get_distances(&[bvh.get_nearest_intersection(ray)]), get_distances(&[expected]));
+            assert_eq!(bvh.get_intersections_reverse_ordered(ray).len(), simple.get_intersections_reverse_ordered(ray).len());
+            assert_eq!(bvh.get_occlusion(ray, 12.0), simple.get_occlusion(ray, 12.0));
+        }
+
+        for chunk in rays.chunks(PACKET_SIZE) {
+            let packet = RayPacket::new(chunk);
+            let single: Vec<Option<RayIntersection>> = chunk.iter().map(|ray| simple.get_nearest_intersection(ray)).collect();
+            assert_eq!(get_distances(&bvh.get_nearest_intersections_packet(&packet)), get_distances(&single));
+            assert_eq!(get_distances(&simple.get_nearest_intersections_packet(&packet)), get_distances(&single));
+        }
+    }
+
+    #[test]
+    fn world_statistics_count_bvh_model_tests() {
+        let statistics = Arc::new(RenderStatistics::new());
+        let mut world = World::new(BvhIntersector::new(create_test_models()), SimpleColorCalculator::new(), SimpleIlluminator::new(Vec::new()), 4);
+        world.set_statistics(Some(Arc::clone(&statistics)));
+        let rays = create_test_rays(4, 4);
+
+        for ray in rays.iter() {
+            world.cast_model_ray(ray);
+        }
+
+        let model_tests = statistics.get_summary().model_tests;
+        assert!(model_tests >= rays.len());
+        assert!(model_tests < rays.len() * create_test_models().len());
+    }
+
+    #[test]
+    #[ignore]
+    fn bvh_packet_matches_single_rays_on_full_image() {
+        let bvh = BvhIntersector::new(create_test_models());
+        let rays = create_test_rays(512, 512);
+
+        let single_hits = rays.iter().filter(|ray| bvh.get_nearest_intersection(ray).is_some()).count();
+        let packet_hits: usize = rays.chunks(PACKET_SIZE).map(|chunk| {
+            bvh.get_nearest_intersections_packet(&RayPacket::new(chunk)).iter().filter(|intersection| intersection.is_some()).count()
+        }).sum();
+
+        assert_eq!(single_hits, packet_hits);
+    }
+}
@@ -15,6 +15,13 @@ impl SimpleColorCalculator {
         *material.get_ambient_color().unwrap_or(&Color::zero())
     }
 
+    fn get_emitted_color(&self, intersection: &RayIntersection) -> Color {
+        match intersection.get_material().get_emission() {
+            Some(emission) if !intersection.was_inside() => *emission,
+            _ => Color::zero()
+        }
+    }
+
     fn get_local_color(&self, intersection: &RayIntersection, illuminations: &Vec<LightIntersection>) -> Color { 
         illuminations.iter().fold(Color::zero(), |acc, light_intersection|{
             let diffuse_color = Material::get_diffuse_illumination(intersection, light_intersection);
@@ -40,8 +47,11 @@ impl SimpleColorCalculator {
                         Color::zero()
                     }
                 },
-                Err(RayPropagatorError::RayRelated(_)) => Color::zero(),
-                Err(_) => panic!("Unhandled RayPropagator error!")
+                Err(RayPropagatorError::RayRelated(ref error)) => {
+                    ray_caster.report_ray_error(error);
+                    Color::zero()
+                },
+                Err(RayPropagatorError::NoRefraction) | Err(RayPropagatorError::NotRefractiveMaterial) => Color::zero()
             }
         } else {
             Color::zero()
@@ -64,8 +74,11 @@ impl SimpleColorCalculator {
                         Color::zero()
                     }
                 }
-                Err(RayPropagatorError::RayRelated(_)) => Color::zero(),
-                Err(_) => panic!("Unhandled RayPropagator error!")
+                Err(RayPropagatorError::RayRelated(ref error)) => {
+                    ray_caster.report_ray_error(error);
+                    Color::zero()
+                },
+                Err(RayPropagatorError::NoRefraction) | Err(RayPropagatorError::NotRefractiveMaterial) => Color::zero()
             }
         } else {
             Color::zero()
@@ -77,7 +90,8 @@ impl ColorCalculator for SimpleColorCalculator {
     fn get_color(&self, intersection: &RayIntersection, ray_caster: &RayCaster, illumination_caster: &IlluminationCaster) -> Option<Color> {
         let illuminations = illumination_caster.get_illumination_at(intersection);
 
-        let result =    self.get_ambient_color(intersection) +
+        let result =    self.get_emitted_color(intersection) +
+                        self.get_ambient_color(intersection) +
                         self.get_local_color(intersection, &illuminations) +
                         self.get_reflected_color(intersection, ray_caster) +
                         self.get_refracted_color(intersection, ray_caster);
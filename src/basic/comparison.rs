@@ -0,0 +1,376 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc};
+
+use defs::{FloatType, IntType, Point2Int};
+use core::{Color, ImmutableSceneBuffer, MutableSceneBuffer, BasicSceneBuffer, SceneBufferError, ScreenError, WorldViewTrait, execute_rendering_tasks};
+use basic::{WorldViewTaskProducer};
+use basic::image;
+
+#[derive(Debug)]
+pub enum ComparisonError {
+    NotSameSize,
+    BufferRelated(SceneBufferError)
+}
+
+fn get_pixel(buffer: &ImmutableSceneBuffer, pixel: Point2Int) -> Result<Color, ComparisonError> {
+    match buffer.get_pixel_value(pixel) {
+        Ok(color) => Ok(color.unwrap_or(Color::zero())),
+        Err(error) => Err(ComparisonError::BufferRelated(error))
+    }
+}
+
+/// Colors of both buffers row by row, missing pixels are black
+fn get_pixel_pairs(lhs: &ImmutableSceneBuffer, rhs: &ImmutableSceneBuffer) -> Result<Vec<(Color, Color)>, ComparisonError> {
+    if !lhs.is_same_size_buffer(rhs) {
+        return Err(ComparisonError::NotSameSize);
+    }
+
+    let (width, height) = lhs.get_screen().get_resolution();
+    let mut result: Vec<(Color, Color)> = Vec::with_capacity(width as usize * height as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = Point2Int::new(x, y);
+            result.push((get_pixel(lhs, pixel)?, get_pixel(rhs, pixel)?));
+        }
+    }
+    Ok(result)
+}
+
+/// Absolute difference of the buffers per color component
+pub fn get_difference_buffer(lhs: &ImmutableSceneBuffer, rhs: &ImmutableSceneBuffer) -> Result<BasicSceneBuffer, ComparisonError> {
+    let pairs = get_pixel_pairs(lhs, rhs)?;
+    let (width, _) = lhs.get_screen().get_resolution();
+    let result = BasicSceneBuffer::new(*lhs.get_screen());
+
+    for (index, &(lhs_color, rhs_color)) in pairs.iter().enumerate() {
+        let (lhs_r, lhs_g, lhs_b) = lhs_color.get();
+        let (rhs_r, rhs_g, rhs_b) = rhs_color.get();
+        let difference = Color::new((lhs_r - rhs_r).abs(), (lhs_g - rhs_g).abs(), (lhs_b - rhs_b).abs());
+        let pixel = Point2Int::new(index as IntType % width, index as IntType / width);
+        result.set_pixel_value(pixel, &difference).map_err(ComparisonError::BufferRelated)?;
+    }
+
+    Ok(result)
+}
+
+/// Root mean square error over every color component
+pub fn get_rmse(lhs: &ImmutableSceneBuffer, rhs: &ImmutableSceneBuffer) -> Result<FloatType, ComparisonError> {
+    let pairs = get_pixel_pairs(lhs, rhs)?;
+    if pairs.is_empty() {
+        return Ok(0.0);
+    }
+
+    let squared_error_sum = pairs.iter().fold(0.0, |acc, &(lhs_color, rhs_color)| {
+        let (lhs_r, lhs_g, lhs_b) = lhs_color.get();
+        let (rhs_r, rhs_g, rhs_b) = rhs_color.get();
+        acc + (lhs_r - rhs_r).powi(2) + (lhs_g - rhs_g).powi(2) + (lhs_b - rhs_b).powi(2)
+    });
+
+    Ok((squared_error_sum / (pairs.len() * 3) as FloatType).sqrt())
+}
+
+/// Peak signal to noise ratio in decibels for colors peaking at the given value, infinite for identical buffers
+pub fn get_psnr(lhs: &ImmutableSceneBuffer, rhs: &ImmutableSceneBuffer, peak_value: FloatType) -> Result<FloatType, ComparisonError> {
+    let rmse = get_rmse(lhs, rhs)?;
+    if rmse > 0.0 {
+        Ok(20.0 * (peak_value / rmse).log10())
+    } else {
+        Ok(FloatType::INFINITY)
+    }
+}
+
+const SSIM_WINDOW_SIZE: IntType = 8;
+const SSIM_WINDOW_STRIDE: IntType = 4;
+
+/// Mean structural similarity of the luminances over sliding windows, for colors in [0, 1]
+pub fn get_ssim(lhs: &ImmutableSceneBuffer, rhs: &ImmutableSceneBuffer) -> Result<FloatType, ComparisonError> {
+    let pairs = get_pixel_pairs(lhs, rhs)?;
+    let (width, height) = lhs.get_screen().get_resolution();
+    let luminance = |color: &Color| color.intensity_avg().max(0.0).min(1.0);
+    let c1 = (0.01 as FloatType).powi(2);
+    let c2 = (0.03 as FloatType).powi(2);
+
+    let window_width = SSIM_WINDOW_SIZE.min(width);
+    let window_height = SSIM_WINDOW_SIZE.min(height);
+    let mut ssim_sum = 0.0;
+    let mut window_count = 0;
+
+    let mut top = 0;
+    while top + window_height <= height {
+        let mut left = 0;
+        while left + window_width <= width {
+            let mut samples: Vec<(FloatType, FloatType)> = Vec::with_capacity((window_width * window_height) as usize);
+            for y in top..top + window_height {
+                for x in left..left + window_width {
+                    let (ref lhs_color, ref rhs_color) = pairs[(y * width + x) as usize];
+                    samples.push((luminance(lhs_color), luminance(rhs_color)));
+                }
+            }
+
+            let count = samples.len() as FloatType;
+            let lhs_mean = samples.iter().map(|sample| sample.0).sum::<FloatType>() / count;
+            let rhs_mean = samples.iter().map(|sample| sample.1).sum::<FloatType>() / count;
+            let (lhs_variance, rhs_variance, covariance) = samples.iter().fold((0.0, 0.0, 0.0), |acc, sample| {
+                (acc.0 + (sample.0 - lhs_mean).powi(2),
+                 acc.1 + (sample.1 - rhs_mean).powi(2),
+                 acc.2 + (sample.0 - lhs_mean) * (sample.1 - rhs_mean))
+            });
+            let (lhs_variance, rhs_variance, covariance) = (lhs_variance / count, rhs_variance / count, covariance / count);
+
+            ssim_sum += ((2.0 * lhs_mean * rhs_mean + c1) * (2.0 * covariance + c2)) /
+                        ((lhs_mean.powi(2) + rhs_mean.powi(2) + c1) * (lhs_variance + rhs_variance + c2));
+            window_count += 1;
+            left += SSIM_WINDOW_STRIDE;
+        }
+        top += SSIM_WINDOW_STRIDE;
+    }
+
+    if window_count > 0 {
+        Ok(ssim_sum / window_count as FloatType)
+    } else {
+        Ok(1.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageComparison {
+    pub rmse: FloatType,
+    pub psnr: FloatType,
+    pub ssim: FloatType
+}
+
+impl ImageComparison {
+    /// Metrics of the buffers for colors in [0, 1]
+    pub fn new(lhs: &ImmutableSceneBuffer, rhs: &ImmutableSceneBuffer) -> Result<Self, ComparisonError> {
+        Ok(Self {
+            rmse: get_rmse(lhs, rhs)?,
+            psnr: get_psnr(lhs, rhs, 1.0)?,
+            ssim: get_ssim(lhs, rhs)?
+        })
+    }
+}
+
+impl fmt::Display for ImageComparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RMSE {:.6}, PSNR {:.2} dB, SSIM {:.4}", self.rmse, self.psnr, self.ssim)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RegressionTolerance {
+    pub max_rmse: FloatType,
+    pub min_psnr: FloatType,
+    pub min_ssim: FloatType
+}
+
+impl RegressionTolerance {
+    pub fn new(max_rmse: FloatType, min_psnr: FloatType, min_ssim: FloatType) -> Self {
+        Self {
+            max_rmse: max_rmse,
+            min_psnr: min_psnr,
+            min_ssim: min_ssim
+        }
+    }
+
+    pub fn is_satisfied_by(&self, comparison: &ImageComparison) -> bool {
+        comparison.rmse <= self.max_rmse && comparison.psnr >= self.min_psnr && comparison.ssim >= self.min_ssim
+    }
+}
+
+#[derive(Debug)]
+pub enum RegressionError {
+    MissingReference(PathBuf),
+    IoRelated(io::Error),
+    ComparisonRelated(ComparisonError),
+    ScreenRelated(ScreenError),
+    /// Metrics outside of the tolerance, with the directory the rendered and difference images were written to
+    ToleranceExceeded(ImageComparison, PathBuf)
+}
+
+impl From<io::Error> for RegressionError {
+    fn from(error: io::Error) -> Self {
+        RegressionError::IoRelated(error)
+    }
+}
+
+impl From<ComparisonError> for RegressionError {
+    fn from(error: ComparisonError) -> Self {
+        RegressionError::ComparisonRelated(error)
+    }
+}
+
+impl From<ScreenError> for RegressionError {
+    fn from(error: ScreenError) -> Self {
+        RegressionError::ScreenRelated(error)
+    }
+}
+
+/// Compares rendered buffers against the `<name>.exr` references, writing `<name>_actual.exr` and `<name>_diff.exr` to the output directory on failure
+pub struct RegressionHarness {
+    reference_directory: PathBuf,
+    output_directory: PathBuf,
+    tolerance: RegressionTolerance,
+    update_references: bool
+}
+
+impl RegressionHarness {
+    pub fn new(reference_directory: &Path, output_directory: &Path, tolerance: RegressionTolerance) -> Self {
+        Self {
+            reference_directory: reference_directory.to_path_buf(),
+            output_directory: output_directory.to_path_buf(),
+            tolerance: tolerance,
+            update_references: false
+        }
+    }
+
+    /// Missing or failing references are overwritten by the rendered buffer instead of failing the check
+    pub fn set_update_references(&mut self, update_references: bool) {
+        self.update_references = update_references;
+    }
+
+    pub fn get_reference_path(&self, name: &str) -> PathBuf {
+        self.reference_directory.join(format!("{}.exr", name))
+    }
+
+    fn save_reference(&self, name: &str, rendered: &ImmutableSceneBuffer) -> Result<(), RegressionError> {
+        fs::create_dir_all(&self.reference_directory)?;
+        image::save_exr(&[("", rendered)], &self.get_reference_path(name))?;
+        Ok(())
+    }
+
+    pub fn check(&self, name: &str, rendered: &ImmutableSceneBuffer) -> Result<ImageComparison, RegressionError> {
+        let reference_path = self.get_reference_path(name);
+        if !reference_path.exists() {
+            if self.update_references {
+                self.save_reference(name, rendered)?;
+                return ImageComparison::new(rendered, rendered).map_err(RegressionError::from);
+            }
+            return Err(RegressionError::MissingReference(reference_path));
+        }
+
+        let reference = image::load_exr(&reference_path)?.into_iter()
+                                                          .find(|&(ref layer_name, _)| layer_name.is_empty())
+                                                          .map(|(_, reference_image)| reference_image.to_scene_buffer())
+                                                          .ok_or(RegressionError::MissingReference(reference_path))??;
+        let comparison = ImageComparison::new(rendered, &reference)?;
+
+        if self.tolerance.is_satisfied_by(&comparison) {
+            Ok(comparison)
+        } else if self.update_references {
+            self.save_reference(name, rendered)?;
+            Ok(comparison)
+        } else {
+            fs::create_dir_all(&self.output_directory)?;
+            let difference = get_difference_buffer(rendered, &reference)?;
+            image::save_exr(&[("", rendered)], &self.output_directory.join(format!("{}_actual.exr", name)))?;
+            image::save_exr(&[("", &difference)], &self.output_directory.join(format!("{}_diff.exr", name)))?;
+            Err(RegressionError::ToleranceExceeded(comparison, self.output_directory.clone()))
+        }
+    }
+
+    /// Renders the scene then checks it against its reference
+    pub fn check_scene(&self, name: &str, worldview: Arc<WorldViewTrait>, thread_count: usize) -> Result<ImageComparison, RegressionError> {
+        execute_rendering_tasks(WorldViewTaskProducer::new(Arc::clone(&worldview)), thread_count);
+        self.check(name, worldview.get_scene_buffer())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use defs::{Point3, Vector3, TEST_TOLERANCE};
+    use core::{Screen, View, Material, World, WorldView};
+    use basic::{SimpleIntersector, SimpleColorCalculator, SimpleIlluminator};
+    use basic::model::{SolidSphere};
+    use basic::lightsource::{DotLightSource};
+
+    fn create_buffer(color_at: &Fn(IntType, IntType) -> Color) -> BasicSceneBuffer {
+        let screen = Screen::new(Point3::origin(), Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 1.0, 0.0), 1.0, 1.0, 16, 16).unwrap();
+        let buffer = BasicSceneBuffer::new(screen);
+        for y in 0..16 {
+            for x in 0..16 {
+                buffer.set_pixel_value(Point2Int::new(x, y), &color_at(x, y)).unwrap();
+            }
+        }
+        buffer
+    }
+
+    /// A diffuse unit sphere lit from the upper left of the camera
+    fn create_reference_scene(light_color: Color) -> Arc<WorldViewTrait> {
+        let sphere = SolidSphere::new(Material::new_diffuse(Color::one(), None));
+        let light = DotLightSource::new_natural(light_color, 50.0, Point3::new(-3.0, 3.0, -6.0));
+        let world = World::new(SimpleIntersector::new(vec![Box::new(sphere)]),
+                               SimpleColorCalculator::new(),
+                               SimpleIlluminator::new(vec![Box::new(light)]),
+                               3);
+        let view = View::new_unit(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 1.0, 0.0), 1.0, 1.0, 16).unwrap();
+        Arc::new(WorldView::new(world, view))
+    }
+
+    fn checkerboard(x: IntType, y: IntType) -> Color {
+        if (x / 2 + y / 2) % 2 == 0 { Color::one() } else { Color::zero() }
+    }
+
+    #[test]
+    fn comparison_metrics() {
+        let reference = create_buffer(&checkerboard);
+        let offset = create_buffer(&|x, y| checkerboard(x, y) + Color::new(0.1, 0.1, 0.1));
+        let inverted = create_buffer(&|x, y| Color::one() - checkerboard(x, y));
+
+        let same = ImageComparison::new(&reference, &reference).unwrap();
+        assert_relative_eq!(same.rmse, 0.0);
+        assert!(same.psnr.is_infinite());
+        assert_relative_eq!(same.ssim, 1.0);
+
+        assert_relative_eq!(get_rmse(&reference, &offset).unwrap(), 0.1, epsilon = TEST_TOLERANCE);
+        assert_relative_eq!(get_psnr(&reference, &offset, 1.0).unwrap(), 20.0, epsilon = TEST_TOLERANCE);
+        assert!(get_ssim(&reference, &inverted).unwrap() < 0.0);
+
+        let difference = get_difference_buffer(&reference, &offset).unwrap();
+        assert!(difference.get_pixel_value(Point2Int::new(3, 5)).unwrap().unwrap().equal_eps(&Color::new(0.1, 0.1, 0.1)));
+    }
+
+    #[test]
+    fn regression_harness_writes_diff_on_failure() {
+        let directory = env::temp_dir().join(format!("rtrace_regression_test_{}", ::uuid::Uuid::new_v4()));
+        let mut harness = RegressionHarness::new(&directory.join("reference"), &directory.join("output"), RegressionTolerance::new(0.01, 40.0, 0.99));
+        let reference = create_buffer(&checkerboard);
+        let changed = create_buffer(&|x, y| if x == 0 { Color::new(0.5, 0.0, 0.0) } else { checkerboard(x, y) });
+
+        assert!(match harness.check("checkerboard", &reference) { Err(RegressionError::MissingReference(_)) => true, _ => false });
+        harness.set_update_references(true);
+        harness.check("checkerboard", &reference).unwrap();
+        harness.set_update_references(false);
+
+        assert!(harness.check("checkerboard", &reference).is_ok());
+        assert!(match harness.check("checkerboard", &changed) { Err(RegressionError::ToleranceExceeded(_, _)) => true, _ => false });
+        assert!(directory.join("output").join("checkerboard_diff.exr").exists());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn regression_harness_checks_rendered_reference_scene() {
+        let directory = env::temp_dir().join(format!("rtrace_regression_scene_test_{}", ::uuid::Uuid::new_v4()));
+        let references = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("references");
+        let harness = RegressionHarness::new(&references, &directory, RegressionTolerance::new(0.01, 40.0, 0.99));
+
+        let comparison = harness.check_scene("lit_sphere", create_reference_scene(Color::one()), 2).expect("Render should match the committed reference");
+        assert!(comparison.psnr > 60.0);
+        assert!(!directory.exists());
+
+        assert!(match harness.check_scene("lit_sphere", create_reference_scene(Color::new(1.0, 0.0, 0.0)), 2) {
+            Err(RegressionError::ToleranceExceeded(_, _)) => true,
+            _ => false
+        });
+        assert!(directory.join("lit_sphere_actual.exr").exists());
+        assert!(directory.join("lit_sphere_diff.exr").exists());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,211 @@
+use std::sync::atomic::{AtomicIsize, Ordering};
+
+use defs::{FloatType, IntType};
+use core::{RayCaster, IlluminationCaster, ColorCalculator, RayIntersection, Ray, RayError, Color, LightIntersection, LightLinkSet};
+use basic::{SimpleColorCalculator};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugRenderMode {
+    Normals,
+    /// Heatmap of the distance to the intersection, saturated at the given distance
+    Depth(FloatType),
+    /// Heatmap of the number of secondary rays in a row spawned by the base calculator, saturated at the given count
+    Bounces(IntType),
+    ModelId,
+    InsideOutside
+}
+
+/// Blue to green to red gradient of a value between 0 and 1
+pub fn get_heat_color(value: FloatType) -> Color {
+    let value = value.max(0.0).min(1.0);
+    if value < 0.5 {
+        Color::new(0.0, value * 2.0, 1.0 - value * 2.0)
+    } else {
+        Color::new(value * 2.0 - 1.0, 2.0 - value * 2.0, 0.0)
+    }
+}
+
+struct BounceCountingCaster<'caster> {
+    ray_caster: &'caster RayCaster,
+    illumination_caster: &'caster IlluminationCaster,
+    base_calculator: &'caster ColorCalculator,
+    max_depth: AtomicIsize
+}
+
+impl<'caster> RayCaster for BounceCountingCaster<'caster> {
+    fn cast_ray(&self, ray: &Ray) -> Option<Color> {
+        self.max_depth.fetch_max(ray.get_depth_counter() as isize, Ordering::Relaxed);
+        match self.ray_caster.cast_model_ray(ray) {
+            Some(intersection) => self.base_calculator.get_color(&intersection, self, self),
+            None => None
+        }
+    }
+
+    fn cast_colored_light_ray(&self, ray: &Ray, intersection: &RayIntersection) -> Option<Color> {
+        self.ray_caster.cast_colored_light_ray(ray, intersection)
+    }
+
+    fn cast_linked_light_ray(&self, ray: &Ray, intersection: &RayIntersection, shadow_casters: &LightLinkSet) -> Option<Color> {
+        self.ray_caster.cast_linked_light_ray(ray, intersection, shadow_casters)
+    }
+
+    fn cast_model_ray(&self, ray: &Ray) -> Option<RayIntersection> {
+        self.ray_caster.cast_model_ray(ray)
+    }
+
+    fn report_ray_error(&self, error: &RayError) {
+        self.ray_caster.report_ray_error(error)
+    }
+}
+
+impl<'caster> IlluminationCaster for BounceCountingCaster<'caster> {
+    fn get_illumination_at(&self, intersection: &RayIntersection) -> Vec<LightIntersection> {
+        self.illumination_caster.get_illumination_at(intersection)
+    }
+}
+
+/// ColorCalculator visualizing a property of the intersection instead of shading it
+pub struct DebugColorCalculator {
+    mode: DebugRenderMode,
+    base_calculator: Box<ColorCalculator>
+}
+
+impl DebugColorCalculator {
+    pub fn new(mode: DebugRenderMode) -> Self {
+        Self::new_with_base(mode, Box::new(SimpleColorCalculator::new()))
+    }
+
+    /// The base calculator spawns the rays counted by the bounce mode
+    pub fn new_with_base(mode: DebugRenderMode, base_calculator: Box<ColorCalculator>) -> Self {
+        Self {
+            mode: mode,
+            base_calculator: base_calculator
+        }
+    }
+
+    pub fn get_mode(&self) -> DebugRenderMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: DebugRenderMode) {
+        self.mode = mode;
+    }
+
+    fn get_bounce_count(&self, intersection: &RayIntersection, ray_caster: &RayCaster, illumination_caster: &IlluminationCaster) -> IntType {
+        let start_depth = intersection.get_intersector_ray().get_depth_counter();
+        let counting_caster = BounceCountingCaster {
+            ray_caster: ray_caster,
+            illumination_caster: illumination_caster,
+            base_calculator: &*self.base_calculator,
+            max_depth: AtomicIsize::new(start_depth as isize)
+        };
+        self.base_calculator.get_color(intersection, &counting_caster, &counting_caster);
+
+        counting_caster.max_depth.load(Ordering::Relaxed) as IntType - start_depth
+    }
+
+    fn get_model_id_color(intersection: &RayIntersection) -> Color {
+        match intersection.get_model_identifier() {
+            Some(identifier) => {
+                let bytes = identifier.as_bytes();
+                let channel = |offset: usize| {
+                    (bytes[offset] ^ bytes[offset + 3] ^ bytes[offset + 6] ^ bytes[offset + 9]) as FloatType / 255.0
+                };
+                Color::new(channel(0), channel(1), channel(2))
+            },
+            None => Color::new(0.5, 0.5, 0.5)
+        }
+    }
+}
+
+impl ColorCalculator for DebugColorCalculator {
+    fn get_color(&self, intersection: &RayIntersection, ray_caster: &RayCaster, illumination_caster: &IlluminationCaster) -> Option<Color> {
+        match self.mode {
+            DebugRenderMode::Normals => {
+                let normal = intersection.get_normal_vector();
+                Some(Color::new((normal.x + 1.0) * 0.5, (normal.y + 1.0) * 0.5, (normal.z + 1.0) * 0.5))
+            },
+            DebugRenderMode::Depth(max_distance) => {
+                Some(get_heat_color(intersection.get_distance_to_intersection() / max_distance))
+            },
+            DebugRenderMode::Bounces(max_bounces) => {
+                let bounces = self.get_bounce_count(intersection, ray_caster, illumination_caster);
+                Some(get_heat_color(bounces as FloatType / max_bounces.max(1) as FloatType))
+            },
+            DebugRenderMode::ModelId => Some(Self::get_model_id_color(intersection)),
+            DebugRenderMode::InsideOutside => {
+                if intersection.was_inside() {
+                    Some(Color::new(1.0, 0.0, 0.0))
+                } else {
+                    Some(Color::new(0.0, 1.0, 0.0))
+                }
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use defs::{Point3, Vector3};
+    use core::{Material, FresnelIndex};
+
+    struct MirrorRoomCaster {
+
+    }
+
+    impl RayCaster for MirrorRoomCaster {
+        fn cast_ray(&self, _ray: &Ray) -> Option<Color> {
+            None
+        }
+
+        fn cast_colored_light_ray(&self, _ray: &Ray, _intersection: &RayIntersection) -> Option<Color> {
+            None
+        }
+
+        fn cast_model_ray(&self, ray: &Ray) -> Option<RayIntersection> {
+            if ray.get_depth_counter() < 3 {
+                Some(create_mirror_intersection(ray))
+            } else {
+                None
+            }
+        }
+    }
+
+    impl IlluminationCaster for MirrorRoomCaster {
+        fn get_illumination_at(&self, _intersection: &RayIntersection) -> Vec<LightIntersection> {
+            Vec::new()
+        }
+    }
+
+    fn create_mirror_intersection(ray: &Ray) -> RayIntersection {
+        let mirror = Material::new_reflective(FresnelIndex::one().mul_scalar(&0.2), FresnelIndex::one().mul_scalar(&3.0), None, None, None);
+        let point = ray.get_origin() + ray.get_direction() * 2.0;
+        RayIntersection::new(-ray.get_direction(), point, ray, mirror, false).unwrap()
+    }
+
+    #[test]
+    fn debug_bounce_count() {
+        let caster = MirrorRoomCaster {};
+        let ray = Ray::new(Point3::origin(), Vector3::new(0.0, 0.0, 1.0));
+        let intersection = caster.cast_model_ray(&ray).unwrap();
+        let calculator = DebugColorCalculator::new(DebugRenderMode::Bounces(3));
+
+        assert_eq!(calculator.get_bounce_count(&intersection, &caster, &caster), 3);
+        assert!(calculator.get_color(&intersection, &caster, &caster).unwrap().equal_eps(&get_heat_color(1.0)));
+    }
+
+    #[test]
+    fn debug_inside_and_normal_colors() {
+        let ray = Ray::new(Point3::origin(), Vector3::new(0.0, 0.0, 1.0));
+        let intersection = RayIntersection::new(Vector3::new(0.0, 0.0, -1.0), Point3::new(0.0, 0.0, 1.0), &ray, Material::new_useless(), true).unwrap();
+        let caster = MirrorRoomCaster {};
+
+        let normal_color = DebugColorCalculator::new(DebugRenderMode::Normals).get_color(&intersection, &caster, &caster).unwrap();
+        let inside_color = DebugColorCalculator::new(DebugRenderMode::InsideOutside).get_color(&intersection, &caster, &caster).unwrap();
+
+        assert!(normal_color.equal_eps(&Color::new(0.5, 0.5, 0.0)));
+        assert!(inside_color.equal_eps(&Color::new(1.0, 0.0, 0.0)));
+    }
+}
@@ -0,0 +1,168 @@
+use core::{View, Model, LightSource, World, WorldView};
+use basic::{SimpleWorld, SimpleIntersector, SimpleColorCalculator, SimpleIlluminator, ModelVec, LightSourceVec};
+use basic::model::{SolidSphere, SolidPlane};
+use basic::lightsource::{DotLightSource, SpotLightSource, DirectionalLightSource};
+
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
+pub enum ModelDocument {
+    Sphere(SolidSphere),
+    Plane(SolidPlane)
+}
+
+impl ModelDocument {
+    fn into_model(self) -> Box<Model> {
+        match self {
+            ModelDocument::Sphere(sphere) => Box::new(sphere),
+            ModelDocument::Plane(plane) => Box::new(plane)
+        }
+    }
+}
+
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
+pub enum LightDocument {
+    Dot(DotLightSource),
+    Spot(SpotLightSource),
+    Directional(DirectionalLightSource)
+}
+
+impl LightDocument {
+    fn into_light_source(self) -> Box<LightSource> {
+        match self {
+            LightDocument::Dot(light) => Box::new(light),
+            LightDocument::Spot(light) => Box::new(light),
+            LightDocument::Directional(light) => Box::new(light)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
+pub struct RenderSettings {
+    pub ray_depth_limit: i32,
+    pub thread_count: usize
+}
+
+impl RenderSettings {
+    pub fn new(ray_depth_limit: i32, thread_count: usize) -> Self {
+        Self {
+            ray_depth_limit: ray_depth_limit,
+            thread_count: thread_count
+        }
+    }
+}
+
+/// Self-contained description of a scene made of the basic models and lights, saved and versioned with the serde-serialize feature
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
+pub struct SceneDocument {
+    pub view: View,
+    pub models: Vec<ModelDocument>,
+    pub lights: Vec<LightDocument>,
+    pub settings: RenderSettings
+}
+
+impl SceneDocument {
+    pub fn new(view: View, settings: RenderSettings) -> Self {
+        Self {
+            view: view,
+            models: Vec::new(),
+            lights: Vec::new(),
+            settings: settings
+        }
+    }
+
+    pub fn with_model(mut self, model: ModelDocument) -> Self {
+        self.models.push(model);
+        self
+    }
+
+    pub fn with_light(mut self, light: LightDocument) -> Self {
+        self.lights.push(light);
+        self
+    }
+
+    pub fn into_world(self) -> (SimpleWorld, View) {
+        let models: ModelVec = self.models.into_iter().map(ModelDocument::into_model).collect();
+        let lights: LightSourceVec = self.lights.into_iter().map(LightDocument::into_light_source).collect();
+
+        let illuminator = SimpleIlluminator::new_with_emitters(lights, &models);
+        (World::new(SimpleIntersector::new(models), SimpleColorCalculator::new(), illuminator, self.settings.ray_depth_limit),
+         self.view)
+    }
+
+    pub fn into_worldview(self) -> WorldView<SimpleWorld> {
+        let (world, view) = self.into_world();
+        WorldView::new(world, view)
+    }
+}
+
+
+#[cfg(all(test, feature = "serde-serialize"))]
+mod tests {
+    use super::*;
+    use defs::{Point3, Vector3, Point2Int};
+    use core::{Color, Material, FresnelIndex, Scene};
+    use serde_json;
+
+    fn create_test_document() -> SceneDocument {
+        let view = View::new_unit(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 1.0, 0.0), 1.0, 1.0, 4).unwrap();
+        let glass = Material::new_refractive(FresnelIndex::new(1.5, 1.5, 1.5), FresnelIndex::zero(), None, None, None);
+        let dot_light = DotLightSource::new_natural(Color::one(), 10.0, Point3::new(0.0, 5.0, -5.0));
+        let spot_light = SpotLightSource::new(DotLightSource::new_natural(Color::new(1.0, 0.5, 0.5), 5.0, Point3::new(0.0, 0.0, -5.0)),
+                                              Vector3::new(0.0, 0.0, 1.0),
+                                              0.5);
+
+        SceneDocument::new(view, RenderSettings::new(5, 4))
+            .with_model(ModelDocument::Sphere(SolidSphere::new(glass)))
+            .with_model(ModelDocument::Plane(SolidPlane::new(Material::new_diffuse(Color::one(), None))))
+            .with_light(LightDocument::Dot(dot_light))
+            .with_light(LightDocument::Spot(spot_light))
+            .with_light(LightDocument::Directional(DirectionalLightSource::new(Color::one(), 0.5, Vector3::new(1.0, -1.0, 1.0), 50.0)))
+    }
+
+    #[test]
+    fn scene_document_round_trip() {
+        let json = serde_json::to_string(&create_test_document()).expect("Document should serialize");
+        let restored: SceneDocument = serde_json::from_str(&json).expect("Document should deserialize");
+
+        assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+        assert_eq!(restored.models.len(), 2);
+        assert_eq!(restored.lights.len(), 3);
+        assert_eq!(restored.settings.ray_depth_limit, 5);
+    }
+
+    #[test]
+    fn scene_document_renders_same_after_round_trip() {
+        let json = serde_json::to_string(&create_test_document()).unwrap();
+        let original = create_test_document().into_worldview();
+        let restored = serde_json::from_str::<SceneDocument>(&json).unwrap().into_worldview();
+
+        for pixel in [Point2Int::new(0, 0), Point2Int::new(2, 2)].iter() {
+            let original_color = original.get_pixel_color(*pixel).ok();
+            let restored_color = restored.get_pixel_color(*pixel).ok();
+            assert_eq!(original_color.is_some(), restored_color.is_some());
+            if let (Some(original_color), Some(restored_color)) = (original_color, restored_color) {
+                assert!(original_color.equal_eps(&restored_color));
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_screen_is_rejected_when_loading() {
+        let view = View::new_unit(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 1.0, 0.0), 1.0, 1.0, 4).unwrap();
+        let json = serde_json::to_string(&view).unwrap();
+        let invalid_json = json.replace("\"vertical_resolution\":4", "\"vertical_resolution\":0");
+
+        assert_ne!(json, invalid_json);
+        assert!(serde_json::from_str::<View>(&json).is_ok());
+        assert!(serde_json::from_str::<View>(&invalid_json).is_err());
+    }
+
+    #[test]
+    fn material_and_color_round_trip() {
+        let material = Material::new_shiny(Color::new(0.1, 0.2, 0.3), (Color::one(), 20.0), Some(Color::zero()));
+        let json = serde_json::to_string(&material).unwrap();
+        let restored: Material = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+    }
+}
@@ -1,36 +1,259 @@
-use core::{RayIntersection, RayCaster, LightIntersection, LightSource, Illuminator};
+use core::{RayIntersection, RayCaster, LightIntersection, LightSource, LightLinking, Illuminator, Ray, Color};
+use basic::{ModelVec};
+use basic::lightbvh::{LightBvh};
+use defs::{FloatType};
+use rand;
+use rand::{Rng};
 
 pub type LightSourceVec = Vec<Box<LightSource>>;
 
 pub struct SimpleIlluminator {
-    lights : LightSourceVec
+    lights : LightSourceVec,
+    links: Vec<LightLinking>
 }
 
 impl SimpleIlluminator {
     pub fn new(lights: LightSourceVec) -> Self {
-        Self {  lights: lights}
+        let links = lights.iter().map(|_| LightLinking::new_unlinked()).collect();
+        Self {  lights: lights,
+                links: links}
+    }
+
+    /// The lights extended with the light sources of the emissive models
+    pub fn new_with_emitters(lights: LightSourceVec, models: &ModelVec) -> Self {
+        let mut lights = lights;
+        lights.extend(models.iter().filter_map(|model| model.get_light_source()));
+        Self::new(lights)
+    }
+
+    pub fn add_light_source(&mut self, light: Box<LightSource>) {
+        self.add_linked_light_source(light, LightLinking::new_unlinked());
+    }
+
+    pub fn add_linked_light_source(&mut self, light: Box<LightSource>, linking: LightLinking) {
+        self.lights.push(light);
+        self.links.push(linking);
+    }
+
+    /// Linking of the light at the index in the order the lights were given
+    pub fn get_light_linking(&self, light_index: usize) -> Option<&LightLinking> {
+        self.links.get(light_index)
+    }
+
+    pub fn get_light_linking_mut(&mut self, light_index: usize) -> Option<&mut LightLinking> {
+        self.links.get_mut(light_index)
     }
 }
 
 impl Illuminator for SimpleIlluminator {
     fn get_illumination_at(&self, intersection: &RayIntersection, illumination_caster: &RayCaster) -> Vec<LightIntersection> {
-        self.lights.iter().filter_map(|light| {
-            match light.get_ray_to_intersection(intersection) {
-                None => None,
-                Some(ray) => {
-                    match illumination_caster.cast_colored_light_ray(&ray, intersection) {
-                        None => None,
-                        Some(illumintaion_shadowing) => {
-                            match light.get_illumination_at(intersection) {
-                                None => None,
-                                Some(illumination) => {
-                                    Some(illumination.get_shadowed(&illumintaion_shadowing))
-                                }
-                            }
-                        }
-                    }
+        self.lights.iter().zip(self.links.iter()).filter(|&(_, linking)| {
+            linking.illuminated.contains(intersection.get_model_identifier())
+        }).flat_map(|(light, linking)| {
+            light.get_illumination_samples(intersection).into_iter().filter_map(|(ray, illumination)| {
+                match illumination_caster.cast_linked_light_ray(&ray, intersection, &linking.shadow_casters) {
+                    None => None,
+                    Some(illumintaion_shadowing) => Some(illumination.get_shadowed(&illumintaion_shadowing))
                 }
+            }).collect::<Vec<LightIntersection>>()
+        }).collect()
+    }
+
+    fn get_escaped_ray_color(&self, ray: &Ray) -> Option<Color> {
+        self.lights.iter().filter_map(|light| light.get_intersection(ray)).fold(None, |acc, light_intersection| {
+            Some(acc.unwrap_or(Color::zero()) + *light_intersection.get_illumination())
+        })
+    }
+
+    fn get_light_sources(&self) -> Vec<&LightSource> {
+        self.lights.iter().map(|light| &**light).collect()
+    }
+}
+
+
+/// Illuminator choosing sample_count lights per shading point with a light BVH instead of evaluating every light.
+/// Lights without bounds are evaluated at every point, the chosen lights are weighted by the inverse of their probability
+pub struct SampledIlluminator {
+    lights: LightSourceVec,
+    unbounded_lights: Vec<usize>,
+    bounded_lights: Vec<usize>,
+    links: Vec<LightLinking>,
+    light_bvh: LightBvh,
+    sample_count: usize
+}
+
+impl SampledIlluminator {
+    pub fn new(lights: LightSourceVec, sample_count: usize) -> Self {
+        let mut unbounded_lights: Vec<usize> = Vec::new();
+        let mut bounded_lights: Vec<usize> = Vec::new();
+        let mut light_bounds = Vec::new();
+        for (index, light) in lights.iter().enumerate() {
+            match light.get_bounds() {
+                Some(bounds) => {
+                    bounded_lights.push(index);
+                    light_bounds.push(bounds);
+                },
+                None => unbounded_lights.push(index)
             }
+        }
+
+        let links = lights.iter().map(|_| LightLinking::new_unlinked()).collect();
+        Self {  lights: lights,
+                links: links,
+                unbounded_lights: unbounded_lights,
+                bounded_lights: bounded_lights,
+                light_bvh: LightBvh::new(light_bounds),
+                sample_count: sample_count.max(1)}
+    }
+
+    pub fn get_sample_count(&self) -> usize {
+        self.sample_count
+    }
+
+    pub fn set_sample_count(&mut self, sample_count: usize) {
+        self.sample_count = sample_count.max(1);
+    }
+
+    /// Linking of the light at the index in the order the lights were given
+    pub fn get_light_linking(&self, light_index: usize) -> Option<&LightLinking> {
+        self.links.get(light_index)
+    }
+
+    pub fn get_light_linking_mut(&mut self, light_index: usize) -> Option<&mut LightLinking> {
+        self.links.get_mut(light_index)
+    }
+
+    /// Lights not linked to the intersected model contribute nothing, so a sample choosing them is lost like a shadowed one
+    fn get_shadowed_illumination(&self, light_index: usize, weight: FloatType, intersection: &RayIntersection, illumination_caster: &RayCaster) -> Vec<LightIntersection> {
+        let linking = &self.links[light_index];
+        if !linking.illuminated.contains(intersection.get_model_identifier()) {
+            return Vec::new();
+        }
+
+        self.lights[light_index].get_illumination_samples(intersection).into_iter().filter_map(|(ray, illumination)| {
+            illumination_caster.cast_linked_light_ray(&ray, intersection, &linking.shadow_casters).map(|shadowing| {
+                illumination.get_shadowed(&shadowing.mul_scalar(&weight))
+            })
         }).collect()
     }
-}
\ No newline at end of file
+}
+
+impl Illuminator for SampledIlluminator {
+    fn get_illumination_at(&self, intersection: &RayIntersection, illumination_caster: &RayCaster) -> Vec<LightIntersection> {
+        let mut result: Vec<LightIntersection> = self.unbounded_lights.iter().flat_map(|index| {
+            self.get_shadowed_illumination(*index, 1.0, intersection, illumination_caster)
+        }).collect();
+
+        if !self.light_bvh.is_empty() {
+            let intersection_point = intersection.get_intersection_point();
+            let mut random_generator = rand::thread_rng();
+            for _ in 0..self.sample_count {
+                if let Some((bvh_index, probability)) = self.light_bvh.sample(intersection_point, random_generator.gen::<FloatType>()) {
+                    if probability > 0.0 {
+                        let weight = (probability * self.sample_count as FloatType).recip();
+                        result.extend(self.get_shadowed_illumination(self.bounded_lights[bvh_index], weight, intersection, illumination_caster));
+                    }
+                }
+            }
+        }
+
+        result
+    }
+
+    fn get_escaped_ray_color(&self, ray: &Ray) -> Option<Color> {
+        self.unbounded_lights.iter().filter_map(|index| self.lights[*index].get_intersection(ray)).fold(None, |acc, light_intersection| {
+            Some(acc.unwrap_or(Color::zero()) + *light_intersection.get_illumination())
+        })
+    }
+
+    fn get_light_sources(&self) -> Vec<&LightSource> {
+        self.lights.iter().map(|light| &**light).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{HashSet};
+    use defs::{Point3, Vector3};
+    use core::{World, Material, LightLinkSet, RayIntersection};
+    use basic::{SimpleIntersector, SimpleColorCalculator};
+    use basic::model::{SolidSphere, SolidPlane};
+    use basic::lightsource::{DotLightSource};
+    use uuid::{Uuid};
+
+    #[test]
+    fn light_linking_limits_illumination_and_shadows() {
+        let (floor_identifier, blocker_identifier) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut floor = SolidPlane::new(Material::new_diffuse(Color::one(), None));
+        floor.set_custom_identifier(floor_identifier);
+        let mut blocker = SolidSphere::new_positioned(Material::new_diffuse(Color::one(), None), Point3::new(0.0, 0.0, 5.0), 1.0);
+        blocker.set_custom_identifier(blocker_identifier);
+        let models: ModelVec = vec![Box::new(floor), Box::new(blocker)];
+
+        let mut illuminator = SimpleIlluminator::new(vec![Box::new(DotLightSource::new_natural(Color::one(), 1.0, Point3::new(0.0, 0.0, 10.0)))]);
+        let world = World::new(SimpleIntersector::new(models), SimpleColorCalculator::new(), SimpleIlluminator::new(Vec::new()), 4);
+        let ray = Ray::new(Point3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        let floor_intersection = world.cast_model_ray(&ray).expect("Ray should hit the floor");
+
+        assert!(illuminator.get_illumination_at(&floor_intersection, &world).is_empty());
+
+        let shadow_casters: HashSet<Uuid> = vec![blocker_identifier].into_iter().collect();
+        *illuminator.get_light_linking_mut(0).unwrap() = LightLinking::new(LightLinkSet::All, LightLinkSet::Exclude(shadow_casters));
+        assert_eq!(illuminator.get_illumination_at(&floor_intersection, &world).len(), 1);
+
+        let blocker_only: HashSet<Uuid> = vec![blocker_identifier].into_iter().collect();
+        *illuminator.get_light_linking_mut(0).unwrap() = LightLinking::new(LightLinkSet::Include(blocker_only), LightLinkSet::All);
+        assert!(illuminator.get_illumination_at(&floor_intersection, &world).is_empty());
+        assert!(illuminator.get_light_linking(1).is_none());
+    }
+
+    #[test]
+    fn sampled_illuminator_follows_light_linking() {
+        let (floor_identifier, blocker_identifier) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut floor = SolidPlane::new(Material::new_diffuse(Color::one(), None));
+        floor.set_custom_identifier(floor_identifier);
+        let mut blocker = SolidSphere::new_positioned(Material::new_diffuse(Color::one(), None), Point3::new(0.0, 0.0, 5.0), 1.0);
+        blocker.set_custom_identifier(blocker_identifier);
+        let models: ModelVec = vec![Box::new(floor), Box::new(blocker)];
+
+        let mut illuminator = SampledIlluminator::new(vec![Box::new(DotLightSource::new_natural(Color::one(), 1.0, Point3::new(0.0, 0.0, 10.0)))], 1);
+        let world = World::new(SimpleIntersector::new(models), SimpleColorCalculator::new(), SimpleIlluminator::new(Vec::new()), 4);
+        let floor_intersection = world.cast_model_ray(&Ray::new(Point3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 0.0, -1.0))).expect("Ray should hit the floor");
+
+        assert!(illuminator.get_illumination_at(&floor_intersection, &world).is_empty());
+
+        let shadow_casters: HashSet<Uuid> = vec![blocker_identifier].into_iter().collect();
+        *illuminator.get_light_linking_mut(0).unwrap() = LightLinking::new(LightLinkSet::All, LightLinkSet::Exclude(shadow_casters));
+        assert_eq!(illuminator.get_illumination_at(&floor_intersection, &world).len(), 1);
+
+        let blocker_only: HashSet<Uuid> = vec![blocker_identifier].into_iter().collect();
+        *illuminator.get_light_linking_mut(0).unwrap() = LightLinking::new(LightLinkSet::Include(blocker_only), LightLinkSet::All);
+        assert!(illuminator.get_illumination_at(&floor_intersection, &world).is_empty());
+        assert!(illuminator.get_light_linking(1).is_none());
+    }
+
+    #[test]
+    fn sampled_illuminator_matches_simple_on_average() {
+        let create_lights = || -> LightSourceVec {
+            (0..200).map(|index| {
+                let position = Point3::new((index % 20) as FloatType - 10.0, (index / 20) as FloatType - 5.0, 3.0);
+                Box::new(DotLightSource::new_natural(Color::one(), 0.5 + (index % 3) as FloatType, position)) as Box<LightSource>
+            }).collect()
+        };
+        let world = World::new(SimpleIntersector::new(Vec::new()), SimpleColorCalculator::new(), SimpleIlluminator::new(Vec::new()), 4);
+        let ray = Ray::new(Point3::new(1.0, 1.0, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        let floor_intersection = RayIntersection::new(Vector3::new(0.0, 0.0, 1.0), Point3::new(1.0, 1.0, 0.0), &ray, Material::new_diffuse(Color::one(), None), false).unwrap();
+        let get_diffuse = |illuminations: Vec<LightIntersection>| illuminations.iter().fold(0.0, |acc, illumination| {
+            acc + Material::get_diffuse_illumination(&floor_intersection, illumination).unwrap().intensity_avg()
+        });
+
+        let expected = get_diffuse(SimpleIlluminator::new(create_lights()).get_illumination_at(&floor_intersection, &world));
+        let sampled = SampledIlluminator::new(create_lights(), 4);
+        let iterations = 2000;
+        let average = (0..iterations).map(|_| get_diffuse(sampled.get_illumination_at(&floor_intersection, &world))).sum::<FloatType>() / iterations as FloatType;
+
+        assert!(sampled.get_illumination_at(&floor_intersection, &world).len() <= 4);
+        assert!((average - expected).abs() < expected * 0.05);
+    }
+}
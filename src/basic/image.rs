@@ -0,0 +1,658 @@
+use std::fs::{File};
+use std::io::{Write, BufWriter, BufRead, BufReader, Read};
+use std::io;
+use std::mem;
+use std::path::{Path};
+
+use defs::{FloatType, IntType, Point2Int, Point3, Vector3};
+use core::{Color, ImmutableSceneBuffer, MutableSceneBuffer, BasicSceneBuffer, Screen, ScreenError};
+
+
+fn to_byte(value: FloatType) -> u8 {
+    (value.max(0.0).min(1.0) * 255.0).round() as u8
+}
+
+fn get_pixel_color(buffer: &ImmutableSceneBuffer, pixel: Point2Int) -> io::Result<Color> {
+    match buffer.get_pixel_value(pixel) {
+        Ok(color) => Ok(color.unwrap_or(Color::zero())),
+        Err(error) => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Scene buffer error: {:?}", error)))
+    }
+}
+
+/// Writes the buffer as a binary PPM image, colors clamped to [0, 1] and missing pixels written black
+pub fn write_ppm<W: Write>(buffer: &ImmutableSceneBuffer, writer: &mut W) -> io::Result<()> {
+    let (width, height) = buffer.get_screen().get_resolution();
+    write!(writer, "P6\n{} {}\n255\n", width, height)?;
+
+    let mut line: Vec<u8> = Vec::with_capacity(width as usize * 3);
+    for y in 0..height {
+        line.clear();
+        for x in 0..width {
+            let (r, g, b) = get_pixel_color(buffer, Point2Int::new(x, y))?.get();
+            line.push(to_byte(r));
+            line.push(to_byte(g));
+            line.push(to_byte(b));
+        }
+        writer.write_all(&line)?;
+    }
+
+    Ok(())
+}
+
+pub fn save_ppm(buffer: &ImmutableSceneBuffer, path: &Path) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_ppm(buffer, &mut writer)?;
+    writer.flush()
+}
+
+
+/// Floating point image with the first pixel at the top left corner
+#[derive(Clone)]
+pub struct HdrImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>
+}
+
+impl HdrImage {
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert_eq!(width * height, pixels.len(), "HdrImage pixel count does not match its size");
+        Self {
+            width: width,
+            height: height,
+            pixels: pixels
+        }
+    }
+
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> &Color {
+        &self.pixels[y * self.width + x]
+    }
+
+    pub fn get_pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    /// Copies the buffer, missing pixels become black
+    pub fn new_from_buffer(buffer: &ImmutableSceneBuffer) -> io::Result<Self> {
+        let (width, height) = buffer.get_screen().get_resolution();
+        let mut pixels: Vec<Color> = Vec::with_capacity(width as usize * height as usize);
+        for y in 0..height {
+            for x in 0..width {
+                pixels.push(get_pixel_color(buffer, Point2Int::new(x, y))?);
+            }
+        }
+
+        Ok(Self::new(width as usize, height as usize, pixels))
+    }
+
+    /// Scene buffer on a unit height screen with the resolution of the image, to compare against rendered buffers
+    pub fn to_scene_buffer(&self) -> Result<BasicSceneBuffer, ScreenError> {
+        let screen = Screen::new(Point3::origin(),
+                                 Vector3::new(0.0, 0.0, 1.0),
+                                 Vector3::new(0.0, 1.0, 0.0),
+                                 self.width as FloatType / self.height as FloatType,
+                                 1.0,
+                                 self.width as IntType,
+                                 self.height as IntType)?;
+        let buffer = BasicSceneBuffer::new(screen);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                buffer.set_pixel_value(Point2Int::new(x as IntType, y as IntType), self.get_pixel(x, y)).expect("Scene buffer has the image resolution");
+            }
+        }
+
+        Ok(buffer)
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Loaded images have to be non empty and fit the resolution of a screen, without their pixel count overflowing
+fn check_image_size(width: usize, height: usize) -> io::Result<(usize, usize)> {
+    let max_size = IntType::max_value() as usize;
+    if width == 0 || height == 0 || width > max_size || height > max_size {
+        return Err(invalid_data("Image dimensions are empty or too large"));
+    }
+    width.checked_mul(height)
+         .and_then(|pixel_count| pixel_count.checked_mul(mem::size_of::<Color>()))
+         .map(|_| (width, height))
+         .ok_or_else(|| invalid_data("Image dimensions are empty or too large"))
+}
+
+fn rgbe_to_color(rgbe: &[u8]) -> Color {
+    if rgbe[3] == 0 {
+        Color::zero()
+    } else {
+        let scale = (2.0 as FloatType).powi(rgbe[3] as i32 - (128 + 8));
+        Color::new(rgbe[0] as FloatType * scale, rgbe[1] as FloatType * scale, rgbe[2] as FloatType * scale)
+    }
+}
+
+fn read_hdr_header<R: BufRead>(reader: &mut R) -> io::Result<(usize, usize)> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if !line.starts_with("#?RADIANCE") && !line.starts_with("#?RGBE") {
+        return Err(invalid_data("Missing Radiance HDR signature"));
+    }
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid_data("Unexpected end of Radiance HDR header"));
+        }
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            break;
+        }
+        if trimmed.starts_with("FORMAT=") && trimmed != "FORMAT=32-bit_rle_rgbe" {
+            return Err(invalid_data("Unsupported Radiance HDR pixel format"));
+        }
+    }
+
+    line.clear();
+    reader.read_line(&mut line)?;
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if tokens.len() != 4 || tokens[0] != "-Y" || tokens[2] != "+X" {
+        return Err(invalid_data("Unsupported Radiance HDR orientation"));
+    }
+    match (tokens[3].parse::<usize>(), tokens[1].parse::<usize>()) {
+        (Ok(width), Ok(height)) => check_image_size(width, height),
+        _ => Err(invalid_data("Invalid Radiance HDR resolution"))
+    }
+}
+
+/// Reads a scanline, either flat or with the run length encoding of separated channels
+fn read_hdr_scanline<R: Read>(reader: &mut R, width: usize, scanline: &mut Vec<u8>) -> io::Result<()> {
+    let mut start = [0u8; 4];
+    reader.read_exact(&mut start)?;
+    scanline.clear();
+
+    let is_run_length_encoded = width >= 8 && width < 32768 && start[0] == 2 && start[1] == 2 && start[2] & 0x80 == 0;
+    if !is_run_length_encoded {
+        scanline.extend_from_slice(&start);
+        let mut rest = vec![0u8; (width - 1) * 4];
+        reader.read_exact(&mut rest)?;
+        scanline.extend_from_slice(&rest);
+        return Ok(());
+    }
+
+    if ((start[2] as usize) << 8 | start[3] as usize) != width {
+        return Err(invalid_data("Radiance HDR scanline width mismatch"));
+    }
+
+    let mut channels = vec![0u8; width * 4];
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let mut count = [0u8; 1];
+            reader.read_exact(&mut count)?;
+            if count[0] > 128 {
+                let run = (count[0] - 128) as usize;
+                let mut value = [0u8; 1];
+                reader.read_exact(&mut value)?;
+                if x + run > width {
+                    return Err(invalid_data("Radiance HDR run overflows scanline"));
+                }
+                for offset in 0..run {
+                    channels[channel * width + x + offset] = value[0];
+                }
+                x += run;
+            } else {
+                let run = count[0] as usize;
+                if run == 0 || x + run > width {
+                    return Err(invalid_data("Invalid Radiance HDR run"));
+                }
+                reader.read_exact(&mut channels[channel * width + x..channel * width + x + run])?;
+                x += run;
+            }
+        }
+    }
+
+    for x in 0..width {
+        for channel in 0..4 {
+            scanline.push(channels[channel * width + x]);
+        }
+    }
+    Ok(())
+}
+
+/// Reads a Radiance RGBE (.hdr) image
+pub fn read_hdr<R: BufRead>(reader: &mut R) -> io::Result<HdrImage> {
+    let (width, height) = read_hdr_header(reader)?;
+    let mut pixels: Vec<Color> = Vec::with_capacity(width * height);
+    let mut scanline: Vec<u8> = Vec::with_capacity(width * 4);
+
+    for _ in 0..height {
+        read_hdr_scanline(reader, width, &mut scanline)?;
+        pixels.extend(scanline.chunks(4).map(rgbe_to_color));
+    }
+
+    Ok(HdrImage::new(width, height, pixels))
+}
+
+pub fn load_hdr(path: &Path) -> io::Result<HdrImage> {
+    read_hdr(&mut BufReader::new(File::open(path)?))
+}
+
+fn color_to_rgbe(color: &Color) -> [u8; 4] {
+    let (r, g, b) = color.get();
+    let (r, g, b) = (r.max(0.0), g.max(0.0), b.max(0.0));
+    let maximum = r.max(g).max(b);
+
+    if maximum < 1e-32 {
+        [0, 0, 0, 0]
+    } else {
+        let exponent = maximum.log2().floor() as i32 + 1;
+        let scale = 256.0 / (2.0 as FloatType).powi(exponent);
+        let to_mantissa = |value: FloatType| (value * scale).min(255.0) as u8;
+        [to_mantissa(r), to_mantissa(g), to_mantissa(b), (exponent + 128).max(0).min(255) as u8]
+    }
+}
+
+/// Writes the buffer as a Radiance RGBE image with flat scanlines, missing pixels written black
+pub fn write_hdr<W: Write>(buffer: &ImmutableSceneBuffer, writer: &mut W) -> io::Result<()> {
+    let (width, height) = buffer.get_screen().get_resolution();
+    write!(writer, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", height, width)?;
+
+    let mut line: Vec<u8> = Vec::with_capacity(width as usize * 4);
+    for y in 0..height {
+        line.clear();
+        for x in 0..width {
+            line.extend_from_slice(&color_to_rgbe(&get_pixel_color(buffer, Point2Int::new(x, y))?));
+        }
+        writer.write_all(&line)?;
+    }
+
+    Ok(())
+}
+
+pub fn save_hdr(buffer: &ImmutableSceneBuffer, path: &Path) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_hdr(buffer, &mut writer)?;
+    writer.flush()
+}
+
+const EXR_MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+const EXR_PIXEL_TYPE_FLOAT: i32 = 2;
+
+fn write_u32_le<W: Write>(writer: &mut W, value: u32) -> io::Result<()> {
+    writer.write_all(&[value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8])
+}
+
+fn write_i32_le<W: Write>(writer: &mut W, value: i32) -> io::Result<()> {
+    write_u32_le(writer, value as u32)
+}
+
+fn write_u64_le<W: Write>(writer: &mut W, value: u64) -> io::Result<()> {
+    write_u32_le(writer, value as u32)?;
+    write_u32_le(writer, (value >> 32) as u32)
+}
+
+fn write_f32_le<W: Write>(writer: &mut W, value: f32) -> io::Result<()> {
+    write_u32_le(writer, value.to_bits())
+}
+
+fn write_exr_attribute<W: Write>(writer: &mut W, name: &str, attribute_type: &str, value: &[u8]) -> io::Result<()> {
+    writer.write_all(name.as_bytes())?;
+    writer.write_all(&[0])?;
+    writer.write_all(attribute_type.as_bytes())?;
+    writer.write_all(&[0])?;
+    write_i32_le(writer, value.len() as i32)?;
+    writer.write_all(value)
+}
+
+fn read_u32_le<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16 | (bytes[3] as u32) << 24)
+}
+
+fn read_i32_le<R: Read>(reader: &mut R) -> io::Result<i32> {
+    read_u32_le(reader).map(|value| value as i32)
+}
+
+fn read_null_terminated<R: Read>(reader: &mut R) -> io::Result<String> {
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut byte = [0u8; 1];
+    loop {
+        reader.read_exact(&mut byte)?;
+        if byte[0] == 0 {
+            break;
+        }
+        bytes.push(byte[0]);
+    }
+    String::from_utf8(bytes).map_err(|_| invalid_data("Invalid OpenEXR string"))
+}
+
+/// Channel name of a layer component, the unnamed layer uses the bare component name
+fn get_exr_channel_name(layer_name: &str, component: &str) -> String {
+    if layer_name.is_empty() {
+        component.to_string()
+    } else {
+        format!("{}.{}", layer_name, component)
+    }
+}
+
+/// Writes the named buffers as the RGB layers of an uncompressed scanline OpenEXR image with 32-bit float channels,
+/// the layer named by the empty string becomes the default R, G, B channels
+pub fn write_exr<W: Write>(layers: &[(&str, &ImmutableSceneBuffer)], writer: &mut W) -> io::Result<()> {
+    let (width, height) = match layers.first() {
+        Some(&(_, buffer)) => buffer.get_screen().get_resolution(),
+        None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "No layers to write"))
+    };
+    if layers.iter().any(|&(_, buffer)| buffer.get_screen().get_resolution() != (width, height)) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Layers are not the same size"));
+    }
+
+    let mut images: Vec<HdrImage> = Vec::with_capacity(layers.len());
+    let mut channels: Vec<(String, usize, usize)> = Vec::new();
+    for (layer_index, &(name, buffer)) in layers.iter().enumerate() {
+        images.push(HdrImage::new_from_buffer(buffer)?);
+        for (component_index, component) in ["R", "G", "B"].iter().enumerate() {
+            channels.push((get_exr_channel_name(name, component), layer_index, component_index));
+        }
+    }
+    channels.sort_by(|lhs, rhs| lhs.0.cmp(&rhs.0));
+
+    let mut channel_list: Vec<u8> = Vec::new();
+    for &(ref name, _, _) in channels.iter() {
+        channel_list.extend_from_slice(name.as_bytes());
+        channel_list.push(0);
+        write_i32_le(&mut channel_list, EXR_PIXEL_TYPE_FLOAT)?;
+        channel_list.extend_from_slice(&[0, 0, 0, 0]);
+        write_i32_le(&mut channel_list, 1)?;
+        write_i32_le(&mut channel_list, 1)?;
+    }
+    channel_list.push(0);
+
+    let mut window: Vec<u8> = Vec::new();
+    for value in [0, 0, width - 1, height - 1].iter() {
+        write_i32_le(&mut window, *value)?;
+    }
+    let mut header: Vec<u8> = Vec::new();
+    header.extend_from_slice(&EXR_MAGIC);
+    write_u32_le(&mut header, 2)?;
+    write_exr_attribute(&mut header, "channels", "chlist", &channel_list)?;
+    write_exr_attribute(&mut header, "compression", "compression", &[0])?;
+    write_exr_attribute(&mut header, "dataWindow", "box2i", &window)?;
+    write_exr_attribute(&mut header, "displayWindow", "box2i", &window)?;
+    write_exr_attribute(&mut header, "lineOrder", "lineOrder", &[0])?;
+    let mut unit_float: Vec<u8> = Vec::new();
+    write_f32_le(&mut unit_float, 1.0)?;
+    write_exr_attribute(&mut header, "pixelAspectRatio", "float", &unit_float)?;
+    write_exr_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8])?;
+    write_exr_attribute(&mut header, "screenWindowWidth", "float", &unit_float)?;
+    header.push(0);
+    writer.write_all(&header)?;
+
+    let chunk_size = 8 + channels.len() as u64 * width as u64 * 4;
+    let first_chunk_offset = header.len() as u64 + height as u64 * 8;
+    for y in 0..height as u64 {
+        write_u64_le(writer, first_chunk_offset + y * chunk_size)?;
+    }
+
+    for y in 0..height {
+        write_i32_le(writer, y)?;
+        write_i32_le(writer, (chunk_size - 8) as i32)?;
+        for &(_, layer_index, component_index) in channels.iter() {
+            for x in 0..width {
+                let (r, g, b) = images[layer_index].get_pixel(x as usize, y as usize).get();
+                write_f32_le(writer, [r, g, b][component_index] as f32)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn save_exr(layers: &[(&str, &ImmutableSceneBuffer)], path: &Path) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_exr(layers, &mut writer)?;
+    writer.flush()
+}
+
+/// Reads the RGB layers of an uncompressed scanline OpenEXR image with 32-bit float channels, ordered by layer name
+pub fn read_exr<R: Read>(reader: &mut R) -> io::Result<Vec<(String, HdrImage)>> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != EXR_MAGIC {
+        return Err(invalid_data("Missing OpenEXR signature"));
+    }
+    if read_u32_le(reader)? & 0x1e00 != 0 {
+        return Err(invalid_data("Only single part scanline OpenEXR images are supported"));
+    }
+
+    let mut channel_names: Vec<String> = Vec::new();
+    let mut data_window: Option<(i32, i32, i32, i32)> = None;
+    loop {
+        let name = read_null_terminated(reader)?;
+        if name.is_empty() {
+            break;
+        }
+        let _attribute_type = read_null_terminated(reader)?;
+        let size = read_i32_le(reader)?;
+        let mut value = vec![0u8; size.max(0) as usize];
+        reader.read_exact(&mut value)?;
+        let mut value_reader = &value[..];
+
+        match name.as_str() {
+            "channels" => {
+                loop {
+                    let channel_name = read_null_terminated(&mut value_reader)?;
+                    if channel_name.is_empty() {
+                        break;
+                    }
+                    if read_i32_le(&mut value_reader)? != EXR_PIXEL_TYPE_FLOAT {
+                        return Err(invalid_data("Only 32-bit float OpenEXR channels are supported"));
+                    }
+                    let mut rest = [0u8; 12];
+                    value_reader.read_exact(&mut rest)?;
+                    channel_names.push(channel_name);
+                }
+            },
+            "compression" => {
+                if value.first() != Some(&0) {
+                    return Err(invalid_data("Only uncompressed OpenEXR images are supported"));
+                }
+            },
+            "dataWindow" => {
+                data_window = Some((read_i32_le(&mut value_reader)?, read_i32_le(&mut value_reader)?,
+                                    read_i32_le(&mut value_reader)?, read_i32_le(&mut value_reader)?));
+            },
+            _ => {}
+        }
+    }
+
+    let (x_min, y_min, x_max, y_max) = data_window.ok_or_else(|| invalid_data("Missing OpenEXR data window"))?;
+    let (width, height) = check_image_size((x_max as i64 - x_min as i64 + 1).max(0) as usize,
+                                           (y_max as i64 - y_min as i64 + 1).max(0) as usize)?;
+
+    let mut offsets = vec![0u8; height * 8];
+    reader.read_exact(&mut offsets)?;
+
+    let mut channel_values: Vec<Vec<f32>> = vec![vec![0.0; width * height]; channel_names.len()];
+    for _ in 0..height {
+        let y = (read_i32_le(reader)? - y_min) as usize;
+        let _size = read_i32_le(reader)?;
+        if y >= height {
+            return Err(invalid_data("OpenEXR scanline outside of the data window"));
+        }
+        for values in channel_values.iter_mut() {
+            for x in 0..width {
+                values[y * width + x] = f32::from_bits(read_u32_le(reader)?);
+            }
+        }
+    }
+
+    let mut layers: Vec<(String, HdrImage)> = Vec::new();
+    for (channel_name, values) in channel_names.iter().zip(channel_values.iter()) {
+        let (layer_name, component) = match channel_name.rfind('.') {
+            Some(position) => (&channel_name[..position], &channel_name[position + 1..]),
+            None => ("", channel_name.as_str())
+        };
+        let component_index = match component {
+            "R" => 0,
+            "G" => 1,
+            "B" => 2,
+            _ => continue
+        };
+
+        let layer_position = match layers.iter().position(|&(ref name, _)| name == layer_name) {
+            Some(position) => position,
+            None => {
+                layers.push((layer_name.to_string(), HdrImage::new(width, height, vec![Color::zero(); width * height])));
+                layers.len() - 1
+            }
+        };
+        let image = &mut layers[layer_position].1;
+        for (pixel, value) in image.pixels.iter_mut().zip(values.iter()) {
+            let (r, g, b) = pixel.get();
+            let mut components = [r, g, b];
+            components[component_index] = *value as FloatType;
+            *pixel = Color::new(components[0], components[1], components[2]);
+        }
+    }
+    layers.sort_by(|lhs, rhs| lhs.0.cmp(&rhs.0));
+
+    Ok(layers)
+}
+
+pub fn load_exr(path: &Path) -> io::Result<Vec<(String, HdrImage)>> {
+    read_exr(&mut BufReader::new(File::open(path)?))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use defs::{Point3, Vector3};
+    use core::{Screen, BasicSceneBuffer, MutableSceneBuffer};
+
+    #[test]
+    fn ppm_header_and_pixels() {
+        let screen = Screen::new(Point3::origin(), Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 1.0, 0.0), 2.0, 1.0, 2, 1).unwrap();
+        let buffer = BasicSceneBuffer::new(screen);
+        buffer.set_pixel_value(Point2Int::new(1, 0), &Color::new(1.0, 0.5, 2.0)).unwrap();
+
+        let mut output: Vec<u8> = Vec::new();
+        write_ppm(&buffer, &mut output).unwrap();
+
+        let header = b"P6\n2 1\n255\n";
+        assert_eq!(&output[..header.len()], &header[..]);
+        assert_eq!(&output[header.len()..], &[0, 0, 0, 255, 128, 255][..]);
+    }
+
+    #[test]
+    fn hdr_read_flat_and_run_length_encoded() {
+        let mut flat: Vec<u8> = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 2\n".to_vec();
+        flat.extend_from_slice(&[128, 64, 0, 129, 0, 0, 0, 0]);
+        let image = read_hdr(&mut &flat[..]).unwrap();
+
+        assert_eq!((image.get_width(), image.get_height()), (2, 1));
+        assert!(image.get_pixel(0, 0).equal_eps(&Color::new(1.0, 0.5, 0.0)));
+        assert!(image.get_pixel(1, 0).equal_eps(&Color::zero()));
+
+        let mut encoded: Vec<u8> = b"#?RADIANCE\n\n-Y 1 +X 8\n".to_vec();
+        encoded.extend_from_slice(&[2, 2, 0, 8]);
+        encoded.extend_from_slice(&[136, 128]);
+        encoded.extend_from_slice(&[136, 64]);
+        encoded.extend_from_slice(&[4, 1, 2, 3, 4, 132, 0]);
+        encoded.extend_from_slice(&[136, 128]);
+        let image = read_hdr(&mut &encoded[..]).unwrap();
+
+        assert!(image.get_pixel(0, 0).equal_eps(&Color::new(0.5, 0.25, 1.0 / 256.0)));
+        assert!(image.get_pixel(7, 0).equal_eps(&Color::new(0.5, 0.25, 0.0)));
+        assert!(read_hdr(&mut &b"P6\n"[..]).is_err());
+    }
+
+    #[test]
+    fn hdr_rejects_empty_and_oversized_resolution() {
+        let empty_width = b"#?RADIANCE\n\n-Y 4 +X 0\n\x00\x00\x00\x00";
+        let oversized = b"#?RADIANCE\n\n-Y 4 +X 99999999999\n";
+
+        assert_eq!(read_hdr(&mut &empty_width[..]).err().map(|error| error.kind()), Some(io::ErrorKind::InvalidData));
+        assert_eq!(read_hdr(&mut &oversized[..]).err().map(|error| error.kind()), Some(io::ErrorKind::InvalidData));
+    }
+
+    fn create_gradient_buffer() -> BasicSceneBuffer {
+        let screen = Screen::new(Point3::origin(), Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 1.0, 0.0), 1.5, 1.0, 3, 2).unwrap();
+        let buffer = BasicSceneBuffer::new(screen);
+        for y in 0..2 {
+            for x in 0..3 {
+                buffer.set_pixel_value(Point2Int::new(x, y), &Color::new(x as FloatType * 4.0, y as FloatType * 0.25, 0.125)).unwrap();
+            }
+        }
+        buffer
+    }
+
+    #[test]
+    fn hdr_write_read_round_trip() {
+        let buffer = create_gradient_buffer();
+        let mut output: Vec<u8> = Vec::new();
+        write_hdr(&buffer, &mut output).unwrap();
+
+        let restored = read_hdr(&mut &output[..]).unwrap().to_scene_buffer().unwrap();
+
+        assert_eq!(restored.get_screen().get_resolution(), (3, 2));
+        for y in 0..2 {
+            for x in 0..3 {
+                let (r, g, b) = buffer.get_pixel_value(Point2Int::new(x, y)).unwrap().unwrap().get();
+                let (loaded_r, loaded_g, loaded_b) = restored.get_pixel_value(Point2Int::new(x, y)).unwrap().unwrap().get();
+                let tolerance = r.max(g).max(b) / 128.0;
+                assert!((r - loaded_r).abs() <= tolerance && (g - loaded_g).abs() <= tolerance && (b - loaded_b).abs() <= tolerance);
+            }
+        }
+    }
+
+    #[test]
+    fn exr_write_read_layers() {
+        let beauty = create_gradient_buffer();
+        let screen = *beauty.get_screen();
+        let normals = BasicSceneBuffer::new(screen);
+        normals.set_pixel_value(Point2Int::new(2, 1), &Color::new(-1.0, 0.5, 1.0e6)).unwrap();
+
+        let mut output: Vec<u8> = Vec::new();
+        write_exr(&[("", &beauty), ("normal", &normals)], &mut output).unwrap();
+        let layers = read_exr(&mut &output[..]).unwrap();
+
+        assert_eq!(&output[..4], &EXR_MAGIC[..]);
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[0].0, "");
+        assert_eq!(layers[1].0, "normal");
+        assert!(layers[0].1.get_pixel(2, 1).equal_eps(&Color::new(8.0, 0.25, 0.125)));
+        assert!(layers[1].1.get_pixel(2, 1).equal_eps(&Color::new(-1.0, 0.5, 1.0e6)));
+        assert!(layers[1].1.get_pixel(0, 0).equal_eps(&Color::zero()));
+    }
+
+    #[test]
+    fn exr_rejects_empty_and_overflowing_data_window() {
+        let mut output: Vec<u8> = Vec::new();
+        write_exr(&[("", &create_gradient_buffer())], &mut output).unwrap();
+        let attribute = b"dataWindow\0box2i\0";
+        let window_start = output.windows(attribute.len()).position(|window| window == &attribute[..]).unwrap() + attribute.len() + 4;
+        let with_window = |window: [i32; 4]| {
+            let mut patched = output.clone();
+            for (index, value) in window.iter().enumerate() {
+                let bytes = *value as u32;
+                for byte in 0..4 {
+                    patched[window_start + index * 4 + byte] = (bytes >> (byte * 8)) as u8;
+                }
+            }
+            patched
+        };
+
+        assert!(read_exr(&mut &with_window([0, 0, 2, 1])[..]).is_ok());
+        assert_eq!(read_exr(&mut &with_window([0, 0, -1, 1])[..]).err().map(|error| error.kind()), Some(io::ErrorKind::InvalidData));
+        assert_eq!(read_exr(&mut &with_window([IntType::min_value(), 0, IntType::max_value(), 1])[..]).err().map(|error| error.kind()), Some(io::ErrorKind::InvalidData));
+    }
+}
@@ -0,0 +1,141 @@
+use std::sync::{Arc};
+
+use defs::{Matrix4};
+use core::{Model, ModelError, Material, Ray, RayIntersection, BoundingBox, get_affine_inverse};
+use uuid::{Uuid};
+
+/// Places a shared model into the scene with its own transformation, identifier and optional material
+pub struct ModelInstance {
+    model: Arc<Model>,
+    tf_matrix: Matrix4,
+    inverse_tf_matrix: Matrix4,
+    material_override: Option<Material>,
+    identifier: Uuid,
+    bounding_box: Option<BoundingBox>
+}
+
+impl ModelInstance {
+    pub fn new(model: Arc<Model>, model_view_matrix: Matrix4) -> Result<Self, ModelError> {
+        let bounding_box = model.get_bounding_box().map(|bounding_box| bounding_box.get_transformed(&model_view_matrix));
+        Ok(Self {
+            model: model,
+            inverse_tf_matrix: get_affine_inverse(&model_view_matrix).ok_or(ModelError::UninvertibleTransformation)?,
+            tf_matrix: model_view_matrix,
+            material_override: None,
+            identifier: Uuid::new_v4(),
+            bounding_box: bounding_box
+        })
+    }
+
+    pub fn new_identity(model: Arc<Model>) -> Self {
+        Self {
+            bounding_box: model.get_bounding_box(),
+            model: model,
+            inverse_tf_matrix: Matrix4::identity(),
+            tf_matrix: Matrix4::identity(),
+            material_override: None,
+            identifier: Uuid::new_v4()
+        }
+    }
+
+    pub fn set_material_override(&mut self, material: Option<Material>) {
+        self.material_override = material;
+    }
+
+    pub fn set_custom_identifier(&mut self, identifier: Uuid) {
+        self.identifier = identifier;
+    }
+
+    pub fn get_identifier(&self) -> &Uuid {
+        &self.identifier
+    }
+
+    pub fn get_shared_model(&self) -> &Arc<Model> {
+        &self.model
+    }
+
+    pub fn get_tf_matrix(&self) -> &Matrix4 {
+        &self.tf_matrix
+    }
+
+    pub fn get_inverse_tf_matrix(&self) -> &Matrix4 {
+        &self.inverse_tf_matrix
+    }
+
+    /// Bounding box of the shared model in its own space, for building the lower level of a hierarchy
+    pub fn get_local_bounding_box(&self) -> Option<BoundingBox> {
+        self.model.get_bounding_box()
+    }
+}
+
+impl Model for ModelInstance {
+    fn get_intersection(&self, ray: &Ray) -> Option<RayIntersection> {
+        if let Some(ref bounding_box) = self.bounding_box {
+            if !bounding_box.is_intersected_by(ray) {
+                return None;
+            }
+        }
+
+        let transformed_ray = ray.get_transformed(&self.inverse_tf_matrix);
+        match self.model.get_intersection(&transformed_ray) {
+            Some(transformed_intersection) => {
+                match transformed_intersection.get_transformed(&self.tf_matrix) {
+                    Ok(mut intersection) => {
+                        if let Some(material) = self.material_override {
+                            intersection.set_material_mut(material);
+                        }
+                        intersection.set_model_identifier_mut(Some(self.identifier));
+                        Some(intersection)
+                    },
+                    Err(_) => None
+                }
+            },
+            None => None
+        }
+    }
+
+    fn get_bounding_box(&self) -> Option<BoundingBox> {
+        self.bounding_box
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use defs::{Point3, Vector3};
+    use core::{Color};
+    use basic::model::{SolidSphere};
+
+    #[test]
+    fn instances_share_model_with_own_transform_and_identity() {
+        let sphere: Arc<Model> = Arc::new(SolidSphere::new(Material::new_diffuse(Color::one(), None)));
+        let mut translation = Matrix4::identity();
+        translation[(0, 3)] = 5.0;
+
+        let first = ModelInstance::new_identity(Arc::clone(&sphere));
+        let mut second = ModelInstance::new(Arc::clone(&sphere), translation).unwrap();
+        second.set_material_override(Some(Material::new_diffuse(Color::new(1.0, 0.0, 0.0), None)));
+
+        let ray = Ray::new(Point3::new(5.0, -5.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+        let intersection = second.get_intersection(&ray).expect("Ray should hit the translated instance");
+
+        assert!(first.get_intersection(&ray).is_none());
+        assert_relative_eq!(intersection.get_intersection_point(), &Point3::new(5.0, -1.0, 0.0));
+        assert_eq!(intersection.get_model_identifier(), Some(second.get_identifier()));
+        assert!(intersection.get_material().get_diffuse_color().unwrap().equal_eps(&Color::new(1.0, 0.0, 0.0)));
+        assert_relative_eq!(second.get_bounding_box().unwrap().get_center(), Point3::new(5.0, 0.0, 0.0));
+        assert_eq!(Arc::strong_count(&sphere), 3);
+    }
+
+    #[test]
+    fn instance_rejects_singular_transformation() {
+        let sphere: Arc<Model> = Arc::new(SolidSphere::new(Material::new_diffuse(Color::one(), None)));
+        let mut flattened = Matrix4::identity();
+        flattened[(2, 2)] = 0.0;
+
+        assert!(ModelInstance::new(Arc::clone(&sphere), Matrix4::new_scaling(0.0)).is_err());
+        assert!(ModelInstance::new(Arc::clone(&sphere), flattened).is_err());
+        assert!(ModelInstance::new(sphere, Matrix4::new_scaling(2.0)).is_ok());
+    }
+}
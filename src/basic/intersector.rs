@@ -1,22 +1,37 @@
-use core::{Intersector, Model, RayIntersection, Ray};
+use core::{Intersector, Model, RayIntersection, Ray, RenderStatistics, Occlusion, RayPacket};
+use defs::{FloatType};
 
 use tools::CompareWithTolerance;
+use std::sync::{Arc};
 
 
 pub type ModelVec = Vec<Box<Model>>;
 
 pub struct SimpleIntersector {
     models: ModelVec,
+    statistics: Option<Arc<RenderStatistics>>,
 }
 
 impl SimpleIntersector {
     pub fn new(models: ModelVec) -> Self {
-        Self {  models: models}
+        Self {  models: models,
+                statistics: None}
+    }
+
+    fn record_model_tests(&self) {
+        if let Some(ref statistics) = self.statistics {
+            statistics.record_model_tests(self.models.len());
+        }
     }
 }
 
 impl Intersector for SimpleIntersector {
+    fn set_statistics(&mut self, statistics: Option<Arc<RenderStatistics>>) {
+        self.statistics = statistics;
+    }
+
     fn get_intersections_reverse_ordered(&self, ray: &Ray) -> Vec<RayIntersection> { //Nearest elem is last
+        self.record_model_tests();
         let mut result: Vec<RayIntersection> = self.models.iter().filter_map(|model_box| model_box.get_intersection(ray)).collect();
 
         result.sort_by(|lhs: &RayIntersection, rhs: &RayIntersection| {
@@ -27,6 +42,7 @@ impl Intersector for SimpleIntersector {
     }
 
     fn get_nearest_intersection(&self, ray: &Ray) -> Option<RayIntersection> {
+        self.record_model_tests();
         self.models.iter().fold(None, |acc, model_box| {
             match model_box.get_intersection(ray) {
                 Some(intersection) => {
@@ -45,4 +61,78 @@ impl Intersector for SimpleIntersector {
             }
         })
     }
-}
\ No newline at end of file
+
+    fn get_nearest_intersections_packet(&self, packet: &RayPacket) -> Vec<Option<RayIntersection>> {
+        if let Some(ref statistics) = self.statistics {
+            statistics.record_model_tests(self.models.len() * packet.len());
+        }
+        self.models.iter().fold(vec![None; packet.len()], |acc, model_box| {
+            acc.into_iter().zip(model_box.get_packet_intersections(packet)).map(|(accumulated, intersection)| {
+                match (accumulated, intersection) {
+                    (Some(accumulated_intersection), Some(intersection)) => {
+                        if intersection.get_distance_to_intersection().less_eps(&accumulated_intersection.get_distance_to_intersection()) {
+                            Some(intersection)
+                        } else {
+                            Some(accumulated_intersection)
+                        }
+                    },
+                    (accumulated, intersection) => accumulated.or(intersection)
+                }
+            }).collect()
+        })
+    }
+
+    fn get_occlusion(&self, ray: &Ray, max_distance: FloatType) -> Occlusion {
+        let mut result = Occlusion::Unoccluded;
+        for (index, model_box) in self.models.iter().enumerate() {
+            if let Some(intersection) = model_box.get_intersection(ray) {
+                if intersection.get_distance_to_intersection().less_eps(&max_distance) {
+                    if intersection.get_material().is_opaque() {
+                        if let Some(ref statistics) = self.statistics {
+                            statistics.record_model_tests(index + 1);
+                        }
+                        return Occlusion::Opaque;
+                    }
+                    result = Occlusion::Transparent;
+                }
+            }
+        }
+
+        self.record_model_tests();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use defs::{Point3, Vector3};
+    use core::{Material, FresnelIndex, Color};
+    use basic::model::{SolidSphere};
+
+    #[test]
+    fn occlusion_stops_at_max_distance() {
+        let glass = Material::new_refractive(FresnelIndex::new(1.5, 1.5, 1.5), FresnelIndex::zero(), None, None, None);
+        let wall = Material::new_diffuse(Color::one(), None);
+        let intersector = SimpleIntersector::new(vec![Box::new(SolidSphere::new_positioned(glass, Point3::new(0.0, 0.0, 3.0), 1.0)),
+                                                      Box::new(SolidSphere::new_positioned(wall, Point3::new(0.0, 0.0, 8.0), 1.0))]);
+        let ray = Ray::new(Point3::origin(), Vector3::new(0.0, 0.0, 1.0));
+
+        assert_eq!(intersector.get_occlusion(&ray, 1.5), Occlusion::Unoccluded);
+        assert_eq!(intersector.get_occlusion(&ray, 5.0), Occlusion::Transparent);
+        assert_eq!(intersector.get_occlusion(&ray, 10.0), Occlusion::Opaque);
+        assert_eq!(intersector.get_occlusion(&ray, 5.0), Intersector::get_occlusion(&UnorderedIntersector(&intersector), &ray, 5.0));
+    }
+
+    struct UnorderedIntersector<'intersector>(&'intersector SimpleIntersector);
+
+    impl<'intersector> Intersector for UnorderedIntersector<'intersector> {
+        fn get_intersections_reverse_ordered(&self, ray: &Ray) -> Vec<RayIntersection> {
+            self.0.get_intersections_reverse_ordered(ray)
+        }
+
+        fn get_nearest_intersection(&self, ray: &Ray) -> Option<RayIntersection> {
+            self.0.get_nearest_intersection(ray)
+        }
+    }
+}
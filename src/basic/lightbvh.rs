@@ -0,0 +1,182 @@
+use defs::{FloatType, Point3};
+use core::{BoundingBox, LightBounds};
+
+enum LightBvhNodeKind {
+    Leaf(usize),
+    Interior(usize, usize)
+}
+
+struct LightBvhNode {
+    bounds: LightBounds,
+    parent: Option<usize>,
+    kind: LightBvhNodeKind
+}
+
+/// Hierarchy of light bounds choosing a light with probability proportional to its estimated contribution at a point, in time logarithmic to the light count
+pub struct LightBvh {
+    nodes: Vec<LightBvhNode>,
+    root: Option<usize>,
+    /// Leaf node of each light index
+    leaves: Vec<usize>
+}
+
+impl LightBvh {
+    /// The light indices are the positions in the given vector
+    pub fn new(light_bounds: Vec<LightBounds>) -> Self {
+        let mut result = Self {
+            nodes: Vec::with_capacity(light_bounds.len() * 2),
+            root: None,
+            leaves: vec![0; light_bounds.len()]
+        };
+        let mut indexed_bounds: Vec<(usize, LightBounds)> = light_bounds.into_iter().enumerate().collect();
+        result.root = result.build(&mut indexed_bounds, None);
+        result
+    }
+
+    fn build(&mut self, indexed_bounds: &mut [(usize, LightBounds)], parent: Option<usize>) -> Option<usize> {
+        if indexed_bounds.is_empty() {
+            return None;
+        }
+
+        let node_index = self.nodes.len();
+        if indexed_bounds.len() == 1 {
+            let (light_index, bounds) = indexed_bounds[0];
+            self.nodes.push(LightBvhNode {
+                bounds: bounds,
+                parent: parent,
+                kind: LightBvhNodeKind::Leaf(light_index)
+            });
+            self.leaves[light_index] = node_index;
+            return Some(node_index);
+        }
+
+        let bounding_box = indexed_bounds[1..].iter().fold(indexed_bounds[0].1.bounding_box, |acc, &(_, ref bounds)| acc.get_union(&bounds.bounding_box));
+        let intensity = indexed_bounds.iter().map(|&(_, ref bounds)| bounds.intensity).sum();
+        self.nodes.push(LightBvhNode {
+            bounds: LightBounds::new(bounding_box, intensity),
+            parent: parent,
+            kind: LightBvhNodeKind::Leaf(0)
+        });
+
+        let axis = bounding_box.get_longest_axis();
+        indexed_bounds.sort_by(|lhs, rhs| {
+            lhs.1.bounding_box.get_center()[axis].partial_cmp(&rhs.1.bounding_box.get_center()[axis]).unwrap_or(::std::cmp::Ordering::Equal)
+        });
+        let (left_bounds, right_bounds) = indexed_bounds.split_at_mut(indexed_bounds.len() / 2);
+        let left = self.build(left_bounds, Some(node_index)).unwrap();
+        let right = self.build(right_bounds, Some(node_index)).unwrap();
+        self.nodes[node_index].kind = LightBvhNodeKind::Interior(left, right);
+
+        Some(node_index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Intensity over squared distance to the center, the distance is clamped to the half diagonal of the bounds so points inside do not blow up
+    fn get_importance(bounds: &LightBounds, point: &Point3) -> FloatType {
+        let distance_squared = (bounds.bounding_box.get_center() - point).norm_squared();
+        let half_diagonal_squared = bounds.bounding_box.get_extent().norm_squared() * 0.25;
+        bounds.intensity.max(0.0) / distance_squared.max(half_diagonal_squared).max(1e-9)
+    }
+
+    fn get_left_probability(&self, left: usize, right: usize, point: &Point3) -> FloatType {
+        let left_importance = Self::get_importance(&self.nodes[left].bounds, point);
+        let right_importance = Self::get_importance(&self.nodes[right].bounds, point);
+        if left_importance + right_importance > 0.0 {
+            left_importance / (left_importance + right_importance)
+        } else {
+            0.5
+        }
+    }
+
+    /// Index of the chosen light and the probability of choosing it, from one uniform random number
+    pub fn sample(&self, point: &Point3, random: FloatType) -> Option<(usize, FloatType)> {
+        let mut node_index = self.root?;
+        let mut random = random;
+        let mut probability = 1.0;
+
+        loop {
+            match self.nodes[node_index].kind {
+                LightBvhNodeKind::Leaf(light_index) => return Some((light_index, probability)),
+                LightBvhNodeKind::Interior(left, right) => {
+                    let left_probability = self.get_left_probability(left, right, point);
+                    if random < left_probability {
+                        random /= left_probability;
+                        probability *= left_probability;
+                        node_index = left;
+                    } else {
+                        random = ((random - left_probability) / (1.0 - left_probability)).min(1.0);
+                        probability *= 1.0 - left_probability;
+                        node_index = right;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Probability of sample choosing the light at the point
+    pub fn get_probability(&self, point: &Point3, light_index: usize) -> FloatType {
+        let mut node_index = match self.leaves.get(light_index) {
+            Some(node_index) => *node_index,
+            None => return 0.0
+        };
+        let mut probability = 1.0;
+
+        while let Some(parent) = self.nodes[node_index].parent {
+            if let LightBvhNodeKind::Interior(left, right) = self.nodes[parent].kind {
+                let left_probability = self.get_left_probability(left, right, point);
+                probability *= if node_index == left { left_probability } else { 1.0 - left_probability };
+            }
+            node_index = parent;
+        }
+
+        probability
+    }
+
+    pub fn get_bounding_box(&self) -> Option<BoundingBox> {
+        self.root.map(|root| self.nodes[root].bounds.bounding_box)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use defs::{TEST_TOLERANCE};
+
+    fn create_test_bounds() -> Vec<LightBounds> {
+        (0..37).map(|index| {
+            let position = Point3::new((index % 6) as FloatType * 3.0, (index / 6) as FloatType * 2.0, (index % 4) as FloatType);
+            LightBounds::new(BoundingBox::new(position, position), 1.0 + (index % 5) as FloatType)
+        }).collect()
+    }
+
+    #[test]
+    fn light_bvh_probabilities_sum_to_one() {
+        let bvh = LightBvh::new(create_test_bounds());
+        let point = Point3::new(4.0, 3.0, 1.0);
+
+        let probability_sum: FloatType = (0..bvh.len()).map(|light_index| bvh.get_probability(&point, light_index)).sum();
+        assert_relative_eq!(probability_sum, 1.0, epsilon = TEST_TOLERANCE);
+
+        for random in [0.0, 0.1, 0.37, 0.5, 0.93, 0.999999].iter() {
+            let (light_index, probability) = bvh.sample(&point, *random).unwrap();
+            assert_relative_eq!(probability, bvh.get_probability(&point, light_index), epsilon = TEST_TOLERANCE);
+        }
+    }
+
+    #[test]
+    fn light_bvh_prefers_near_lights() {
+        let bvh = LightBvh::new(create_test_bounds());
+        let near_point = Point3::new(0.0, 0.0, 0.5);
+
+        assert!(bvh.get_probability(&near_point, 0) > bvh.get_probability(&near_point, 35));
+        assert!(LightBvh::new(Vec::new()).sample(&near_point, 0.5).is_none());
+    }
+}
@@ -1,15 +1,26 @@
-use core::{LightSource, Ray, LightIntersection, RayIntersection, Color};
-use defs::{Vector3, Point3, FloatType};
+use core::{LightSource, LightEmission, LightBounds, BoundingBox, Ray, LightIntersection, RayIntersection, Color,
+           get_orthonormal_basis, get_uniform_sphere_direction, get_uniform_cone_direction, get_uniform_cone_pdf, get_cosine_weighted_direction};
+use defs::{Vector3, Point3, FloatType, float_consts};
+use basic::image::{HdrImage};
+use basic::photometry::{PhotometricProfile};
 use na;
 use na::{Unit};
+use std;
+use rand;
+use rand::{Rng};
+use tools::{get_rounding_error_bound};
 
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
 pub struct DotLightSource {
     color: Color,
     intensity: FloatType,
     position: Point3,
     attenuation_const: FloatType,
     attenuation_linear: FloatType,
-    attenuation_squared: FloatType
+    attenuation_squared: FloatType,
+    /// Intensity distribution oriented by the unit nadir axis and the unit C0 direction perpendicular to it
+    #[cfg_attr(feature = "serde-serialize", serde(default))]
+    photometric_profile: Option<(PhotometricProfile, Unit<Vector3>, Unit<Vector3>)>
 }
 
 impl DotLightSource {
@@ -20,6 +31,7 @@ impl DotLightSource {
                 attenuation_const: 0.0,
                 attenuation_linear: 0.0,
                 attenuation_squared: 1.0,
+                photometric_profile: None
         }
     }
 
@@ -30,6 +42,32 @@ impl DotLightSource {
                 attenuation_const: match constant {None => 0.0, Some(value) => value },
                 attenuation_linear: match linear {None => 0.0, Some(value) => value },
                 attenuation_squared: match squared {None => 0.0, Some(value) => value },
+                photometric_profile: None
+        }
+    }
+
+    /// The axis points towards the nadir of the profile and the C0 direction towards its zero horizontal angle.
+    /// Only the part of the C0 direction perpendicular to the axis is used, a C0 direction along the axis falls back to an arbitrary one
+    pub fn set_photometric_profile(&mut self, profile: Option<PhotometricProfile>, axis: Vector3, c0_direction: Vector3) {
+        let axis = Unit::new_normalize(axis);
+        let perpendicular = c0_direction - axis.as_ref() * c0_direction.dot(axis.as_ref());
+        let c0_direction = if perpendicular.norm_squared() > 0.0 {
+            Unit::new_normalize(perpendicular)
+        } else {
+            Unit::new_normalize(get_orthonormal_basis(axis.as_ref()).0)
+        };
+        self.photometric_profile = profile.map(|profile| (profile, axis, c0_direction));
+    }
+
+    pub fn get_photometric_profile(&self) -> Option<&PhotometricProfile> {
+        self.photometric_profile.as_ref().map(|&(ref profile, _, _)| profile)
+    }
+
+    /// Scaling of the intensity towards the direction, one without a profile
+    fn get_profile_factor(&self, direction: &Vector3) -> FloatType {
+        match self.photometric_profile {
+            Some((ref profile, ref axis, ref c0_direction)) => profile.get_relative_intensity(direction, axis.as_ref(), c0_direction.as_ref()),
+            None => 1.0
         }
     }
 
@@ -43,7 +81,7 @@ impl LightSource for DotLightSource {
         let intersection_point = intersection.get_intersection_point();
         let to_intersection_point_vector = intersection_point - self.position;
 
-        Some(Ray::new_single_shot(self.position, to_intersection_point_vector))
+        Some(Ray::new_shadow(self.position, to_intersection_point_vector, intersection))
     }
 
     fn get_illumination_at(&self, intersection: &RayIntersection) -> Option<LightIntersection> {
@@ -52,7 +90,9 @@ impl LightSource for DotLightSource {
         let attenuation = self.get_attenuation(distance);
         let intersection_to_light_vector = self.position - intersection_point;
 
-        let result_color = self.color.mul_scalar(&(attenuation * self.intensity));
+        let profile_factor = self.get_profile_factor(&(intersection_point - self.position));
+
+        let result_color = self.color.mul_scalar(&(attenuation * self.intensity * profile_factor));
 
         Some(LightIntersection::new(result_color, intersection_to_light_vector))
     }
@@ -60,20 +100,79 @@ impl LightSource for DotLightSource {
     fn get_intersection(&self, _ray: &Ray) -> Option<LightIntersection> {
         None
     }
+
+    /// Emits uniformly in every direction, the custom attenuation is not taken into account
+    fn sample_emission(&self, u1: FloatType, u2: FloatType) -> Option<LightEmission> {
+        let direction = get_uniform_sphere_direction(u1, u2);
+        let power = self.color.mul_scalar(&(float_consts::PI * self.intensity * 4.0 * float_consts::PI * self.get_profile_factor(&direction)));
+
+        Some(LightEmission::new(Ray::new(self.position, direction), power))
+    }
+
+    fn get_emission_direction_pdf(&self, _direction: &Vector3) -> Option<FloatType> {
+        Some((4.0 * float_consts::PI).recip())
+    }
+
+    fn get_bounds(&self) -> Option<LightBounds> {
+        Some(LightBounds::new(BoundingBox::new(self.position, self.position), self.color.intensity_avg() * self.intensity))
+    }
 }
 
 
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
 pub struct SpotLightSource {
     dot_light: DotLightSource,
     direction: Unit<Vector3>,
-    max_angle_rad: FloatType
+    max_angle_rad: FloatType,
+    /// Angle where the smooth falloff towards the edge of the cone starts, None for a hard edge
+    #[cfg_attr(feature = "serde-serialize", serde(default))]
+    falloff_start_rad: Option<FloatType>
 }
 
 impl SpotLightSource {
     pub fn new(dot_light: DotLightSource, direction: Vector3, max_angle_radian: FloatType) -> Self {
         Self {  dot_light: dot_light,
                 direction: Unit::new_normalize(direction),
-                max_angle_rad: max_angle_radian}
+                max_angle_rad: max_angle_radian,
+                falloff_start_rad: None}
+    }
+
+    /// Full intensity inside the inner cone fading smoothly to nothing at the outer cone
+    pub fn new_with_falloff(dot_light: DotLightSource, direction: Vector3, inner_angle_radian: FloatType, outer_angle_radian: FloatType) -> Self {
+        let mut result = Self::new(dot_light, direction, outer_angle_radian);
+        result.set_falloff_start(Some(inner_angle_radian));
+        result
+    }
+
+    pub fn get_falloff_start(&self) -> Option<FloatType> {
+        self.falloff_start_rad
+    }
+
+    pub fn set_falloff_start(&mut self, inner_angle_radian: Option<FloatType>) {
+        self.falloff_start_rad = inner_angle_radian.map(|angle| angle.max(0.0).min(self.max_angle_rad));
+    }
+
+    /// The profile is oriented by the direction of the spot and the C0 direction around it, the cone still limits it
+    pub fn set_photometric_profile(&mut self, profile: Option<PhotometricProfile>, c0_direction: Vector3) {
+        let direction = *self.direction.as_ref();
+        self.dot_light.set_photometric_profile(profile, direction, c0_direction);
+    }
+
+    /// Smoothstep between the outer and inner cone cosines, zero outside the cone
+    fn get_falloff_factor(&self, direction: &Vector3) -> FloatType {
+        let cos_angle = direction.normalize().dot(self.direction.as_ref());
+        let cos_outer = self.max_angle_rad.cos();
+        if cos_angle < cos_outer {
+            return 0.0;
+        }
+
+        match self.falloff_start_rad {
+            Some(inner_angle) if inner_angle < self.max_angle_rad => {
+                let ratio = ((cos_angle - cos_outer) / (inner_angle.cos() - cos_outer)).min(1.0);
+                ratio * ratio * (3.0 - 2.0 * ratio)
+            },
+            _ => 1.0
+        }
     }
 }
 
@@ -89,10 +188,567 @@ impl LightSource for SpotLightSource {
     }
 
     fn get_illumination_at(&self, intersection: &RayIntersection) -> Option<LightIntersection> {
-        self.dot_light.get_illumination_at(intersection)
+        let falloff = self.get_falloff_factor(&(intersection.get_intersection_point() - self.dot_light.position));
+        self.dot_light.get_illumination_at(intersection).map(|illumination| illumination.get_shadowed(&Color::one().mul_scalar(&falloff)))
+    }
+
+    fn get_intersection(&self, _ray: &Ray) -> Option<LightIntersection> {
+        None
+    }
+
+    fn sample_emission(&self, u1: FloatType, u2: FloatType) -> Option<LightEmission> {
+        let cos_max_angle = self.max_angle_rad.cos();
+        let direction = get_uniform_cone_direction(self.direction.as_ref(), cos_max_angle, u1, u2);
+        let intensity = self.dot_light.intensity * self.dot_light.get_profile_factor(&direction) * self.get_falloff_factor(&direction);
+        let power = self.dot_light.color.mul_scalar(&(float_consts::PI * intensity / get_uniform_cone_pdf(cos_max_angle)));
+
+        Some(LightEmission::new(Ray::new(self.dot_light.position, direction), power))
+    }
+
+    fn get_emission_direction_pdf(&self, direction: &Vector3) -> Option<FloatType> {
+        let cos_max_angle = self.max_angle_rad.cos();
+        if direction.normalize().dot(self.direction.as_ref()) >= cos_max_angle {
+            Some(get_uniform_cone_pdf(cos_max_angle))
+        } else {
+            Some(0.0)
+        }
+    }
+
+    fn get_bounds(&self) -> Option<LightBounds> {
+        self.dot_light.get_bounds()
+    }
+}
+
+
+/// Light arriving in parallel from a far away source like the sun, without attenuation
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
+pub struct DirectionalLightSource {
+    color: Color,
+    intensity: FloatType,
+    direction: Unit<Vector3>,
+    scene_radius: FloatType,
+    angular_diameter_rad: FloatType,
+    sample_count: usize
+}
+
+impl DirectionalLightSource {
+    /// The direction is the one the light travels in, shadow rays start at scene_radius distance from the illuminated point, so it has to enclose every model
+    pub fn new(color: Color, intensity: FloatType, direction: Vector3, scene_radius: FloatType) -> Self {
+        Self {  color: color,
+                intensity: intensity,
+                direction: Unit::new_normalize(direction),
+                scene_radius: scene_radius,
+                angular_diameter_rad: 0.0,
+                sample_count: 1}
+    }
+
+    /// Disc of the given apparent size casting soft shadows, sampled with sample_count shadow rays. The sun is about 0.0093 radians wide
+    pub fn new_with_angular_diameter(color: Color, intensity: FloatType, direction: Vector3, scene_radius: FloatType, angular_diameter_radian: FloatType, sample_count: usize) -> Self {
+        let mut result = Self::new(color, intensity, direction, scene_radius);
+        result.set_angular_diameter(angular_diameter_radian, sample_count);
+        result
+    }
+
+    pub fn get_direction(&self) -> &Vector3 {
+        self.direction.as_ref()
+    }
+
+    pub fn get_angular_diameter(&self) -> FloatType {
+        self.angular_diameter_rad
+    }
+
+    pub fn set_angular_diameter(&mut self, angular_diameter_radian: FloatType, sample_count: usize) {
+        self.angular_diameter_rad = angular_diameter_radian.max(0.0);
+        self.sample_count = if self.angular_diameter_rad > 0.0 { sample_count.max(1) } else { 1 };
+    }
+
+    fn get_shadow_ray(&self, intersection: &RayIntersection, to_light: &Vector3) -> Ray {
+        Ray::new_shadow(intersection.get_intersection_point() + to_light * self.scene_radius, -to_light, intersection)
+    }
+}
+
+impl LightSource for DirectionalLightSource {
+    fn get_ray_to_intersection(&self, intersection: &RayIntersection) -> Option<Ray> {
+        Some(self.get_shadow_ray(intersection, &-self.direction.as_ref()))
+    }
+
+    fn get_illumination_at(&self, _intersection: &RayIntersection) -> Option<LightIntersection> {
+        Some(LightIntersection::new(self.color.mul_scalar(&self.intensity), -self.direction.as_ref()))
+    }
+
+    fn get_intersection(&self, _ray: &Ray) -> Option<LightIntersection> {
+        None
+    }
+
+    /// Shadow rays spread uniformly over the disc of the source, each carrying an equal share of the illumination
+    fn get_illumination_samples(&self, intersection: &RayIntersection) -> Vec<(Ray, LightIntersection)> {
+        let to_light = -self.direction.as_ref();
+        if self.angular_diameter_rad <= 0.0 {
+            return vec![(self.get_shadow_ray(intersection, &to_light), LightIntersection::new(self.color.mul_scalar(&self.intensity), to_light))];
+        }
+
+        let mut random_generator = rand::thread_rng();
+        let cos_max_angle = (self.angular_diameter_rad * 0.5).cos();
+        let sample_illumination = self.color.mul_scalar(&(self.intensity / self.sample_count as FloatType));
+
+        (0..self.sample_count).map(|_| {
+            let direction = get_uniform_cone_direction(&to_light, cos_max_angle, random_generator.gen::<FloatType>(), random_generator.gen::<FloatType>());
+            (self.get_shadow_ray(intersection, &direction), LightIntersection::new(sample_illumination, direction))
+        }).collect()
+    }
+}
+
+
+/// Sphere of uniform radiance, shading points sample the cone of directions it covers
+pub struct SphereLightSource {
+    radiance: Color,
+    origo: Point3,
+    radius: FloatType,
+    sample_count: usize
+}
+
+impl SphereLightSource {
+    pub fn new(radiance: Color, origo: Point3, radius: FloatType) -> Self {
+        Self {  radiance: radiance,
+                origo: origo,
+                radius: radius,
+                sample_count: 16}
+    }
+
+    pub fn get_sample_count(&self) -> usize {
+        self.sample_count
+    }
+
+    pub fn set_sample_count(&mut self, sample_count: usize) {
+        self.sample_count = sample_count.max(1);
+    }
+
+    /// Axis towards the center and the cosine of the half angle of the cone covered by the sphere, None from inside it
+    fn get_visible_cone(&self, point: &Point3) -> Option<(Vector3, FloatType)> {
+        let to_origo = self.origo - point;
+        let distance = to_origo.norm();
+        if distance <= self.radius {
+            None
+        } else {
+            let cos_max_angle = (1.0 - (self.radius / distance).powi(2)).max(0.0).sqrt();
+            Some((to_origo / distance, cos_max_angle))
+        }
+    }
+
+    /// Nearest point of the sphere along the direction, grazing directions land on the silhouette.
+    /// The point is pushed out along the normal by its rounding error, so shadow rays leaving it do not hit the sphere itself
+    fn get_surface_point(&self, point: &Point3, direction: &Vector3) -> Point3 {
+        let to_origo = self.origo - point;
+        let projection = direction.dot(&to_origo);
+        let discriminant = (projection.powi(2) - to_origo.norm_squared() + self.radius.powi(2)).max(0.0);
+        let surface_point = point + direction * (projection - discriminant.sqrt());
+
+        let normal = Unit::new_normalize(surface_point - self.origo);
+        let largest_coordinate = surface_point.iter().chain(point.iter()).fold(self.radius, |acc: FloatType, coordinate| acc.max(coordinate.abs()));
+        self.origo + normal.as_ref() * (self.radius + get_rounding_error_bound(32) * largest_coordinate)
+    }
+}
+
+impl LightSource for SphereLightSource {
+    fn get_ray_to_intersection(&self, intersection: &RayIntersection) -> Option<Ray> {
+        let intersection_point = intersection.get_intersection_point();
+        self.get_visible_cone(intersection_point).map(|(axis, _)| {
+            let surface_point = self.get_surface_point(intersection_point, &axis);
+            Ray::new_shadow(surface_point, intersection_point - surface_point, intersection)
+        })
+    }
+
+    /// The sphere seen as a point at its center, exact for surfaces facing it
+    fn get_illumination_at(&self, intersection: &RayIntersection) -> Option<LightIntersection> {
+        self.get_visible_cone(intersection.get_intersection_point()).map(|(axis, cos_max_angle)| {
+            let sin_squared = 1.0 - cos_max_angle.powi(2);
+            LightIntersection::new(self.radiance.mul_scalar(&sin_squared), axis)
+        })
     }
 
+    /// Camera and reflection rays see the emissive model itself
     fn get_intersection(&self, _ray: &Ray) -> Option<LightIntersection> {
         None
     }
-}
\ No newline at end of file
+
+    fn get_illumination_samples(&self, intersection: &RayIntersection) -> Vec<(Ray, LightIntersection)> {
+        let intersection_point = intersection.get_intersection_point();
+        match self.get_visible_cone(intersection_point) {
+            Some((axis, cos_max_angle)) => {
+                let mut random_generator = rand::thread_rng();
+                let weight = (float_consts::PI * get_uniform_cone_pdf(cos_max_angle) * self.sample_count as FloatType).recip();
+
+                (0..self.sample_count).map(|_| {
+                    let direction = get_uniform_cone_direction(&axis, cos_max_angle, random_generator.gen::<FloatType>(), random_generator.gen::<FloatType>());
+                    let surface_point = self.get_surface_point(intersection_point, &direction);
+                    let ray = Ray::new_shadow(surface_point, intersection_point - surface_point, intersection);
+                    (ray, LightIntersection::new(self.radiance.mul_scalar(&weight), direction))
+                }).collect()
+            },
+            None => Vec::new()
+        }
+    }
+
+    /// The point is drawn with the random numbers, the cosine weighted direction around its normal with the thread generator
+    fn sample_emission(&self, u1: FloatType, u2: FloatType) -> Option<LightEmission> {
+        let mut random_generator = rand::thread_rng();
+        let normal = get_uniform_sphere_direction(u1, u2);
+        let direction = get_cosine_weighted_direction(&normal, random_generator.gen::<FloatType>(), random_generator.gen::<FloatType>());
+        let area = 4.0 * float_consts::PI * self.radius.powi(2);
+        let power = self.radiance.mul_scalar(&(float_consts::PI * area));
+
+        Some(LightEmission::new(Ray::new(self.origo + normal * self.radius, direction), power))
+    }
+
+    /// Illumination of a facing surface is radiance times radius squared over distance squared, like a dot light
+    fn get_bounds(&self) -> Option<LightBounds> {
+        let radius_vector = Vector3::new(self.radius, self.radius, self.radius);
+        Some(LightBounds::new(BoundingBox::new(self.origo - radius_vector, self.origo + radius_vector), self.radiance.intensity_avg() * self.radius.powi(2)))
+    }
+}
+
+
+fn get_cumulative_distribution(weights: &[FloatType]) -> (Vec<FloatType>, FloatType) {
+    let mut sum = 0.0;
+    let mut cdf: Vec<FloatType> = weights.iter().map(|weight| {
+        sum += *weight;
+        sum
+    }).collect();
+
+    if sum > 0.0 {
+        for value in cdf.iter_mut() {
+            *value /= sum;
+        }
+    }
+    (cdf, sum)
+}
+
+fn sample_cumulative_distribution(cdf: &[FloatType], random: FloatType) -> usize {
+    match cdf.binary_search_by(|value| value.partial_cmp(&random).unwrap_or(std::cmp::Ordering::Less)) {
+        Ok(index) => index,
+        Err(index) => index.min(cdf.len() - 1)
+    }
+}
+
+/// Light arriving from every direction as given by an equirectangular image, +Y is up and the image center faces +X
+pub struct EnvironmentLightSource {
+    image: HdrImage,
+    intensity: FloatType,
+    scene_radius: FloatType,
+    sample_count: usize,
+    row_cdf: Vec<FloatType>,
+    column_cdfs: Vec<Vec<FloatType>>,
+    weight_sum: FloatType
+}
+
+impl EnvironmentLightSource {
+    /// Shadow rays start at scene_radius distance from the illuminated point, so it has to enclose every model
+    pub fn new(image: HdrImage, intensity: FloatType, scene_radius: FloatType, sample_count: usize) -> Self {
+        let (width, height) = (image.get_width(), image.get_height());
+        let mut row_weights: Vec<FloatType> = Vec::with_capacity(height);
+        let mut column_cdfs: Vec<Vec<FloatType>> = Vec::with_capacity(height);
+
+        for y in 0..height {
+            let sin_theta = (float_consts::PI * (y as FloatType + 0.5) / height as FloatType).sin();
+            let weights: Vec<FloatType> = (0..width).map(|x| image.get_pixel(x, y).intensity_avg() * sin_theta).collect();
+            let (cdf, row_weight) = get_cumulative_distribution(&weights);
+            row_weights.push(row_weight);
+            column_cdfs.push(cdf);
+        }
+        let (row_cdf, weight_sum) = get_cumulative_distribution(&row_weights);
+
+        Self {
+            image: image,
+            intensity: intensity,
+            scene_radius: scene_radius,
+            sample_count: sample_count.max(1),
+            row_cdf: row_cdf,
+            column_cdfs: column_cdfs,
+            weight_sum: weight_sum
+        }
+    }
+
+    fn get_direction(u: FloatType, v: FloatType) -> Vector3 {
+        let phi = 2.0 * float_consts::PI * u;
+        let theta = float_consts::PI * v;
+        Vector3::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin())
+    }
+
+    fn get_pixel_of_direction(&self, direction: &Vector3) -> (usize, usize) {
+        let direction = direction.normalize();
+        let theta = direction.y.max(-1.0).min(1.0).acos();
+        let mut phi = direction.z.atan2(direction.x);
+        if phi < 0.0 {
+            phi += 2.0 * float_consts::PI;
+        }
+        let x = (phi / (2.0 * float_consts::PI) * self.image.get_width() as FloatType) as usize;
+        let y = (theta / float_consts::PI * self.image.get_height() as FloatType) as usize;
+        (x.min(self.image.get_width() - 1), y.min(self.image.get_height() - 1))
+    }
+
+    pub fn get_radiance(&self, direction: &Vector3) -> Color {
+        let (x, y) = self.get_pixel_of_direction(direction);
+        self.image.get_pixel(x, y).mul_scalar(&self.intensity)
+    }
+
+    /// Probability density per solid angle of sampling the direction
+    pub fn get_direction_pdf(&self, direction: &Vector3) -> FloatType {
+        if self.weight_sum <= 0.0 {
+            return (4.0 * float_consts::PI).recip();
+        }
+        let (x, y) = self.get_pixel_of_direction(direction);
+        let (width, height) = (self.image.get_width() as FloatType, self.image.get_height() as FloatType);
+        let weight = self.image.get_pixel(x, y).intensity_avg() * (float_consts::PI * (y as FloatType + 0.5) / height).sin();
+        let sin_theta = (1.0 - direction.normalize().y.powi(2)).max(0.0).sqrt();
+
+        if sin_theta > 0.0 {
+            (weight / self.weight_sum) * width * height / (2.0 * float_consts::PI.powi(2) * sin_theta)
+        } else {
+            0.0
+        }
+    }
+
+    /// Direction drawn proportional to the luminance of the environment, with its radiance and pdf
+    pub fn sample_direction(&self, u1: FloatType, u2: FloatType) -> (Vector3, Color, FloatType) {
+        let (width, height) = (self.image.get_width(), self.image.get_height());
+        let direction = if self.weight_sum > 0.0 {
+            let y = sample_cumulative_distribution(&self.row_cdf, u1);
+            let x = sample_cumulative_distribution(&self.column_cdfs[y], u2);
+            let jitter = rand::thread_rng().gen::<(FloatType, FloatType)>();
+            Self::get_direction((x as FloatType + jitter.0) / width as FloatType, (y as FloatType + jitter.1) / height as FloatType)
+        } else {
+            Self::get_direction(u2, (1.0 - 2.0 * u1).acos() / float_consts::PI)
+        };
+
+        (direction, self.get_radiance(&direction), self.get_direction_pdf(&direction))
+    }
+}
+
+impl LightSource for EnvironmentLightSource {
+    fn get_ray_to_intersection(&self, _intersection: &RayIntersection) -> Option<Ray> {
+        None
+    }
+
+    fn get_illumination_at(&self, _intersection: &RayIntersection) -> Option<LightIntersection> {
+        None
+    }
+
+    fn get_intersection(&self, ray: &Ray) -> Option<LightIntersection> {
+        Some(LightIntersection::new(self.get_radiance(ray.get_direction()), -ray.get_direction()))
+    }
+
+    /// Estimates of the irradiance divided by pi, so a white environment of unit radiance lights a diffuse surface to its diffuse color
+    fn get_illumination_samples(&self, intersection: &RayIntersection) -> Vec<(Ray, LightIntersection)> {
+        let intersection_point = intersection.get_intersection_point();
+        let normal = intersection.get_normal_vector();
+        let mut random_generator = rand::thread_rng();
+
+        (0..self.sample_count).filter_map(|_| {
+            let (direction, radiance, pdf) = self.sample_direction(random_generator.gen::<FloatType>(), random_generator.gen::<FloatType>());
+            if pdf <= 0.0 || direction.dot(normal) <= 0.0 {
+                None
+            } else {
+                let weight = (float_consts::PI * pdf * self.sample_count as FloatType).recip();
+                let ray = Ray::new_shadow(intersection_point + direction * self.scene_radius, -direction, intersection);
+                Some((ray, LightIntersection::new(radiance.mul_scalar(&weight), direction)))
+            }
+        }).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use defs::{TEST_TOLERANCE};
+    use core::{Material};
+
+    fn create_test_environment() -> EnvironmentLightSource {
+        let (width, height) = (8, 4);
+        let pixels: Vec<Color> = (0..width * height).map(|index| {
+            if index == 2 * width + 2 { Color::new(100.0, 100.0, 100.0) } else { Color::new(0.1, 0.1, 0.1) }
+        }).collect();
+        EnvironmentLightSource::new(HdrImage::new(width, height, pixels), 1.0, 100.0, 16)
+    }
+
+    #[test]
+    fn environment_importance_sampling_prefers_bright_pixels() {
+        let environment = create_test_environment();
+        let bright_direction = EnvironmentLightSource::get_direction(2.5 / 8.0, 2.5 / 4.0);
+
+        let bright_samples = (0..200).filter(|_| {
+            let (direction, _, _) = environment.sample_direction(rand::thread_rng().gen(), rand::thread_rng().gen());
+            environment.get_pixel_of_direction(&direction) == (2, 2)
+        }).count();
+
+        assert_eq!(environment.get_pixel_of_direction(&bright_direction), (2, 2));
+        assert!(bright_samples > 150);
+        assert!(environment.get_direction_pdf(&bright_direction) > environment.get_direction_pdf(&Vector3::new(0.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn environment_uniform_illumination_of_diffuse_surface() {
+        let environment = EnvironmentLightSource::new(HdrImage::new(4, 2, vec![Color::one(); 8]), 1.0, 100.0, 4000);
+        let ray = Ray::new(Point3::new(0.0, 2.0, 0.0), Vector3::new(0.0, -1.0, 0.0));
+        let intersection = RayIntersection::new(Vector3::new(0.0, 1.0, 0.0), Point3::origin(), &ray, Material::new_diffuse(Color::one(), None), false).unwrap();
+
+        let diffuse = environment.get_illumination_samples(&intersection).iter().fold(Color::zero(), |acc, &(_, ref light_intersection)| {
+            acc + Material::get_diffuse_illumination(&intersection, light_intersection).unwrap()
+        });
+
+        assert!((diffuse.intensity_avg() - 1.0).abs() < 0.1);
+        assert!(environment.get_intersection(&ray).unwrap().get_illumination().equal_eps(&Color::one()));
+    }
+
+    #[test]
+    fn shadow_rays_keep_time_of_shaded_ray() {
+        let ray = Ray::new(Point3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 0.0, -1.0)).set_time(0.75);
+        let intersection = RayIntersection::new(Vector3::new(0.0, 0.0, 1.0), Point3::origin(), &ray, Material::new_diffuse(Color::one(), None), false).unwrap();
+        let light_sources: Vec<Box<LightSource>> = vec![Box::new(DotLightSource::new_natural(Color::one(), 1.0, Point3::new(0.0, 0.0, 2.0))),
+                                                        Box::new(DirectionalLightSource::new_with_angular_diameter(Color::one(), 1.0, Vector3::new(0.0, 0.0, -1.0), 10.0, 0.1, 4)),
+                                                        Box::new(SphereLightSource::new(Color::one(), Point3::new(0.0, 0.0, 3.0), 0.5))];
+
+        for light_source in light_sources.iter() {
+            assert_eq!(light_source.get_ray_to_intersection(&intersection).unwrap().get_time(), 0.75);
+            assert!(light_source.get_illumination_samples(&intersection).iter().all(|&(ref shadow_ray, _)| shadow_ray.get_time() == 0.75));
+        }
+    }
+
+    #[test]
+    fn spot_light_smooth_falloff_and_profile() {
+        let dot_light = DotLightSource::new_natural(Color::one(), 1.0, Point3::new(0.0, 0.0, 1.0));
+        let mut spot_light = SpotLightSource::new_with_falloff(dot_light, Vector3::new(0.0, 0.0, -1.0), 0.2, 0.6);
+        let ray = Ray::new(Point3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        let illumination_at = |spot_light: &SpotLightSource, x: FloatType| {
+            let point = Point3::new(x, 0.0, 0.0);
+            let intersection = RayIntersection::new(Vector3::new(0.0, 0.0, 1.0), point, &ray, Material::new_diffuse(Color::one(), None), false).unwrap();
+            spot_light.get_illumination_at(&intersection).unwrap().get_illumination().intensity_avg() * (1.0 + x * x)
+        };
+
+        let falloff: Vec<FloatType> = [0.0, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7].iter().map(|angle: &FloatType| illumination_at(&spot_light, angle.tan())).collect();
+        assert_relative_eq!(falloff[0], 1.0, epsilon = TEST_TOLERANCE);
+        assert_relative_eq!(falloff[1], 1.0, epsilon = TEST_TOLERANCE);
+        assert!(falloff.windows(2).skip(1).all(|pair| pair[1] < pair[0] || pair[1] == 0.0));
+        assert!(falloff[3] > 0.0 && falloff[3] < 1.0);
+        assert_relative_eq!(falloff[6], 0.0, epsilon = TEST_TOLERANCE);
+
+        // The profile halves the intensity at 30 degrees, still inside the hard edged cone
+        let profile = PhotometricProfile::new(vec![0.0, 60.0], vec![0.0], vec![vec![2.0, 0.0]]).unwrap();
+        spot_light.set_falloff_start(None);
+        spot_light.set_photometric_profile(Some(profile), Vector3::new(1.0, 0.0, 0.0));
+        assert_relative_eq!(illumination_at(&spot_light, 0.0), 1.0, epsilon = TEST_TOLERANCE);
+        assert_relative_eq!(illumination_at(&spot_light, (30.0 as FloatType).to_radians().tan()), 0.5, epsilon = TEST_TOLERANCE);
+        assert_relative_eq!(illumination_at(&spot_light, (0.7 as FloatType).tan()), 0.0, epsilon = TEST_TOLERANCE);
+    }
+
+    #[test]
+    fn dot_light_profile_oriented_by_c0_direction() {
+        // Facing down with C0 towards +y, so C90 is towards -x seen from above
+        let profile = PhotometricProfile::new(vec![0.0, 90.0], vec![0.0, 90.0, 180.0, 270.0, 360.0],
+                                              vec![vec![4.0, 4.0], vec![4.0, 3.0], vec![4.0, 2.0], vec![4.0, 1.0], vec![4.0, 4.0]]).unwrap();
+        let mut dot_light = DotLightSource::new_natural(Color::one(), 1.0, Point3::new(0.0, 0.0, 0.0));
+        dot_light.set_photometric_profile(Some(profile), Vector3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 1.0, -0.5));
+        let ray = Ray::new(Point3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        let illumination_at = |point: Point3| {
+            let intersection = RayIntersection::new(Vector3::new(0.0, 0.0, 1.0), point, &ray, Material::new_diffuse(Color::one(), None), false).unwrap();
+            dot_light.get_illumination_at(&intersection).unwrap().get_illumination().intensity_avg()
+        };
+
+        assert_relative_eq!(illumination_at(Point3::new(0.0, 1.0, 0.0)), 1.0, epsilon = TEST_TOLERANCE);
+        assert_relative_eq!(illumination_at(Point3::new(-1.0, 0.0, 0.0)), 0.75, epsilon = TEST_TOLERANCE);
+        assert_relative_eq!(illumination_at(Point3::new(0.0, -1.0, 0.0)), 0.5, epsilon = TEST_TOLERANCE);
+        assert_relative_eq!(illumination_at(Point3::new(1.0, 0.0, 0.0)), 0.25, epsilon = TEST_TOLERANCE);
+        assert_relative_eq!(illumination_at(Point3::new(-1.0, -1.0, 0.0)) * 2.0, 0.625, epsilon = TEST_TOLERANCE);
+    }
+
+    #[test]
+    fn directional_light_soft_shadow_edge() {
+        use core::{World, RayCaster};
+        use basic::{SimpleIntersector, SimpleColorCalculator, SimpleIlluminator, ModelVec};
+        use basic::model::{SolidSphere};
+
+        let light = DirectionalLightSource::new_with_angular_diameter(Color::one(), 2.0, Vector3::new(0.0, 0.0, -1.0), 100.0, 0.2, 64);
+        let floor_ray = Ray::new(Point3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        let floor = RayIntersection::new(Vector3::new(0.0, 0.0, 1.0), Point3::new(5.0, 5.0, 0.0), &floor_ray, Material::new_diffuse(Color::one(), None), false).unwrap();
+
+        let samples = light.get_illumination_samples(&floor);
+        let total = samples.iter().fold(Color::zero(), |acc, &(_, ref light_intersection)| acc + *light_intersection.get_illumination());
+        assert_eq!(samples.len(), 64);
+        assert!(total.equal_eps(&Color::new(2.0, 2.0, 2.0)));
+        assert!(samples.iter().all(|&(ref ray, _)| (ray.get_origin() - floor.get_intersection_point()).norm() > 99.0));
+
+        let models: ModelVec = vec![Box::new(SolidSphere::new_positioned(Material::new_diffuse(Color::one(), None), Point3::new(0.0, 0.0, 10.0), 1.0))];
+        let world = World::new(SimpleIntersector::new(models), SimpleColorCalculator::new(), SimpleIlluminator::new(Vec::new()), 4);
+        let shadowing = |x: FloatType| {
+            let point = RayIntersection::new(Vector3::new(0.0, 0.0, 1.0), Point3::new(x, 0.0, 0.0), &floor_ray, Material::new_diffuse(Color::one(), None), false).unwrap();
+            light.get_illumination_samples(&point).iter().filter(|&&(ref ray, _)| world.cast_colored_light_ray(ray, &point).is_some()).count()
+        };
+
+        assert_eq!(shadowing(0.0), 0);
+        assert_eq!(shadowing(3.0), 64);
+        let penumbra = shadowing(1.0);
+        assert!(penumbra > 0 && penumbra < 64);
+    }
+
+    #[test]
+    fn sphere_light_samples_match_analytic_illumination() {
+        let mut sphere_light = SphereLightSource::new(Color::one(), Point3::new(0.0, 0.0, 3.0), 1.0);
+        sphere_light.set_sample_count(4000);
+        let ray = Ray::new(Point3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        let intersection = RayIntersection::new(Vector3::new(0.0, 0.0, 1.0), Point3::origin(), &ray, Material::new_diffuse(Color::one(), None), false).unwrap();
+
+        let sampled = sphere_light.get_illumination_samples(&intersection).iter().fold(Color::zero(), |acc, &(_, ref light_intersection)| {
+            acc + Material::get_diffuse_illumination(&intersection, light_intersection).unwrap()
+        });
+        let analytic = Material::get_diffuse_illumination(&intersection, &sphere_light.get_illumination_at(&intersection).unwrap()).unwrap();
+
+        assert_relative_eq!(analytic.intensity_avg(), 1.0 / 9.0, epsilon = TEST_TOLERANCE);
+        assert!((sampled.intensity_avg() - analytic.intensity_avg()).abs() < 0.01);
+        assert!(sphere_light.get_illumination_samples(&RayIntersection::new(Vector3::new(0.0, 0.0, 1.0), Point3::new(0.0, 0.0, 3.0), &ray, Material::new_useless(), false).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn emissive_sphere_registers_as_light() {
+        use core::{World, RayCaster};
+        use basic::{SimpleIntersector, SimpleColorCalculator, SimpleIlluminator, ModelVec};
+        use basic::model::{SolidSphere, SolidPlane};
+
+        let emission = Color::new(4.0, 4.0, 2.0);
+        let models: ModelVec = vec![Box::new(SolidSphere::new_positioned(Material::new_emissive(emission), Point3::new(0.0, 0.0, 3.0), 1.0)),
+                                    Box::new(SolidPlane::new(Material::new_diffuse(Color::one(), None)))];
+        assert!(models[0].get_light_source().is_some());
+        assert!(models[1].get_light_source().is_none());
+
+        let illuminator = SimpleIlluminator::new_with_emitters(Vec::new(), &models);
+        let world = World::new(SimpleIntersector::new(models), SimpleColorCalculator::new(), illuminator, 4);
+
+        let floor_color = world.cast_ray(&Ray::new(Point3::new(2.0, 0.0, 1.0), Vector3::new(0.0, 0.0, -1.0))).unwrap();
+        let emitter_color = world.cast_ray(&Ray::new(Point3::new(0.0, 0.0, 10.0), Vector3::new(0.0, 0.0, -1.0))).unwrap();
+
+        assert!(floor_color.intensity_avg() > 0.0);
+        assert!(emitter_color.equal_eps(&emission));
+    }
+
+    #[test]
+    fn emissive_sphere_samples_reach_unoccluded_floor() {
+        use core::{World, RayCaster, Model};
+        use basic::{SimpleIntersector, SimpleColorCalculator, SimpleIlluminator, ModelVec};
+        use basic::model::{SolidSphere, SolidPlane};
+
+        for height in [3.0, 30.0].iter() {
+            let floor = SolidPlane::new(Material::new_diffuse(Color::one(), None));
+            let floor_intersection = floor.get_intersection(&Ray::new(Point3::new(2.0, 0.0, 1.0), Vector3::new(0.0, 0.0, -1.0))).unwrap();
+            let models: ModelVec = vec![Box::new(SolidSphere::new_positioned(Material::new_emissive(Color::one()), Point3::new(0.0, 0.0, *height), 1.0)),
+                                        Box::new(floor)];
+            let mut light = SphereLightSource::new(Color::one(), Point3::new(0.0, 0.0, *height), 1.0);
+            light.set_sample_count(800);
+
+            let illuminator = SimpleIlluminator::new_with_emitters(Vec::new(), &models);
+            let world = World::new(SimpleIntersector::new(models), SimpleColorCalculator::new(), illuminator, 4);
+
+            let samples = light.get_illumination_samples(&floor_intersection);
+            assert_eq!(samples.len(), 800);
+            assert!(samples.iter().all(|&(ref ray, _)| world.cast_colored_light_ray(ray, &floor_intersection).is_some()));
+        }
+    }
+}
@@ -1,18 +1,32 @@
 use core::*;
 
 pub mod intersector;
+pub mod bvh;
 pub mod illuminator;
 pub mod colorcalculator;
 pub mod lightsource;
+pub mod photometry;
+pub mod lightbvh;
 pub mod model;
 pub mod postprocessing;
 pub mod rendering;
+pub mod image;
+pub mod animation;
+pub mod instance;
+pub mod scenegraph;
+pub mod debug;
+pub mod document;
+pub mod comparison;
+pub mod scattering;
+pub mod photonmap;
+pub mod bdpt;
 
 pub use self::intersector::*;
 pub use self::illuminator::*;
 pub use self::colorcalculator::*;
 pub use self::postprocessing::*;
 pub use self::rendering::*;
+pub use self::instance::*;
 
 
 pub type SimpleWorld = World<SimpleIntersector, SimpleColorCalculator, SimpleIlluminator>;
\ No newline at end of file
@@ -1,11 +1,12 @@
-use core::{Model, Material, RayIntersection, Ray, RayIntersectionError};
-use defs::{Point3, Vector3, FloatType};
+use core::{Model, Material, RayIntersection, Ray, RayIntersectionError, BoundingBox, LightSource, RayPacket, PACKET_SIZE};
+use basic::lightsource::{SphereLightSource};
+use defs::{Point3, Vector3, FloatType, float_consts};
 use tools::{CompareWithTolerance};
 use na;
 use na::{Unit};
-use std;
 use uuid::{Uuid};
 
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
 pub struct SolidSphere {
     material: Material,
     origo: Point3,
@@ -33,6 +34,36 @@ impl SolidSphere {
     }
 }
 
+impl SolidSphere {
+    /// Intersection from the two roots of the ray sphere equation, the larger first
+    fn get_intersection_from_roots(&self, ray: &Ray, t1: FloatType, t2: FloatType) -> Option<RayIntersection> {
+        let dir = ray.get_direction();
+        let origin = ray.get_origin();
+        let result_calc = |t: FloatType, inside: bool| {
+            // Reprojected onto the surface, so the rounding error of t does not end up in the point
+            let intersection_point = self.origo + (origin + dir * t - self.origo).normalize() * self.radius;
+            let normal = if !inside { intersection_point - self.origo } else { self.origo - intersection_point };
+
+            match RayIntersection::new_model_identifier(normal, intersection_point, ray, self.material, inside, self.identifier) {
+                Ok(intersection) => Some(intersection),
+                Err(RayIntersectionError::NoRayTravelDistance) => None,
+                _ => panic!("Unrecoverable RayIntersectin:new_model_identifier error")
+            }
+        };
+
+        if t1.is_sign_negative() && t2.is_sign_negative() {
+            None
+        } else if t1.is_sign_positive() && t2.is_sign_negative() {
+            result_calc(t1, true)
+        } else {
+            match result_calc(t2, false) {
+                Some(result) => Some(result),
+                None => result_calc(t1, true),
+            }
+        }
+    }
+}
+
 impl Model for SolidSphere {
     fn get_intersection(&self, ray: &Ray) -> Option<RayIntersection> {
         let dir = ray.get_direction();
@@ -49,32 +80,48 @@ impl Model for SolidSphere {
         } else {
             let t1 = (-b + determinant.sqrt()) / (2.0 * a);
             let t2 = (-b - determinant.sqrt()) / (2.0 * a);
-            let result_calc = |t, inside: bool| {
-                let intersection_point = origin + dir * t;
-                let normal = if !inside { intersection_point - self.origo } else { self.origo - intersection_point };
+            self.get_intersection_from_roots(ray, t1, t2)
+        }
+    }
 
-                match RayIntersection::new_model_identifier(normal, intersection_point, ray, self.material, inside, self.identifier) {
-                    Ok(intersection) => Some(intersection),
-                    Err(RayIntersectionError::NoRayTravelDistance) => None,
-                    _ => panic!("Unrecoverable RayIntersectin:new_model_identifier error")
-                }
-            };
+    fn get_packet_intersections(&self, packet: &RayPacket) -> Vec<Option<RayIntersection>> {
+        let mut a = [0.0; PACKET_SIZE];
+        let mut b = [0.0; PACKET_SIZE];
+        let mut determinant = [0.0; PACKET_SIZE];
+        for lane in 0..PACKET_SIZE {
+            let (ray_origo_x, ray_origo_y, ray_origo_z) = (packet.origin_x[lane] - self.origo.x, packet.origin_y[lane] - self.origo.y, packet.origin_z[lane] - self.origo.z);
+            let (dir_x, dir_y, dir_z) = (packet.direction_x[lane], packet.direction_y[lane], packet.direction_z[lane]);
+            a[lane] = dir_x * dir_x + dir_y * dir_y + dir_z * dir_z;
+            b[lane] = 2.0 * (ray_origo_x * dir_x + ray_origo_y * dir_y + ray_origo_z * dir_z);
+            let c = ray_origo_x * ray_origo_x + ray_origo_y * ray_origo_y + ray_origo_z * ray_origo_z - self.radius * self.radius;
+            determinant[lane] = b[lane] * b[lane] - 4.0 * a[lane] * c;
+        }
 
-            if t1.is_sign_negative() && t2.is_sign_negative() {
+        packet.iter().enumerate().map(|(lane, ray)| {
+            if determinant[lane].less_eps(&0.0) {
                 None
-            } else if t1.is_sign_positive() && t2.is_sign_negative() {
-                result_calc(t1, true)
             } else {
-                match result_calc(t2, false) {
-                    Some(result) => Some(result),
-                    None => result_calc(t1, true),
-                }
+                let t1 = (-b[lane] + determinant[lane].sqrt()) / (2.0 * a[lane]);
+                let t2 = (-b[lane] - determinant[lane].sqrt()) / (2.0 * a[lane]);
+                self.get_intersection_from_roots(ray, t1, t2)
             }
-        }
+        }).collect()
+    }
+
+    fn get_bounding_box(&self) -> Option<BoundingBox> {
+        let radius_vector = Vector3::new(self.radius, self.radius, self.radius);
+        Some(BoundingBox::new(self.origo - radius_vector, self.origo + radius_vector))
+    }
+
+    fn get_light_source(&self) -> Option<Box<LightSource>> {
+        self.material.get_emission().map(|emission| {
+            Box::new(SphereLightSource::new(*emission, self.origo, self.radius)) as Box<LightSource>
+        })
     }
 }
 
 
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
 pub struct SolidPlane {
     material: Material,
     base: Point3,
@@ -104,6 +151,33 @@ impl SolidPlane {
     }
 }
 
+impl SolidPlane {
+    fn get_intersection_at(&self, ray: &Ray, t: FloatType) -> Option<RayIntersection> {
+        let origin = ray.get_origin();
+        let dir = ray.get_direction();
+
+        if t.greater_eq_eps(&0.0) {
+            let is_inside = na::angle(&self.normal, dir).less_eq_eps(&float_consts::FRAC_PI_2);
+            let point = origin + dir * t;
+            if !is_inside {
+                match RayIntersection::new_model_identifier(self.normal, point, ray, self.material, is_inside, self.identifier) {
+                    Ok(intersection) => Some(intersection),
+                    Err(RayIntersectionError::NoRayTravelDistance) => None,
+                    _ => panic!("Unrecoverable RayIntersectin:new_model_identifier error")
+                }
+            } else {
+                match RayIntersection::new_model_identifier(-self.normal, point, ray, self.material, is_inside, self.identifier) {
+                    Ok(intersection) => Some(intersection),
+                    Err(RayIntersectionError::NoRayTravelDistance) => None,
+                    _ => panic!("Unrecoverable RayIntersectin:new_model_identifier error")
+                }
+            }
+        } else {
+            None
+        }
+    }
+}
+
 impl Model for SolidPlane {
     fn get_intersection(&self, ray: &Ray) -> Option<RayIntersection> {
         let origin = ray.get_origin();
@@ -114,37 +188,183 @@ impl Model for SolidPlane {
             let k = self.normal.x * self.base.x + self.normal.y * self.base.y + self.normal.z * self.base.z;
             let s = self.normal.x * origin.x + self.normal.y * origin.y + self.normal.z * origin.z;
             let t = (k-s) / u;
-            if t.greater_eq_eps(&0.0) {
-                let is_inside = na::angle(&self.normal, dir).less_eq_eps(&std::f64::consts::FRAC_PI_2);
-                let point = origin + dir * t;
-                if !is_inside {
-                    match RayIntersection::new_model_identifier(self.normal, point, ray, self.material, is_inside, self.identifier) {
-                        Ok(intersection) => Some(intersection),
-                        Err(RayIntersectionError::NoRayTravelDistance) => None,
-                        _ => panic!("Unrecoverable RayIntersectin:new_model_identifier error")
-                    }
-                } else {
-                    match RayIntersection::new_model_identifier(-self.normal, point, ray, self.material, is_inside, self.identifier) {
-                        Ok(intersection) => Some(intersection),
-                        Err(RayIntersectionError::NoRayTravelDistance) => None,
-                        _ => panic!("Unrecoverable RayIntersectin:new_model_identifier error")
-                    }
-                }
+            self.get_intersection_at(ray, t)
+        } else {
+            None
+        }
+    }
+
+    fn get_packet_intersections(&self, packet: &RayPacket) -> Vec<Option<RayIntersection>> {
+        let k = self.normal.x * self.base.x + self.normal.y * self.base.y + self.normal.z * self.base.z;
+        let mut u = [0.0; PACKET_SIZE];
+        let mut t = [0.0; PACKET_SIZE];
+        for lane in 0..PACKET_SIZE {
+            u[lane] = self.normal.x * packet.direction_x[lane] + self.normal.y * packet.direction_y[lane] + self.normal.z * packet.direction_z[lane];
+            let s = self.normal.x * packet.origin_x[lane] + self.normal.y * packet.origin_y[lane] + self.normal.z * packet.origin_z[lane];
+            t[lane] = (k - s) / u[lane];
+        }
+
+        packet.iter().enumerate().map(|(lane, ray)| {
+            if !u[lane].near_zero_eps() {
+                self.get_intersection_at(ray, t[lane])
             } else {
                 None
             }
+        }).collect()
+    }
+}
+
+
+/// Single sided intersection math with two sided shading, hits from behind flip the normal like SolidPlane
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
+pub struct SolidTriangle {
+    material: Material,
+    vertices: [Point3; 3],
+    normal: Vector3,
+    identifier: Uuid
+}
+
+impl SolidTriangle {
+    pub fn new(material: Material, first: Point3, second: Point3, third: Point3) -> Self {
+        Self {  material: material,
+                vertices: [first, second, third],
+                normal: (second - first).cross(&(third - first)).normalize(),
+                identifier: Uuid::new_v4()
+        }
+    }
+
+    pub fn set_custom_identifier(&mut self, identifier: Uuid) {
+        self.identifier = identifier;
+    }
+
+    pub fn get_vertices(&self) -> &[Point3; 3] {
+        &self.vertices
+    }
+
+    fn get_intersection_at(&self, ray: &Ray, t: FloatType) -> Option<RayIntersection> {
+        let dir = ray.get_direction();
+        let is_inside = self.normal.dot(dir) > 0.0;
+        let normal = if is_inside { -self.normal } else { self.normal };
+
+        match RayIntersection::new_model_identifier(normal, ray.get_origin() + dir * t, ray, self.material, is_inside, self.identifier) {
+            Ok(intersection) => Some(intersection),
+            Err(RayIntersectionError::NoRayTravelDistance) => None,
+            _ => panic!("Unrecoverable RayIntersectin:new_model_identifier error")
+        }
+    }
+}
+
+impl Model for SolidTriangle {
+    /// Moller-Trumbore intersection
+    fn get_intersection(&self, ray: &Ray) -> Option<RayIntersection> {
+        let dir = ray.get_direction();
+        let edge1 = self.vertices[1] - self.vertices[0];
+        let edge2 = self.vertices[2] - self.vertices[0];
+        let p = dir.cross(&edge2);
+        let determinant = edge1.dot(&p);
+        if determinant.near_zero_eps() {
+            return None;
+        }
+
+        let inverse_determinant = determinant.recip();
+        let to_origin = ray.get_origin() - self.vertices[0];
+        let u = to_origin.dot(&p) * inverse_determinant;
+        if u < 0.0 || u > 1.0 {
+            return None;
+        }
+
+        let q = to_origin.cross(&edge1);
+        let v = dir.dot(&q) * inverse_determinant;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = edge2.dot(&q) * inverse_determinant;
+        if t.greater_eq_eps(&0.0) {
+            self.get_intersection_at(ray, t)
         } else {
             None
         }
     }
-}
 
+    fn get_packet_intersections(&self, packet: &RayPacket) -> Vec<Option<RayIntersection>> {
+        let edge1 = self.vertices[1] - self.vertices[0];
+        let edge2 = self.vertices[2] - self.vertices[0];
+        let mut t = [0.0; PACKET_SIZE];
+        let mut is_hit = [false; PACKET_SIZE];
+
+        for lane in 0..PACKET_SIZE {
+            let (dir_x, dir_y, dir_z) = (packet.direction_x[lane], packet.direction_y[lane], packet.direction_z[lane]);
+            let (p_x, p_y, p_z) = (dir_y * edge2.z - dir_z * edge2.y, dir_z * edge2.x - dir_x * edge2.z, dir_x * edge2.y - dir_y * edge2.x);
+            let determinant = edge1.x * p_x + edge1.y * p_y + edge1.z * p_z;
+            let inverse_determinant = determinant.recip();
+
+            let (to_origin_x, to_origin_y, to_origin_z) = (packet.origin_x[lane] - self.vertices[0].x, packet.origin_y[lane] - self.vertices[0].y, packet.origin_z[lane] - self.vertices[0].z);
+            let u = (to_origin_x * p_x + to_origin_y * p_y + to_origin_z * p_z) * inverse_determinant;
+
+            let (q_x, q_y, q_z) = (to_origin_y * edge1.z - to_origin_z * edge1.y, to_origin_z * edge1.x - to_origin_x * edge1.z, to_origin_x * edge1.y - to_origin_y * edge1.x);
+            let v = (dir_x * q_x + dir_y * q_y + dir_z * q_z) * inverse_determinant;
+
+            t[lane] = (edge2.x * q_x + edge2.y * q_y + edge2.z * q_z) * inverse_determinant;
+            is_hit[lane] = !determinant.near_zero_eps() && u >= 0.0 && v >= 0.0 && u + v <= 1.0 && t[lane].greater_eq_eps(&0.0);
+        }
+
+        packet.iter().enumerate().map(|(lane, ray)| {
+            if is_hit[lane] {
+                self.get_intersection_at(ray, t[lane])
+            } else {
+                None
+            }
+        }).collect()
+    }
+
+    fn get_bounding_box(&self) -> Option<BoundingBox> {
+        BoundingBox::new_from_points(&self.vertices)
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use defs::{TEST_TOLERANCE};
     use core::Color;
 
+    #[test]
+    fn packet_intersections_match_single_rays() {
+        let material = Material::new_diffuse(Color::one(), None);
+        let models: Vec<Box<Model>> = vec![Box::new(SolidSphere::new_positioned(material, Point3::new(0.0, 0.0, 5.0), 1.0)),
+                                           Box::new(SolidPlane::new(material)),
+                                           Box::new(SolidTriangle::new(material, Point3::new(-1.0, -1.0, 3.0), Point3::new(1.0, -1.0, 3.0), Point3::new(0.0, 1.0, 3.0)))];
+        let rays = vec![Ray::new(Point3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 0.0, 1.0)),
+                        Ray::new(Point3::new(0.5, 0.0, 10.0), Vector3::new(0.0, 0.0, -1.0)),
+                        Ray::new(Point3::new(0.0, 0.0, 1.0), Vector3::new(1.0, 0.0, 0.0)),
+                        Ray::new(Point3::new(3.0, 3.0, 1.0), Vector3::new(-0.5, -0.5, -1.0))];
+        let packet = RayPacket::new(&rays);
+
+        for model in models.iter() {
+            let packet_intersections = model.get_packet_intersections(&packet);
+            assert_eq!(packet_intersections.len(), rays.len());
+            for (ray, packet_intersection) in rays.iter().zip(packet_intersections.iter()) {
+                match (model.get_intersection(ray), packet_intersection) {
+                    (Some(ref single), &Some(ref packed)) => assert_relative_eq!(single.get_intersection_point(), packed.get_intersection_point(), epsilon = TEST_TOLERANCE),
+                    (None, &None) => {},
+                    _ => panic!("Packet and single ray intersections differ")
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn triangle_hit_inside_and_miss_outside() {
+        let triangle = SolidTriangle::new(Material::new_diffuse(Color::one(), None), Point3::new(0.0, 0.0, 0.0), Point3::new(2.0, 0.0, 0.0), Point3::new(0.0, 2.0, 0.0));
+        let hit = triangle.get_intersection(&Ray::new(Point3::new(0.5, 0.5, -1.0), Vector3::new(0.0, 0.0, 1.0))).expect("Ray should hit the triangle");
+
+        assert_relative_eq!(hit.get_intersection_point(), &Point3::new(0.5, 0.5, 0.0), epsilon = TEST_TOLERANCE);
+        assert!(hit.was_inside());
+        assert_relative_eq!(hit.get_normal_vector(), &Vector3::new(0.0, 0.0, -1.0), epsilon = TEST_TOLERANCE);
+        assert!(triangle.get_intersection(&Ray::new(Point3::new(1.5, 1.5, -1.0), Vector3::new(0.0, 0.0, 1.0))).is_none());
+    }
+
     fn test_solid_unit_sphere(test_ray: &Ray, expected_result: Option<&Point3>) {
         let test_material = Material::new_shiny(Color::new(1.0, 1.0, 1.0), (Color::new(1.0, 1.0, 1.0), 1.5), None);
         let test_sphere = SolidSphere::new(test_material);
@@ -0,0 +1,223 @@
+use std::fs::{File};
+use std::io::{BufRead, BufReader};
+use std::io;
+use std::path::{Path};
+
+use defs::{FloatType, Vector3};
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Angular intensity distribution of a luminaire in type C photometry, vertical angles are measured from the photometric axis and horizontal angles around it, in degrees
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
+pub struct PhotometricProfile {
+    vertical_angles: Vec<FloatType>,
+    horizontal_angles: Vec<FloatType>,
+    /// One row of vertical samples per horizontal angle
+    candela: Vec<Vec<FloatType>>,
+    max_candela: FloatType
+}
+
+impl PhotometricProfile {
+    /// None if the angles are not increasing or the candela rows do not match them
+    pub fn new(vertical_angles: Vec<FloatType>, horizontal_angles: Vec<FloatType>, candela: Vec<Vec<FloatType>>) -> Option<Self> {
+        let is_increasing = |angles: &Vec<FloatType>| !angles.is_empty() && angles.windows(2).all(|pair| pair[0] < pair[1]);
+        if !is_increasing(&vertical_angles) || !is_increasing(&horizontal_angles) ||
+           candela.len() != horizontal_angles.len() || candela.iter().any(|row| row.len() != vertical_angles.len()) {
+            return None;
+        }
+
+        let max_candela = candela.iter().flat_map(|row| row.iter()).fold(0.0, |acc: FloatType, value| acc.max(*value));
+        Some(Self {
+            vertical_angles: vertical_angles,
+            horizontal_angles: horizontal_angles,
+            candela: candela,
+            max_candela: max_candela
+        })
+    }
+
+    pub fn get_max_candela(&self) -> FloatType {
+        self.max_candela
+    }
+
+    /// Horizontal angle folded into the measured range following the symmetry the last horizontal angle implies
+    fn get_folded_horizontal_angle(&self, horizontal_degree: FloatType) -> FloatType {
+        let angle = horizontal_degree.rem_euclid(360.0);
+        let last = *self.horizontal_angles.last().unwrap();
+        if last <= 0.0 {
+            0.0
+        } else if last <= 90.0 {
+            let half = angle % 180.0;
+            if half > 90.0 { 180.0 - half } else { half }
+        } else if last <= 180.0 {
+            if angle > 180.0 { 360.0 - angle } else { angle }
+        } else {
+            angle
+        }
+    }
+
+    /// Index of the segment containing the value and the position inside it, None outside the sampled angles
+    fn get_segment(angles: &[FloatType], value: FloatType) -> Option<(usize, FloatType)> {
+        if angles.len() == 1 {
+            return if value == angles[0] { Some((0, 0.0)) } else { None };
+        }
+        if value < angles[0] || value > angles[angles.len() - 1] {
+            return None;
+        }
+
+        let index = match angles.iter().position(|angle| *angle > value) {
+            Some(index) => index - 1,
+            None => angles.len() - 2
+        };
+        Some((index, (value - angles[index]) / (angles[index + 1] - angles[index])))
+    }
+
+    fn get_row_candela(&self, row: usize, vertical_degree: FloatType) -> FloatType {
+        match Self::get_segment(&self.vertical_angles, vertical_degree) {
+            Some((index, ratio)) if ratio > 0.0 => self.candela[row][index] * (1.0 - ratio) + self.candela[row][index + 1] * ratio,
+            Some((index, _)) => self.candela[row][index],
+            None => 0.0
+        }
+    }
+
+    /// Bilinearly interpolated intensity, zero outside the measured vertical range
+    pub fn get_candela(&self, vertical_degree: FloatType, horizontal_degree: FloatType) -> FloatType {
+        let horizontal = self.get_folded_horizontal_angle(horizontal_degree);
+        if self.horizontal_angles.len() == 1 {
+            return self.get_row_candela(0, vertical_degree);
+        }
+
+        match Self::get_segment(&self.horizontal_angles, horizontal) {
+            Some((index, ratio)) if ratio > 0.0 => {
+                self.get_row_candela(index, vertical_degree) * (1.0 - ratio) + self.get_row_candela(index + 1, vertical_degree) * ratio
+            },
+            Some((index, _)) => self.get_row_candela(index, vertical_degree),
+            None => self.get_row_candela(self.horizontal_angles.len() - 1, vertical_degree)
+        }
+    }
+
+    /// Intensity towards the direction relative to the brightest direction, the unit axis is the photometric nadir and
+    /// the unit C0 direction perpendicular to it the zero horizontal angle, which grows counterclockwise seen from the zenith
+    pub fn get_relative_intensity(&self, direction: &Vector3, axis: &Vector3, c0_direction: &Vector3) -> FloatType {
+        if self.max_candela <= 0.0 {
+            return 0.0;
+        }
+
+        let direction = direction.normalize();
+        let c90_direction = c0_direction.cross(axis);
+        let vertical = direction.dot(axis).max(-1.0).min(1.0).acos().to_degrees();
+        let horizontal = direction.dot(&c90_direction).atan2(direction.dot(c0_direction)).to_degrees();
+
+        self.get_candela(vertical, horizontal) / self.max_candela
+    }
+}
+
+/// Reads the candela table of an IES LM-63 file, tilt data is skipped as only TILT=NONE and TILT=INCLUDE are supported
+pub fn read_ies<R: BufRead>(reader: &mut R) -> io::Result<PhotometricProfile> {
+    let mut content = String::new();
+    reader.read_to_string(&mut content)?;
+
+    let mut lines = content.lines();
+    let tilt = loop {
+        match lines.next() {
+            Some(line) => {
+                let line = line.trim();
+                if line.starts_with("TILT=") {
+                    break line["TILT=".len()..].trim().to_string();
+                }
+            },
+            None => return Err(invalid_data("Missing TILT line"))
+        }
+    };
+
+    let remaining: Vec<&str> = lines.collect();
+    let mut values = Vec::new();
+    for token in remaining.iter().flat_map(|line| line.split(|character: char| character.is_whitespace() || character == ',')) {
+        if !token.is_empty() {
+            values.push(token.parse::<FloatType>().map_err(|_| invalid_data("Invalid number"))?);
+        }
+    }
+
+    let mut position = 0;
+    match tilt.as_str() {
+        "NONE" => {},
+        "INCLUDE" => {
+            let pair_count = *values.get(1).ok_or(invalid_data("Truncated tilt data"))? as usize;
+            position = 2 + 2 * pair_count;
+        },
+        _ => return Err(invalid_data("Tilt files are not supported"))
+    }
+
+    let header = values.get(position..position + 13).ok_or(invalid_data("Truncated photometric header"))?;
+    let candela_multiplier = header[2];
+    let vertical_count = header[3] as usize;
+    let horizontal_count = header[4] as usize;
+    let ballast_factor = header[10];
+    if header[5] as i32 != 1 {
+        return Err(invalid_data("Only type C photometry is supported"));
+    }
+    position += 13;
+
+    let table = values.get(position..position + vertical_count + horizontal_count + vertical_count * horizontal_count)
+                      .ok_or(invalid_data("Truncated candela table"))?;
+    let vertical_angles = table[..vertical_count].to_vec();
+    let horizontal_angles = table[vertical_count..vertical_count + horizontal_count].to_vec();
+    let candela = table[vertical_count + horizontal_count..].chunks(vertical_count.max(1)).map(|row| {
+        row.iter().map(|value| value * candela_multiplier * ballast_factor).collect()
+    }).collect();
+
+    PhotometricProfile::new(vertical_angles, horizontal_angles, candela).ok_or(invalid_data("Inconsistent candela table"))
+}
+
+pub fn load_ies(path: &Path) -> io::Result<PhotometricProfile> {
+    read_ies(&mut BufReader::new(File::open(path)?))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use defs::{TEST_TOLERANCE};
+
+    const TEST_IES: &str = "IESNA:LM-63-2002\n\
+                            [TEST] rtrace\n\
+                            [MANUFAC] none\n\
+                            TILT=INCLUDE\n\
+                            1\n\
+                            2\n\
+                            0 90\n\
+                            1.0 1.0\n\
+                            1 1000 2.0 3 2 1 1 0.1 0.1 0.0\n\
+                            0.5 1.0 100\n\
+                            0 45 90\n\
+                            0 90\n\
+                            100 50 0\n\
+                            200, 100, 0\n";
+
+    #[test]
+    fn ies_profile_reads_and_interpolates() {
+        let profile = read_ies(&mut TEST_IES.as_bytes()).expect("Test file should parse");
+
+        assert_relative_eq!(profile.get_max_candela(), 200.0, epsilon = TEST_TOLERANCE);
+        assert_relative_eq!(profile.get_candela(0.0, 0.0), 100.0, epsilon = TEST_TOLERANCE);
+        assert_relative_eq!(profile.get_candela(22.5, 0.0), 75.0, epsilon = TEST_TOLERANCE);
+        assert_relative_eq!(profile.get_candela(0.0, 45.0), 150.0, epsilon = TEST_TOLERANCE);
+        assert_relative_eq!(profile.get_candela(0.0, 135.0), 150.0, epsilon = TEST_TOLERANCE);
+        assert_relative_eq!(profile.get_candela(0.0, 270.0), 200.0, epsilon = TEST_TOLERANCE);
+        assert_relative_eq!(profile.get_candela(120.0, 0.0), 0.0, epsilon = TEST_TOLERANCE);
+    }
+
+    #[test]
+    fn ies_profile_oriented_by_axis() {
+        let profile = PhotometricProfile::new(vec![0.0, 90.0, 180.0], vec![0.0], vec![vec![10.0, 5.0, 0.0]]).unwrap();
+        let (axis, c0_direction) = (Vector3::new(0.0, -1.0, 0.0), Vector3::new(1.0, 0.0, 0.0));
+
+        assert_relative_eq!(profile.get_relative_intensity(&Vector3::new(0.0, -2.0, 0.0), &axis, &c0_direction), 1.0, epsilon = TEST_TOLERANCE);
+        assert_relative_eq!(profile.get_relative_intensity(&Vector3::new(1.0, 0.0, 0.0), &axis, &c0_direction), 0.5, epsilon = TEST_TOLERANCE);
+        assert_relative_eq!(profile.get_relative_intensity(&Vector3::new(0.0, 1.0, 0.0), &axis, &c0_direction), 0.0, epsilon = TEST_TOLERANCE);
+        assert!(PhotometricProfile::new(vec![0.0, 90.0], vec![0.0], vec![vec![1.0]]).is_none());
+        assert!(read_ies(&mut "TILT=lamp.tlt\n".as_bytes()).is_err());
+    }
+}
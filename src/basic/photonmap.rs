@@ -0,0 +1,402 @@
+use std::cmp::{Ordering};
+use std::collections::{BinaryHeap};
+use std::sync::{Arc, RwLock};
+
+use defs::{FloatType, Point3, Vector3, float_consts};
+use core::{Color, Ray, RayIntersection, RayCaster, IlluminationCaster, ColorCalculator, TraceableWorld};
+use basic::scattering::{SurfaceScattering, sample_surface_scattering};
+use rand;
+use rand::{Rng};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Photon {
+    pub position: Point3,
+    /// Direction the photon travelled in when it arrived
+    pub direction: Vector3,
+    pub power: Color
+}
+
+struct KdTreeNode {
+    photon: Photon,
+    axis: usize,
+    left: Option<usize>,
+    right: Option<usize>
+}
+
+struct PhotonDistance<'tree> {
+    distance_squared: FloatType,
+    photon: &'tree Photon
+}
+
+impl<'tree> PartialEq for PhotonDistance<'tree> {
+    fn eq(&self, other: &Self) -> bool {
+        self.distance_squared == other.distance_squared
+    }
+}
+
+impl<'tree> Eq for PhotonDistance<'tree> {}
+
+impl<'tree> PartialOrd for PhotonDistance<'tree> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'tree> Ord for PhotonDistance<'tree> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance_squared.partial_cmp(&other.distance_squared).unwrap_or(Ordering::Equal)
+    }
+}
+
+/// Balanced kd-tree of photons split along the axis of the largest extent
+pub struct PhotonKdTree {
+    nodes: Vec<KdTreeNode>,
+    root: Option<usize>
+}
+
+impl PhotonKdTree {
+    pub fn new(mut photons: Vec<Photon>) -> Self {
+        let mut nodes: Vec<KdTreeNode> = Vec::with_capacity(photons.len());
+        let root = Self::build(&mut photons[..], &mut nodes);
+        Self {
+            nodes: nodes,
+            root: root
+        }
+    }
+
+    fn build(photons: &mut [Photon], nodes: &mut Vec<KdTreeNode>) -> Option<usize> {
+        if photons.is_empty() {
+            return None;
+        }
+
+        let axis = (0..3).map(|axis| {
+            let (min, max) = photons.iter().fold((FloatType::INFINITY, FloatType::NEG_INFINITY), |acc, photon| {
+                (acc.0.min(photon.position[axis]), acc.1.max(photon.position[axis]))
+            });
+            (axis, max - min)
+        }).fold((0, FloatType::NEG_INFINITY), |acc, extent| if extent.1 > acc.1 { extent } else { acc }).0;
+
+        photons.sort_by(|lhs, rhs| lhs.position[axis].partial_cmp(&rhs.position[axis]).unwrap_or(Ordering::Equal));
+        let median = photons.len() / 2;
+        let index = nodes.len();
+        nodes.push(KdTreeNode {
+            photon: photons[median],
+            axis: axis,
+            left: None,
+            right: None
+        });
+
+        let (lower, upper) = photons.split_at_mut(median);
+        let left = Self::build(lower, nodes);
+        let right = Self::build(&mut upper[1..], nodes);
+        nodes[index].left = left;
+        nodes[index].right = right;
+
+        Some(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn iter<'tree>(&'tree self) -> Box<Iterator<Item=&'tree Photon> + 'tree> {
+        Box::new(self.nodes.iter().map(|node| &node.photon))
+    }
+
+    fn collect_nearest<'tree>(&'tree self, node_index: Option<usize>, point: &Point3, count: usize, max_distance_squared: FloatType, heap: &mut BinaryHeap<PhotonDistance<'tree>>) {
+        if let Some(index) = node_index {
+            let node = &self.nodes[index];
+            let plane_distance = point[node.axis] - node.photon.position[node.axis];
+            let (near, far) = if plane_distance < 0.0 { (node.left, node.right) } else { (node.right, node.left) };
+
+            self.collect_nearest(near, point, count, max_distance_squared, heap);
+
+            let distance_squared = (node.photon.position - point).norm_squared();
+            if distance_squared <= max_distance_squared {
+                if heap.len() < count {
+                    heap.push(PhotonDistance { distance_squared: distance_squared, photon: &node.photon });
+                } else if heap.peek().map_or(false, |farthest| distance_squared < farthest.distance_squared) {
+                    heap.pop();
+                    heap.push(PhotonDistance { distance_squared: distance_squared, photon: &node.photon });
+                }
+            }
+
+            let search_distance_squared = if heap.len() < count { max_distance_squared } else { heap.peek().map_or(max_distance_squared, |farthest| farthest.distance_squared) };
+            if plane_distance.powi(2) <= search_distance_squared {
+                self.collect_nearest(far, point, count, max_distance_squared, heap);
+            }
+        }
+    }
+
+    /// At most count photons within the distance, with their squared distances, nearest first
+    pub fn get_nearest(&self, point: &Point3, count: usize, max_distance: FloatType) -> Vec<(FloatType, &Photon)> {
+        let mut heap: BinaryHeap<PhotonDistance> = BinaryHeap::with_capacity(count + 1);
+        if count > 0 {
+            self.collect_nearest(self.root, point, count, max_distance.powi(2), &mut heap);
+        }
+        heap.into_sorted_vec().into_iter().map(|item| (item.distance_squared, item.photon)).collect()
+    }
+
+    /// Radiance leaving a diffuse surface towards the viewer estimated from the nearest photons
+    pub fn get_radiance_estimate(&self, intersection: &RayIntersection, count: usize, max_distance: FloatType) -> Color {
+        let diffuse_color = match intersection.get_material().get_diffuse_color() {
+            Some(color) => *color,
+            None => return Color::zero()
+        };
+        let nearest = self.get_nearest(intersection.get_intersection_point(), count, max_distance);
+        let radius_squared = match nearest.last() {
+            Some(&(distance_squared, _)) if distance_squared > 0.0 => distance_squared,
+            _ => return Color::zero()
+        };
+
+        let normal = intersection.get_normal_vector();
+        let flux = nearest.iter().fold(Color::zero(), |acc, &(_, photon)| {
+            if photon.direction.dot(normal) < 0.0 { acc + photon.power } else { acc }
+        });
+
+        (diffuse_color * flux).mul_scalar(&(float_consts::PI.powi(2) * radius_squared).recip())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PhotonMapSettings {
+    pub caustic_photon_count: usize,
+    pub global_photon_count: usize,
+    pub maximum_bounces: usize,
+    pub gather_count: usize,
+    pub gather_radius: FloatType
+}
+
+impl PhotonMapSettings {
+    pub fn new(caustic_photon_count: usize, global_photon_count: usize, maximum_bounces: usize, gather_count: usize, gather_radius: FloatType) -> Self {
+        Self {
+            caustic_photon_count: caustic_photon_count,
+            global_photon_count: global_photon_count,
+            maximum_bounces: maximum_bounces,
+            gather_count: gather_count,
+            gather_radius: gather_radius
+        }
+    }
+}
+
+/// Photons arriving on diffuse surfaces after specular bounces only (caustics) and after at least one diffuse bounce (indirect)
+pub struct PhotonMaps {
+    caustic: PhotonKdTree,
+    global: PhotonKdTree
+}
+
+impl PhotonMaps {
+    /// Traces a photon until absorbed, storing it on diffuse surfaces reached through specular bounces only for caustics, or through a diffuse bounce otherwise
+    fn trace_photon(ray: Ray, power: Color, maximum_bounces: usize, for_caustics: bool, world: &RayCaster, photons: &mut Vec<Photon>) {
+        let mut random_generator = rand::thread_rng();
+        let mut ray = ray;
+        let mut power = power;
+        let mut specular_path = true;
+
+        for bounce in 0..maximum_bounces + 1 {
+            let intersection = match world.cast_model_ray(&ray) {
+                Some(intersection) => intersection,
+                None => return
+            };
+
+            if intersection.get_material().get_diffuse_color().is_some() {
+                if bounce > 0 && specular_path == for_caustics {
+                    photons.push(Photon {
+                        position: *intersection.get_intersection_point(),
+                        direction: *ray.get_direction(),
+                        power: power
+                    });
+                }
+                if for_caustics {
+                    return;
+                }
+            }
+
+            match sample_surface_scattering(&intersection, random_generator.gen::<FloatType>(), world) {
+                SurfaceScattering::Diffuse(next_ray, weight, _) => {
+                    specular_path = false;
+                    ray = next_ray;
+                    power *= weight;
+                },
+                SurfaceScattering::Specular(next_ray, weight, _) => {
+                    ray = next_ray;
+                    power *= weight;
+                },
+                SurfaceScattering::Absorbed => return
+            }
+        }
+    }
+
+    fn emit_photons<WorldT: TraceableWorld>(world: &WorldT, emission_count: usize, maximum_bounces: usize, for_caustics: bool) -> Vec<Photon> {
+        let lights = world.get_light_sources();
+        let mut random_generator = rand::thread_rng();
+        let mut photons: Vec<Photon> = Vec::new();
+
+        if !lights.is_empty() && emission_count > 0 {
+            let emission_per_light = (emission_count + lights.len() - 1) / lights.len();
+            for light in lights.iter() {
+                for _ in 0..emission_per_light {
+                    if let Some(emission) = light.sample_emission(random_generator.gen::<FloatType>(), random_generator.gen::<FloatType>()) {
+                        let power = emission.power.mul_scalar(&(emission_per_light as FloatType).recip());
+                        Self::trace_photon(emission.ray, power, maximum_bounces, for_caustics, world, &mut photons);
+                    }
+                }
+            }
+        }
+
+        photons
+    }
+
+    /// Emits the photons of both maps from the light sources of the world in separate passes
+    pub fn new<WorldT: TraceableWorld>(world: &WorldT, settings: &PhotonMapSettings) -> Self {
+        Self {
+            caustic: PhotonKdTree::new(Self::emit_photons(world, settings.caustic_photon_count, settings.maximum_bounces, true)),
+            global: PhotonKdTree::new(Self::emit_photons(world, settings.global_photon_count, settings.maximum_bounces, false))
+        }
+    }
+
+    pub fn get_caustic_map(&self) -> &PhotonKdTree {
+        &self.caustic
+    }
+
+    pub fn get_global_map(&self) -> &PhotonKdTree {
+        &self.global
+    }
+}
+
+/// Photon maps filled after the world owning the color calculator using them is built
+#[derive(Clone)]
+pub struct SharedPhotonMaps {
+    maps: Arc<RwLock<Option<PhotonMaps>>>
+}
+
+impl SharedPhotonMaps {
+    pub fn new() -> Self {
+        Self {
+            maps: Arc::new(RwLock::new(None))
+        }
+    }
+
+    pub fn build<WorldT: TraceableWorld>(&self, world: &WorldT, settings: &PhotonMapSettings) {
+        let maps = PhotonMaps::new(world, settings);
+        if let Ok(ref mut unlocked_maps) = self.maps.write() {
+            **unlocked_maps = Some(maps);
+        } else {
+            panic!("Lock error inside SharedPhotonMaps");
+        }
+    }
+
+    pub fn clear(&self) {
+        if let Ok(ref mut unlocked_maps) = self.maps.write() {
+            **unlocked_maps = None;
+        }
+    }
+}
+
+/// Adds the caustic and indirect diffuse radiance estimated from photon maps to the base calculator
+pub struct PhotonMappingColorCalculator<BaseT> {
+    base_calculator: BaseT,
+    photon_maps: SharedPhotonMaps,
+    settings: PhotonMapSettings
+}
+
+impl<BaseT: ColorCalculator> PhotonMappingColorCalculator<BaseT> {
+    pub fn new(base_calculator: BaseT, photon_maps: SharedPhotonMaps, settings: PhotonMapSettings) -> Self {
+        Self {
+            base_calculator: base_calculator,
+            photon_maps: photon_maps,
+            settings: settings
+        }
+    }
+
+    pub fn get_photon_radiance(&self, intersection: &RayIntersection) -> Color {
+        if let Ok(unlocked_maps) = self.photon_maps.maps.read() {
+            match *unlocked_maps {
+                Some(ref maps) => {
+                    maps.caustic.get_radiance_estimate(intersection, self.settings.gather_count, self.settings.gather_radius) +
+                    maps.global.get_radiance_estimate(intersection, self.settings.gather_count, self.settings.gather_radius)
+                },
+                None => Color::zero()
+            }
+        } else {
+            Color::zero()
+        }
+    }
+}
+
+impl<BaseT: ColorCalculator> ColorCalculator for PhotonMappingColorCalculator<BaseT> {
+    fn get_color(&self, intersection: &RayIntersection, ray_caster: &RayCaster, illumination_caster: &IlluminationCaster) -> Option<Color> {
+        let photon_radiance = self.get_photon_radiance(intersection);
+        match self.base_calculator.get_color(intersection, ray_caster, illumination_caster) {
+            Some(color) => Some(color + photon_radiance),
+            None => Some(photon_radiance)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use defs::{TEST_TOLERANCE};
+    use core::{World, Material, FresnelIndex, LightSource};
+    use basic::{SimpleIntersector, SimpleColorCalculator, SimpleIlluminator};
+    use basic::model::{SolidSphere, SolidPlane};
+    use basic::lightsource::{DotLightSource};
+
+    #[test]
+    fn kd_tree_nearest_matches_brute_force() {
+        let mut random_generator = rand::thread_rng();
+        let photons: Vec<Photon> = (0..500).map(|_| Photon {
+            position: Point3::new(random_generator.gen::<FloatType>(), random_generator.gen::<FloatType>(), random_generator.gen::<FloatType>()),
+            direction: Vector3::new(0.0, 0.0, -1.0),
+            power: Color::one()
+        }).collect();
+        let tree = PhotonKdTree::new(photons.clone());
+        let point = Point3::new(0.5, 0.4, 0.3);
+
+        let mut brute_force: Vec<FloatType> = photons.iter().map(|photon| (photon.position - point).norm_squared()).filter(|distance| *distance <= 0.04).collect();
+        brute_force.sort_by(|lhs, rhs| lhs.partial_cmp(rhs).unwrap());
+        brute_force.truncate(10);
+        let nearest: Vec<FloatType> = tree.get_nearest(&point, 10, 0.2).iter().map(|&(distance, _)| distance).collect();
+
+        assert_eq!(tree.len(), 500);
+        assert_eq!(nearest, brute_force);
+    }
+
+    #[test]
+    fn dot_light_emission_power() {
+        let light = DotLightSource::new_natural(Color::one(), 2.0, Point3::origin());
+        let emission = light.sample_emission(0.25, 0.5).unwrap();
+
+        assert_relative_eq!(emission.power.intensity_avg(), 2.0 * 4.0 * float_consts::PI.powi(2), epsilon = TEST_TOLERANCE);
+        assert_relative_eq!(emission.ray.get_direction().norm(), 1.0, epsilon = TEST_TOLERANCE);
+    }
+
+    #[test]
+    fn glass_sphere_focuses_caustic_photons() {
+        let glass = Material::new_refractive(FresnelIndex::new(1.5, 1.5, 1.5), FresnelIndex::zero(), None, None, None);
+        let floor = Material::new_diffuse(Color::one(), None);
+        let models: Vec<Box<::core::Model>> = vec![Box::new(SolidSphere::new_positioned(glass, Point3::new(0.0, 0.0, 2.0), 1.0)),
+                                                    Box::new(SolidPlane::new(floor))];
+        let lights: Vec<Box<LightSource>> = vec![Box::new(DotLightSource::new_natural(Color::one(), 1.0, Point3::new(0.0, 0.0, 10.0)))];
+        let world = World::new(SimpleIntersector::new(models), SimpleColorCalculator::new(), SimpleIlluminator::new(lights), 8);
+
+        let settings = PhotonMapSettings::new(50000, 0, 6, 50, 0.5);
+        let maps = PhotonMaps::new(&world, &settings);
+
+        let ray = Ray::new(Point3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        let under_sphere = RayIntersection::new(Vector3::new(0.0, 0.0, 1.0), Point3::origin(), &ray, floor, false).unwrap();
+        let far_ray = Ray::new(Point3::new(6.0, 0.0, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        let far_from_sphere = RayIntersection::new(Vector3::new(0.0, 0.0, 1.0), Point3::new(6.0, 0.0, 0.0), &far_ray, floor, false).unwrap();
+
+        assert!(!maps.get_caustic_map().is_empty());
+        assert!(maps.get_global_map().is_empty());
+        assert!(maps.get_caustic_map().get_radiance_estimate(&under_sphere, 50, 0.5).intensity_avg() >
+                maps.get_caustic_map().get_radiance_estimate(&far_from_sphere, 50, 0.5).intensity_avg());
+    }
+}
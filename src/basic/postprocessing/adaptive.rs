@@ -0,0 +1,476 @@
+use std::collections::{VecDeque};
+use std::sync::{Arc, Mutex};
+
+use defs::{IntType, FloatType, Point2Int};
+use core::{Color, Screen, ScreenIterator, BasicSceneBuffer, SceneBuffer,
+           RenderingTask, RenderingTaskProducer, ThreadSafeIterator, OrderedTaskProducers};
+
+pub trait PixelSampler: Send + Sync {
+    fn sample_pixel(&self, coord: &Point2Int) -> Option<Color>;
+    fn get_screen(&self) -> &Screen;
+}
+
+#[derive(Debug)]
+pub enum AdaptiveSamplerError {
+    InvalidCoord,
+    MutexLockError
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AdaptiveSamplingSettings {
+    pub initial_samples: u32,
+    pub samples_per_pass: u32,
+    pub maximum_samples: u32,
+    pub error_threshold: FloatType,
+    pub tile_size: IntType
+}
+
+impl AdaptiveSamplingSettings {
+    pub fn new(initial_samples: u32, samples_per_pass: u32, maximum_samples: u32, error_threshold: FloatType) -> Self {
+        Self {
+            initial_samples: initial_samples,
+            samples_per_pass: samples_per_pass,
+            maximum_samples: maximum_samples,
+            error_threshold: error_threshold,
+            tile_size: 1
+        }
+    }
+
+    pub fn with_tile_size(&self, tile_size: IntType) -> Self {
+        Self {
+            tile_size: tile_size.max(1),
+            ..*self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PixelSampleStatistics {
+    sample_count: u32,
+    hit_count: u32,
+    sum: Color,
+    sum_squared: Color
+}
+
+impl PixelSampleStatistics {
+    pub fn new() -> Self {
+        Self {
+            sample_count: 0,
+            hit_count: 0,
+            sum: Color::zero(),
+            sum_squared: Color::zero()
+        }
+    }
+
+    pub fn add_sample(&mut self, sample: Option<Color>) {
+        self.sample_count += 1;
+        if let Some(color) = sample {
+            self.hit_count += 1;
+            self.sum += color;
+            self.sum_squared += color * color;
+        }
+    }
+
+    pub fn get_sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    pub fn get_mean(&self) -> Option<Color> {
+        if self.hit_count > 0 {
+            Some(self.sum.mul_scalar(&(self.sample_count as FloatType).recip()))
+        } else {
+            None
+        }
+    }
+
+    pub fn get_variance(&self) -> Option<Color> {
+        if self.sample_count > 1 {
+            let count = self.sample_count as FloatType;
+            let mean = self.sum.mul_scalar(&count.recip());
+            let (r, g, b) = (self.sum_squared.mul_scalar(&count.recip()) - mean * mean).mul_scalar(&(count / (count - 1.0))).get();
+            Some(Color::new(r.max(0.0), g.max(0.0), b.max(0.0)))
+        } else {
+            None
+        }
+    }
+
+    /// Standard error of the mean luminance, infinite while there are not enough samples to estimate it
+    pub fn get_error(&self) -> FloatType {
+        if let Some(variance) = self.get_variance() {
+            (variance.intensity_avg() / (self.sample_count as FloatType)).sqrt()
+        } else {
+            FloatType::INFINITY
+        }
+    }
+}
+
+struct AdaptiveSamplerState {
+    pub statistics: Vec<PixelSampleStatistics>
+}
+
+impl AdaptiveSamplerState {
+    pub fn new(size: usize) -> Self {
+        let mut statistics: Vec<PixelSampleStatistics> = Vec::with_capacity(size);
+        statistics.resize(size, PixelSampleStatistics::new());
+        Self {
+            statistics: statistics
+        }
+    }
+}
+
+pub struct AdaptiveSampler {
+    sampler: Arc<PixelSampler>,
+    screen: Screen,
+    settings: AdaptiveSamplingSettings,
+    state: Mutex<AdaptiveSamplerState>
+}
+
+impl AdaptiveSampler {
+    pub fn new(sampler: Arc<PixelSampler>, settings: AdaptiveSamplingSettings) -> Self {
+        let screen = *sampler.get_screen();
+        Self {
+            sampler: sampler,
+            screen: screen,
+            settings: settings,
+            state: Mutex::new(AdaptiveSamplerState::new(screen.get_pixel_count() as usize))
+        }
+    }
+
+    pub fn get_screen(&self) -> &Screen {
+        &self.screen
+    }
+
+    pub fn get_settings(&self) -> &AdaptiveSamplingSettings {
+        &self.settings
+    }
+
+    fn to_buffer_index(&self, coord: &Point2Int) -> Result<usize, AdaptiveSamplerError> {
+        match self.screen.get_pixel_index_by_screen_coord(coord) {
+            Ok(index) => Ok(index as usize),
+            Err(_) => Err(AdaptiveSamplerError::InvalidCoord)
+        }
+    }
+
+    pub fn sample_pixel(&self, coord: &Point2Int, sample_count: u32) -> Result<(), AdaptiveSamplerError> {
+        let index = self.to_buffer_index(coord)?;
+        let samples: Vec<Option<Color>> = (0..sample_count).map(|_| self.sampler.sample_pixel(coord)).collect();
+
+        if let Ok(ref mut unlocked_state) = self.state.lock() {
+            let statistics = &mut unlocked_state.statistics[index];
+            for sample in samples {
+                statistics.add_sample(sample);
+            }
+            Ok(())
+        } else {
+            Err(AdaptiveSamplerError::MutexLockError)
+        }
+    }
+
+    pub fn get_pixel_statistics(&self, coord: &Point2Int) -> Result<PixelSampleStatistics, AdaptiveSamplerError> {
+        let index = self.to_buffer_index(coord)?;
+        if let Ok(unlocked_state) = self.state.lock() {
+            Ok(unlocked_state.statistics[index])
+        } else {
+            Err(AdaptiveSamplerError::MutexLockError)
+        }
+    }
+
+    /// Pixels whose tile still has an estimated error above the threshold and which have sample budget left
+    pub fn get_pixels_to_refine(&self) -> Result<Vec<Point2Int>, AdaptiveSamplerError> {
+        if let Ok(unlocked_state) = self.state.lock() {
+            let (h_res, v_res) = self.screen.get_resolution();
+            let tile_size = self.settings.tile_size;
+            let mut result = Vec::new();
+
+            for tile_y in (0..v_res).filter(|y| y % tile_size == 0) {
+                for tile_x in (0..h_res).filter(|x| x % tile_size == 0) {
+                    let tile_coords: Vec<Point2Int> = (tile_y..(tile_y + tile_size).min(v_res))
+                        .flat_map(|y| (tile_x..(tile_x + tile_size).min(h_res)).map(move |x| Point2Int::new(x, y)))
+                        .collect();
+
+                    let tile_error = tile_coords.iter().fold(0.0, |acc: FloatType, coord| {
+                        let index = self.screen.get_pixel_index_by_screen_coord(coord).unwrap() as usize;
+                        acc.max(unlocked_state.statistics[index].get_error())
+                    });
+
+                    if tile_error > self.settings.error_threshold {
+                        for coord in tile_coords {
+                            let index = self.screen.get_pixel_index_by_screen_coord(&coord).unwrap() as usize;
+                            if unlocked_state.statistics[index].get_sample_count() < self.settings.maximum_samples {
+                                result.push(coord);
+                            }
+                        }
+                    }
+                }
+            }
+
+            Ok(result)
+        } else {
+            Err(AdaptiveSamplerError::MutexLockError)
+        }
+    }
+
+    fn get_transformed_buffer<F>(&self, transformation: F) -> Result<Box<SceneBuffer>, AdaptiveSamplerError>
+        where F: Fn(&PixelSampleStatistics) -> Option<Color>
+    {
+        if let Ok(unlocked_state) = self.state.lock() {
+            let transformed_buffer = unlocked_state.statistics.iter().map(transformation).collect();
+            Ok(Box::new(BasicSceneBuffer::with_buffer(self.screen, transformed_buffer).unwrap()))
+        } else {
+            Err(AdaptiveSamplerError::MutexLockError)
+        }
+    }
+
+    pub fn get_entire_buffer(&self) -> Result<Box<SceneBuffer>, AdaptiveSamplerError> {
+        self.get_transformed_buffer(|statistics| statistics.get_mean())
+    }
+
+    pub fn get_sample_heatmap_buffer(&self) -> Result<Box<SceneBuffer>, AdaptiveSamplerError> {
+        let maximum_samples = self.settings.maximum_samples.max(1) as FloatType;
+        self.get_transformed_buffer(|statistics| {
+            let intensity = statistics.get_sample_count() as FloatType / maximum_samples;
+            Some(Color::new(intensity, intensity, intensity))
+        })
+    }
+
+    pub fn get_error_buffer(&self) -> Result<Box<SceneBuffer>, AdaptiveSamplerError> {
+        self.get_transformed_buffer(|statistics| {
+            let error = statistics.get_error().min(1.0);
+            Some(Color::new(error, error, error))
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum AdaptiveSamplingPass {
+    Initial,
+    Refinement
+}
+
+/// Tasks of a refinement pass are selected when its iterator is created, so pixels of the
+/// previous pass still being sampled at that point are judged by their statistics up to then
+pub struct AdaptiveSamplerTaskProducer {
+    sampler: Arc<AdaptiveSampler>,
+    pass: AdaptiveSamplingPass
+}
+
+impl AdaptiveSamplerTaskProducer {
+    pub fn new(sampler: Arc<AdaptiveSampler>, refinement_passes: u32) -> Box<RenderingTaskProducer> {
+        let mut producers: Vec<Box<RenderingTaskProducer>> = Vec::with_capacity(refinement_passes as usize + 1);
+        producers.push(Self::new_pass(Arc::clone(&sampler), AdaptiveSamplingPass::Initial));
+        for _pass in 0..refinement_passes {
+            producers.push(Self::new_pass(Arc::clone(&sampler), AdaptiveSamplingPass::Refinement));
+        }
+        OrderedTaskProducers::new(producers)
+    }
+
+    fn new_pass(sampler: Arc<AdaptiveSampler>, pass: AdaptiveSamplingPass) -> Box<RenderingTaskProducer> {
+        Box::new(Self {
+            sampler: sampler,
+            pass: pass
+        })
+    }
+}
+
+impl RenderingTaskProducer for AdaptiveSamplerTaskProducer {
+    fn create_task_iterator(self: Box<Self>) -> Box<ThreadSafeIterator<Item=Box<RenderingTask>>> {
+        match self.pass {
+            AdaptiveSamplingPass::Initial => {
+                let sample_count = self.sampler.get_settings().initial_samples;
+                let coords: VecDeque<Point2Int> = ScreenIterator::new(self.sampler.get_screen()).collect();
+                Box::new(AdaptiveSamplerTaskIterator::new(Arc::clone(&self.sampler), coords, sample_count))
+            },
+            AdaptiveSamplingPass::Refinement => {
+                let sample_count = self.sampler.get_settings().samples_per_pass;
+                let coords: VecDeque<Point2Int> = self.sampler.get_pixels_to_refine().expect("AdaptiveSamplerTaskProducer: Refinement pixels should be available").into_iter().collect();
+                Box::new(AdaptiveSamplerTaskIterator::new(Arc::clone(&self.sampler), coords, sample_count))
+            }
+        }
+    }
+
+    fn get_task_count(&self) -> Option<usize> {
+        match self.pass {
+            AdaptiveSamplingPass::Initial => Some(self.sampler.get_screen().get_pixel_count() as usize),
+            AdaptiveSamplingPass::Refinement => None
+        }
+    }
+}
+
+pub struct AdaptiveSamplerTaskIterator {
+    sampler: Arc<AdaptiveSampler>,
+    sample_count: u32,
+    coords: Mutex<VecDeque<Point2Int>>
+}
+
+impl AdaptiveSamplerTaskIterator {
+    fn new(sampler: Arc<AdaptiveSampler>, coords: VecDeque<Point2Int>, sample_count: u32) -> Self {
+        Self {
+            sampler: sampler,
+            sample_count: sample_count,
+            coords: Mutex::new(coords)
+        }
+    }
+
+    fn create_task(&self, coord: Point2Int) -> Box<AdaptiveSamplerTask> {
+        Box::new(AdaptiveSamplerTask::new(Arc::clone(&self.sampler), coord, self.sample_count))
+    }
+}
+
+impl ThreadSafeIterator for AdaptiveSamplerTaskIterator {
+    type Item = Box<RenderingTask>;
+
+    fn next(&self) -> Option<Box<RenderingTask>> {
+        if let Ok(ref mut unlocked_coords) = self.coords.lock() {
+            match unlocked_coords.pop_front() {
+                Some(coord) => Some(self.create_task(coord)),
+                None => None
+            }
+        } else {
+            panic!("Mutex lock error inside AdaptiveSamplerTaskIterator");
+        }
+    }
+}
+
+pub struct AdaptiveSamplerTask {
+    sampler: Arc<AdaptiveSampler>,
+    coord: Point2Int,
+    sample_count: u32
+}
+
+impl AdaptiveSamplerTask {
+    pub fn new(sampler: Arc<AdaptiveSampler>, coord: Point2Int, sample_count: u32) -> Self {
+        Self {
+            sampler: sampler,
+            coord: coord,
+            sample_count: sample_count
+        }
+    }
+}
+
+impl RenderingTask for AdaptiveSamplerTask {
+    fn execute(self: Box<Self>) {
+        match self.sampler.sample_pixel(&self.coord, self.sample_count) {
+            Ok(()) => (),
+            Err(error) => panic!("AdaptiveSamplerTask: Unrecoverable AdaptiveSamplerError: {:?}", error)
+        }
+    }
+
+    fn get_pixel_coord(&self) -> Option<Point2Int> {
+        Some(self.coord)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use defs::{Point3, Vector3};
+
+    #[test]
+    fn statistics_constant_samples_have_no_error() {
+        let mut statistics = PixelSampleStatistics::new();
+        for _counter in 0..4 {
+            statistics.add_sample(Some(Color::new(0.5, 0.5, 0.5)));
+        }
+
+        assert_relative_eq!(statistics.get_error(), 0.0);
+        assert!(statistics.get_mean().unwrap().equal_eps(&Color::new(0.5, 0.5, 0.5)));
+    }
+
+    #[test]
+    fn statistics_single_sample_needs_refinement() {
+        let mut statistics = PixelSampleStatistics::new();
+        statistics.add_sample(Some(Color::one()));
+
+        assert!(statistics.get_error().is_infinite());
+    }
+
+    #[test]
+    fn statistics_missed_samples_count_as_black() {
+        let mut statistics = PixelSampleStatistics::new();
+        statistics.add_sample(Some(Color::one()));
+        statistics.add_sample(None);
+
+        assert!(statistics.get_mean().unwrap().equal_eps(&Color::new(0.5, 0.5, 0.5)));
+        assert_relative_eq!(statistics.get_variance().unwrap().intensity_avg(), 0.5);
+        assert_relative_eq!(statistics.get_error(), 0.5);
+    }
+
+    #[test]
+    fn statistics_background_only_pixel() {
+        let mut statistics = PixelSampleStatistics::new();
+        statistics.add_sample(None);
+
+        assert!(statistics.get_mean().is_none());
+        assert!(statistics.get_error().is_infinite());
+
+        statistics.add_sample(None);
+
+        assert!(statistics.get_mean().is_none());
+        assert_relative_eq!(statistics.get_error(), 0.0);
+    }
+
+    struct EdgeSampler {
+        screen: Screen,
+        edge_sample_count: AtomicUsize
+    }
+
+    impl PixelSampler for EdgeSampler {
+        // Pixel 0 alternates between missing and hitting, pixel 1 is background, pixel 2 is flat
+        fn sample_pixel(&self, coord: &Point2Int) -> Option<Color> {
+            match coord.x {
+                0 => if self.edge_sample_count.fetch_add(1, Ordering::SeqCst) % 2 == 0 { None } else { Some(Color::one()) },
+                1 => None,
+                _ => Some(Color::new(0.5, 0.5, 0.5))
+            }
+        }
+
+        fn get_screen(&self) -> &Screen {
+            &self.screen
+        }
+    }
+
+    fn create_edge_sampler(settings: AdaptiveSamplingSettings) -> Arc<AdaptiveSampler> {
+        let screen = Screen::new(Point3::origin(), Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 1.0, 0.0), 3.0, 1.0, 3, 1).unwrap();
+        let sampler = EdgeSampler {
+            screen: screen,
+            edge_sample_count: AtomicUsize::new(0)
+        };
+        Arc::new(AdaptiveSampler::new(Arc::new(sampler), settings))
+    }
+
+    #[test]
+    fn refinement_selects_noisy_and_undersampled_pixels() {
+        let sampler = create_edge_sampler(AdaptiveSamplingSettings::new(1, 2, 8, 0.01));
+        let coords: Vec<Point2Int> = (0..3).map(|x| Point2Int::new(x, 0)).collect();
+
+        for coord in &coords {
+            sampler.sample_pixel(coord, 1).unwrap();
+        }
+        assert_eq!(sampler.get_pixels_to_refine().unwrap(), coords);
+
+        for coord in &coords {
+            sampler.sample_pixel(coord, 2).unwrap();
+        }
+        assert_eq!(sampler.get_pixels_to_refine().unwrap(), vec![Point2Int::new(0, 0)]);
+
+        sampler.sample_pixel(&Point2Int::new(0, 0), 5).unwrap();
+        assert!(sampler.get_pixels_to_refine().unwrap().is_empty());
+    }
+
+    #[test]
+    fn task_producer_refines_until_sample_budget() {
+        let sampler = create_edge_sampler(AdaptiveSamplingSettings::new(1, 1, 4, 0.01));
+        let producer = AdaptiveSamplerTaskProducer::new(Arc::clone(&sampler), 5);
+
+        let iterator = producer.create_task_iterator();
+        while let Some(task) = iterator.next() {
+            task.execute();
+        }
+
+        let sample_counts: Vec<u32> = (0..3).map(|x| sampler.get_pixel_statistics(&Point2Int::new(x, 0)).unwrap().get_sample_count()).collect();
+        assert_eq!(sample_counts, vec![4, 2, 2]);
+        assert!(sampler.get_entire_buffer().unwrap().get_pixel_value(Point2Int::new(0, 0)).unwrap().unwrap().equal_eps(&Color::new(0.5, 0.5, 0.5)));
+    }
+}
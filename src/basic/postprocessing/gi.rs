@@ -1,11 +1,11 @@
 use std::collections::{HashSet};
 use std::sync::{Arc, Mutex};
-use std;
 
-use defs::{IntType, FloatType, Point2Int};
+use defs::{IntType, FloatType, Point2Int, float_consts};
 use core::{WorldViewTrait, Color, Screen, ScreenIterator, RayIntersection, Material,
            RayPropagator, BasicSceneBuffer, SceneBuffer, RayPropagatorError,
-           RenderingTask, RenderingTaskProducer, ThreadSafeIterator, Ray, RayError};
+           RenderingTask, RenderingTaskProducer, ThreadSafeIterator, Ray};
+use basic::{PixelSampler};
 
 use uuid::{Uuid};
 use rand;
@@ -88,7 +88,7 @@ impl GlobalIlluminationShader {
             let mut actually_sampled = 0;
             for _counter in 0..self.sampling_size {
                 let pitch: FloatType = self.maximum_pitch_angle * random_generator.gen::<FloatType>();
-                let yaw: FloatType = 2.0 * std::f64::consts::PI * random_generator.gen::<FloatType>();
+                let yaw: FloatType = 2.0 * float_consts::PI * random_generator.gen::<FloatType>();
                 if let Ok(ray) = propagator.get_diffuse_direction_ray(pitch, yaw) {
                     if let Some(new_color) = self.worldview.get_ray_caster().cast_ray(&ray) {
                         if let Some(ref mut accumulated_color) = result{ 
@@ -128,8 +128,11 @@ impl GlobalIlluminationShader {
                         None
                     }
                 },
-                Err(RayPropagatorError::RayRelated(RayError::DepthLimitReached)) => None,
-                _ => panic!("Unhandleable ray propagation error")
+                Err(RayPropagatorError::RayRelated(ref error)) => {
+                    self.worldview.get_ray_caster().report_ray_error(error);
+                    None
+                },
+                Err(RayPropagatorError::NoRefraction) | Err(RayPropagatorError::NotRefractiveMaterial) => None
             }
         } else {
             None
@@ -151,9 +154,11 @@ impl GlobalIlluminationShader {
                         None
                     }
                 },
-                Err(RayPropagatorError::RayRelated(RayError::DepthLimitReached)) => None,
-                Err(RayPropagatorError::NoRefraction) => None,
-                _ => panic!("Ungandleable ray propagation error")
+                Err(RayPropagatorError::RayRelated(ref error)) => {
+                    self.worldview.get_ray_caster().report_ray_error(error);
+                    None
+                },
+                Err(RayPropagatorError::NoRefraction) | Err(RayPropagatorError::NotRefractiveMaterial) => None
             }
         } else {
             None
@@ -252,6 +257,20 @@ impl GlobalIlluminationShader {
     }
 }
 
+impl PixelSampler for GlobalIlluminationShader {
+    fn sample_pixel(&self, coord: &Point2Int) -> Option<Color> {
+        if let Ok(ray) = self.worldview.get_view().get_ray_to_screen_coordinate(*coord) {
+            self.trace_ray_and_calculate_color(&ray)
+        } else {
+            None
+        }
+    }
+
+    fn get_screen(&self) -> &Screen {
+        self.worldview.get_view().get_screen()
+    }
+}
+
 pub struct GlobalIlluminationShaderTaskProducer {
     shader: Arc<GlobalIlluminationShader>
 }
@@ -268,6 +287,10 @@ impl RenderingTaskProducer for GlobalIlluminationShaderTaskProducer {
     fn create_task_iterator(self: Box<Self>) -> Box<ThreadSafeIterator<Item=Box<RenderingTask>>> {
         Box::new(GlobalIlluminationShaderTaskIterator::new(Arc::clone(&self.shader)))
     }
+
+    fn get_task_count(&self) -> Option<usize> {
+        Some(self.shader.get_screen().get_pixel_count() as usize)
+    }
 }
 
 pub struct GlobalIlluminationShaderTaskIterator {
@@ -327,4 +350,8 @@ impl RenderingTask for GlobalIlluminationShaderTask {
             Err(error) => panic!("WorldViewTask: Unrecoverable SceneError: {:?}", error)
         }
     }
+
+    fn get_pixel_coord(&self) -> Option<Point2Int> {
+        Some(self.coord)
+    }
 }
\ No newline at end of file
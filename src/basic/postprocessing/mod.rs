@@ -1,7 +1,9 @@
 pub mod gi;
 pub mod filter;
 pub mod base;
+pub mod adaptive;
 
 pub use self::gi::*;
 pub use self::filter::*;
-pub use self::base::*;
\ No newline at end of file
+pub use self::base::*;
+pub use self::adaptive::*;
\ No newline at end of file
@@ -18,6 +18,10 @@ impl RenderingTaskProducer for WorldViewTaskProducer {
     fn create_task_iterator(self: Box<Self>) -> Box<ThreadSafeIterator<Item=Box<RenderingTask>>> {
         Box::new(WorldViewTaskIterator::new(Arc::clone(&self.worldview)))
     }
+
+    fn get_task_count(&self) -> Option<usize> {
+        Some(self.worldview.get_view().get_screen_pixel_count() as usize)
+    }
 }
 
 pub struct WorldViewTaskIterator {
@@ -83,4 +87,147 @@ impl RenderingTask for WorldViewTask {
             Err(error) => panic!("WorldViewTask: Unrecoverable SceneError: {:?}", error)
         }
     }
-}
\ No newline at end of file
+
+    fn get_pixel_coord(&self) -> Option<Point2Int> {
+        Some(self.coord)
+    }
+}
+
+/// Camera rays traced in tiles so neighbouring rays can be intersected together in packets
+pub struct WorldViewTileTaskProducer {
+    worldview: Arc<WorldViewTrait>,
+    tile_width: IntType,
+    tile_height: IntType
+}
+
+impl WorldViewTileTaskProducer {
+    pub fn new(worldview: Arc<WorldViewTrait>, tile_width: IntType, tile_height: IntType) -> Box<RenderingTaskProducer> {
+        Box::new(Self {
+            worldview: worldview,
+            tile_width: tile_width.max(1),
+            tile_height: tile_height.max(1)
+        })
+    }
+
+    fn get_tile_counts(screen: &Screen, tile_width: IntType, tile_height: IntType) -> (IntType, IntType) {
+        let (horizontal_resolution, vertical_resolution) = screen.get_resolution();
+        ((horizontal_resolution + tile_width - 1) / tile_width, (vertical_resolution + tile_height - 1) / tile_height)
+    }
+}
+
+impl RenderingTaskProducer for WorldViewTileTaskProducer {
+    fn create_task_iterator(self: Box<Self>) -> Box<ThreadSafeIterator<Item=Box<RenderingTask>>> {
+        Box::new(WorldViewTileTaskIterator::new(Arc::clone(&self.worldview), self.tile_width, self.tile_height))
+    }
+
+    fn get_task_count(&self) -> Option<usize> {
+        let (horizontal_count, vertical_count) = Self::get_tile_counts(self.worldview.get_view().get_screen(), self.tile_width, self.tile_height);
+        Some((horizontal_count * vertical_count) as usize)
+    }
+}
+
+pub struct WorldViewTileTaskIterator {
+    worldview: Arc<WorldViewTrait>,
+    screen: Screen,
+    tile_width: IntType,
+    tile_height: IntType,
+    tile_index: Mutex<IntType>,
+}
+
+impl WorldViewTileTaskIterator {
+    pub fn new(worldview: Arc<WorldViewTrait>, tile_width: IntType, tile_height: IntType) -> Self {
+        let screen_clone = worldview.get_view().get_screen().clone();
+
+        Self {
+            worldview: worldview,
+            screen: screen_clone,
+            tile_width: tile_width,
+            tile_height: tile_height,
+            tile_index: Mutex::new(0)
+        }
+    }
+}
+
+impl ThreadSafeIterator for WorldViewTileTaskIterator {
+    type Item = Box<RenderingTask>;
+
+    fn next(&self) -> Option<Box<RenderingTask>> {
+        if let Ok(mut tile_index) = self.tile_index.lock() {
+            let (horizontal_count, vertical_count) = WorldViewTileTaskProducer::get_tile_counts(&self.screen, self.tile_width, self.tile_height);
+            if *tile_index >= horizontal_count * vertical_count {
+                return None;
+            }
+
+            let origin = Point2Int::new((*tile_index % horizontal_count) * self.tile_width, (*tile_index / horizontal_count) * self.tile_height);
+            *tile_index += 1;
+            Some(Box::new(WorldViewTileTask::new(Arc::clone(&self.worldview), get_tile_pixels(&self.screen, origin, self.tile_width, self.tile_height))))
+        } else {
+            panic!("Mutex lock error inside WorldViewTileTaskIterator");
+        }
+    }
+}
+
+/// Pixels of the tile inside the screen, ordered in 2x2 blocks so consecutive packets of four rays stay coherent
+fn get_tile_pixels(screen: &Screen, origin: Point2Int, tile_width: IntType, tile_height: IntType) -> Vec<Point2Int> {
+    let mut result = Vec::with_capacity((tile_width * tile_height) as usize);
+    for block_y in (0..tile_height).step_by(2) {
+        for block_x in (0..tile_width).step_by(2) {
+            for (offset_x, offset_y) in [(0, 0), (1, 0), (0, 1), (1, 1)].iter() {
+                let (x, y) = (block_x + offset_x, block_y + offset_y);
+                let pixel = Point2Int::new(origin.x + x, origin.y + y);
+                if x < tile_width && y < tile_height && screen.check_pixel_bounds(&pixel) {
+                    result.push(pixel);
+                }
+            }
+        }
+    }
+    result
+}
+
+pub struct WorldViewTileTask {
+    worldview: Arc<WorldViewTrait>,
+    pixels: Vec<Point2Int>
+}
+
+impl WorldViewTileTask {
+    pub fn new(worldview: Arc<WorldViewTrait>, pixels: Vec<Point2Int>) -> Self {
+        Self {
+            worldview: worldview,
+            pixels: pixels
+        }
+    }
+}
+
+impl RenderingTask for WorldViewTileTask {
+    fn execute(self: Box<Self>) {
+        for (pixel, color_result) in self.pixels.iter().zip(self.worldview.get_pixel_colors(&self.pixels)) {
+            match color_result {
+                Ok(color) => self.worldview.accumulate_pixel_value(*pixel, &color).unwrap(),
+                Err(SceneError::NothingIntersected) => (),
+                Err(error) => panic!("WorldViewTileTask: Unrecoverable SceneError: {:?}", error)
+            }
+        }
+    }
+
+    /// Top left pixel of the tile
+    fn get_pixel_coord(&self) -> Option<Point2Int> {
+        self.pixels.first().cloned()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use defs::{Point3, Vector3};
+
+    #[test]
+    fn tile_pixels_cover_clipped_tile_in_blocks() {
+        let screen = Screen::new(Point3::origin(), Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 1.0, 0.0), 1.0, 1.0, 5, 3).unwrap();
+        let pixels = get_tile_pixels(&screen, Point2Int::new(2, 0), 4, 4);
+
+        assert_eq!(pixels.len(), 9);
+        assert_eq!(&pixels[..4], &[Point2Int::new(2, 0), Point2Int::new(3, 0), Point2Int::new(2, 1), Point2Int::new(3, 1)]);
+        assert_eq!(WorldViewTileTaskProducer::get_tile_counts(&screen, 4, 4), (2, 1));
+    }
+}
@@ -0,0 +1,71 @@
+use defs::{FloatType};
+use core::{Color, Ray, RayIntersection, RayCaster, Material, RayPropagator, RayPropagatorError, get_cosine_weighted_direction};
+use rand;
+use rand::{Rng};
+
+/// Continuation of a path at a surface with the throughput weight and the probability of the chosen lobe
+pub enum SurfaceScattering {
+    Diffuse(Ray, Color, FloatType),
+    Specular(Ray, Color, FloatType),
+    Absorbed
+}
+
+/// Probabilities of continuing diffusely, by mirroring and by refraction, the rest is absorbed
+pub fn get_scattering_probabilities(intersection: &RayIntersection) -> [FloatType; 3] {
+    let material = intersection.get_material();
+    let diffuse = material.get_diffuse_color().cloned().unwrap_or(Color::zero());
+    let reflection = if material.is_reflective() { Material::get_fresnel_reflection(intersection).unwrap_or(Color::zero()) } else { Color::zero() };
+    let refraction = if material.is_refractive() { Material::get_fresnel_refraction(intersection).unwrap_or(Color::zero()) } else { Color::zero() };
+
+    let mut probabilities = [diffuse.intensity_avg().max(0.0), reflection.intensity_avg().max(0.0), refraction.intensity_avg().max(0.0)];
+    let probability_sum: FloatType = probabilities.iter().sum();
+    if probability_sum > 1.0 {
+        for probability in probabilities.iter_mut() {
+            *probability /= probability_sum;
+        }
+    }
+    probabilities
+}
+
+/// Chooses a lobe with the uniform random number, diffuse directions are cosine weighted
+pub fn sample_surface_scattering(intersection: &RayIntersection, random: FloatType, ray_caster: &RayCaster) -> SurfaceScattering {
+    let material = intersection.get_material();
+    let probabilities = get_scattering_probabilities(intersection);
+
+    let propagator = RayPropagator::new(intersection);
+    let propagated = if random < probabilities[0] {
+        let mut random_generator = rand::thread_rng();
+        let direction = get_cosine_weighted_direction(intersection.get_normal_vector(), random_generator.gen::<FloatType>(), random_generator.gen::<FloatType>());
+        return match Ray::continue_ray_from_intersection(intersection, direction) {
+            Ok(ray) => {
+                let diffuse = *material.get_diffuse_color().unwrap();
+                SurfaceScattering::Diffuse(ray, diffuse.mul_scalar(&probabilities[0].recip()), probabilities[0])
+            },
+            Err(error) => {
+                ray_caster.report_ray_error(&error);
+                SurfaceScattering::Absorbed
+            }
+        };
+    } else if random < probabilities[0] + probabilities[1] {
+        propagator.get_mirrored_ray().map(|ray| {
+            let reflection = Material::get_fresnel_reflection(intersection).unwrap_or(Color::zero());
+            (ray, reflection.mul_scalar(&probabilities[1].recip()), probabilities[1])
+        })
+    } else if random < probabilities[0] + probabilities[1] + probabilities[2] {
+        propagator.get_refracted_ray().map(|ray| {
+            let refraction = Material::get_fresnel_refraction(intersection).unwrap_or(Color::zero());
+            (ray, refraction.mul_scalar(&probabilities[2].recip()), probabilities[2])
+        })
+    } else {
+        return SurfaceScattering::Absorbed;
+    };
+
+    match propagated {
+        Ok((ray, weight, probability)) => SurfaceScattering::Specular(ray, weight, probability),
+        Err(RayPropagatorError::RayRelated(ref error)) => {
+            ray_caster.report_ray_error(error);
+            SurfaceScattering::Absorbed
+        },
+        Err(_) => SurfaceScattering::Absorbed
+    }
+}
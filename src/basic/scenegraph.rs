@@ -0,0 +1,267 @@
+use std::collections::{HashMap};
+use std::sync::{Arc};
+
+use defs::{Matrix4, Vector3, FloatType};
+use core::{Model, ModelError, Material};
+use basic::{ModelInstance, ModelVec};
+use na::{Rotation3, Translation3, Unit};
+use uuid::{Uuid};
+
+pub type ScenePath = Vec<String>;
+
+#[derive(Debug)]
+pub enum SceneGraphError {
+    NotAGroupNode,
+    NotAModelNode
+}
+
+pub enum SceneNodeContent {
+    Group(Vec<SceneNode>),
+    Model(Arc<Model>, Option<Material>)
+}
+
+pub struct SceneNode {
+    name: String,
+    identifier: Uuid,
+    tf_matrix: Matrix4,
+    content: SceneNodeContent
+}
+
+pub struct FlattenedScene {
+    pub models: ModelVec,
+    pub paths: HashMap<Uuid, ScenePath>
+}
+
+impl SceneNode {
+    pub fn new_group(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            identifier: Uuid::new_v4(),
+            tf_matrix: Matrix4::identity(),
+            content: SceneNodeContent::Group(Vec::new())
+        }
+    }
+
+    pub fn new_model(name: &str, model: Arc<Model>) -> Self {
+        Self {
+            name: name.to_string(),
+            identifier: Uuid::new_v4(),
+            tf_matrix: Matrix4::identity(),
+            content: SceneNodeContent::Model(model, None)
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_identifier(&self) -> &Uuid {
+        &self.identifier
+    }
+
+    pub fn set_custom_identifier(&mut self, identifier: Uuid) {
+        self.identifier = identifier;
+    }
+
+    pub fn get_content(&self) -> &SceneNodeContent {
+        &self.content
+    }
+
+    /// Transformation relative to the parent node
+    pub fn get_tf_matrix(&self) -> &Matrix4 {
+        &self.tf_matrix
+    }
+
+    pub fn set_tf_matrix(&mut self, tf_matrix: Matrix4) {
+        self.tf_matrix = tf_matrix;
+    }
+
+    pub fn scale_uniform(&mut self, scaling: FloatType) {
+        self.tf_matrix = Matrix4::new_scaling(scaling) * self.tf_matrix;
+    }
+
+    pub fn translate(&mut self, translation: Vector3) {
+        self.tf_matrix = Translation3::from_vector(translation).to_homogeneous() * self.tf_matrix;
+    }
+
+    pub fn rotate(&mut self, axis: Vector3, angle: FloatType) {
+        self.tf_matrix = Rotation3::from_axis_angle(&Unit::new_normalize(axis), angle).to_homogeneous() * self.tf_matrix;
+    }
+
+    pub fn set_material_override(&mut self, material: Option<Material>) -> Result<(), SceneGraphError> {
+        match self.content {
+            SceneNodeContent::Model(_, ref mut material_override) => {
+                *material_override = material;
+                Ok(())
+            },
+            SceneNodeContent::Group(_) => Err(SceneGraphError::NotAModelNode)
+        }
+    }
+
+    pub fn add_child(&mut self, child: SceneNode) -> Result<(), SceneGraphError> {
+        match self.content {
+            SceneNodeContent::Group(ref mut children) => {
+                children.push(child);
+                Ok(())
+            },
+            SceneNodeContent::Model(_, _) => Err(SceneGraphError::NotAGroupNode)
+        }
+    }
+
+    pub fn with_child(mut self, child: SceneNode) -> Result<Self, SceneGraphError> {
+        self.add_child(child)?;
+        Ok(self)
+    }
+
+    pub fn get_children(&self) -> &[SceneNode] {
+        match self.content {
+            SceneNodeContent::Group(ref children) => children,
+            SceneNodeContent::Model(_, _) => &[]
+        }
+    }
+
+    fn get_children_mut(&mut self) -> &mut [SceneNode] {
+        match self.content {
+            SceneNodeContent::Group(ref mut children) => children,
+            SceneNodeContent::Model(_, _) => &mut []
+        }
+    }
+
+    pub fn find_by_name(&self, name: &str) -> Option<&SceneNode> {
+        if self.name == name {
+            Some(self)
+        } else {
+            self.get_children().iter().filter_map(|child| child.find_by_name(name)).next()
+        }
+    }
+
+    pub fn find_by_name_mut(&mut self, name: &str) -> Option<&mut SceneNode> {
+        if self.name == name {
+            Some(self)
+        } else {
+            self.get_children_mut().iter_mut().filter_map(|child| child.find_by_name_mut(name)).next()
+        }
+    }
+
+    pub fn find_by_identifier(&self, identifier: &Uuid) -> Option<&SceneNode> {
+        if self.identifier == *identifier {
+            Some(self)
+        } else {
+            self.get_children().iter().filter_map(|child| child.find_by_identifier(identifier)).next()
+        }
+    }
+
+    pub fn find_by_identifier_mut(&mut self, identifier: &Uuid) -> Option<&mut SceneNode> {
+        if self.identifier == *identifier {
+            Some(self)
+        } else {
+            self.get_children_mut().iter_mut().filter_map(|child| child.find_by_identifier_mut(identifier)).next()
+        }
+    }
+
+    /// Names of the nodes from this node down to the node with the identifier
+    pub fn find_path(&self, identifier: &Uuid) -> Option<ScenePath> {
+        if self.identifier == *identifier {
+            Some(vec![self.name.clone()])
+        } else {
+            self.get_children().iter().filter_map(|child| child.find_path(identifier)).next().map(|mut path| {
+                path.insert(0, self.name.clone());
+                path
+            })
+        }
+    }
+
+    fn flatten_into(&self, parent_tf_matrix: &Matrix4, parent_path: &ScenePath, result: &mut FlattenedScene) -> Result<(), ModelError> {
+        let tf_matrix = parent_tf_matrix * self.tf_matrix;
+        let mut path = parent_path.clone();
+        path.push(self.name.clone());
+
+        match self.content {
+            SceneNodeContent::Group(ref children) => {
+                for child in children {
+                    child.flatten_into(&tf_matrix, &path, result)?;
+                }
+            },
+            SceneNodeContent::Model(ref model, material_override) => {
+                let mut instance = ModelInstance::new(Arc::clone(model), tf_matrix)?;
+                instance.set_custom_identifier(self.identifier);
+                instance.set_material_override(material_override);
+                result.models.push(Box::new(instance));
+                result.paths.insert(self.identifier, path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Intersector ready list of the model nodes with their world transformations, and the scene path of every model identifier.
+    /// Fails if the accumulated transformation of a model node is not invertible
+    pub fn flatten(&self) -> Result<FlattenedScene, ModelError> {
+        let mut result = FlattenedScene {
+            models: Vec::new(),
+            paths: HashMap::new()
+        };
+        self.flatten_into(&Matrix4::identity(), &Vec::new(), &mut result)?;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use defs::{Point3, float_consts, TEST_TOLERANCE};
+    use core::{Color, Ray, RayIntersection};
+    use basic::model::{SolidSphere};
+
+    fn create_test_graph() -> SceneNode {
+        let sphere: Arc<Model> = Arc::new(SolidSphere::new(Material::new_diffuse(Color::one(), None)));
+
+        let mut table = SceneNode::new_group("table");
+        table.translate(Vector3::new(10.0, 0.0, 0.0));
+        let mut cup = SceneNode::new_model("cup", Arc::clone(&sphere));
+        cup.translate(Vector3::new(0.0, 0.0, 5.0));
+        table.add_child(cup).unwrap();
+
+        SceneNode::new_group("root").with_child(table).unwrap()
+                                    .with_child(SceneNode::new_model("ball", sphere)).unwrap()
+    }
+
+    #[test]
+    fn scene_graph_lookup() {
+        let graph = create_test_graph();
+        let cup_identifier = *graph.find_by_name("cup").expect("Cup should be found").get_identifier();
+
+        assert_eq!(graph.find_by_identifier(&cup_identifier).unwrap().get_name(), "cup");
+        assert_eq!(graph.find_path(&cup_identifier).unwrap(), vec!["root".to_string(), "table".to_string(), "cup".to_string()]);
+        assert!(graph.find_by_name("chair").is_none());
+    }
+
+    #[test]
+    fn scene_graph_flatten_accumulates_transforms() {
+        let mut graph = create_test_graph();
+        // The table stays on the rotation axis, its child cup swings from z = 5 to y = -5
+        graph.find_by_name_mut("table").unwrap().rotate(Vector3::new(1.0, 0.0, 0.0), float_consts::FRAC_PI_2);
+        let cup_identifier = *graph.find_by_name("cup").unwrap().get_identifier();
+
+        let flattened = graph.flatten().unwrap();
+        let get_hits = |ray: &Ray| -> Vec<RayIntersection> { flattened.models.iter().filter_map(|model| model.get_intersection(ray)).collect() };
+        let hits = get_hits(&Ray::new(Point3::new(10.0, -5.0, -5.0), Vector3::new(0.0, 0.0, 1.0)));
+
+        assert_eq!(flattened.models.len(), 2);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].get_model_identifier(), Some(&cup_identifier));
+        assert_relative_eq!(hits[0].get_intersection_point(), &Point3::new(10.0, -5.0, -1.0), epsilon = TEST_TOLERANCE);
+        assert!(get_hits(&Ray::new(Point3::new(10.0, -5.0, 5.0), Vector3::new(0.0, 1.0, 0.0))).is_empty());
+        assert_eq!(flattened.paths[&cup_identifier].len(), 3);
+    }
+
+    #[test]
+    fn scene_graph_flatten_rejects_zero_scaled_group() {
+        let mut graph = create_test_graph();
+        graph.find_by_name_mut("table").unwrap().scale_uniform(0.0);
+
+        match graph.flatten() {
+            Err(ModelError::UninvertibleTransformation) => {},
+            _ => panic!("Flattening a zero scaled group should fail")
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use defs::{Point3, Vector3, Matrix4, FloatType};
+use core::{Ray};
+
+
+#[derive(Debug, Clone, Copy)]
+pub struct BoundingBox {
+    min: Point3,
+    max: Point3
+}
+
+impl BoundingBox {
+    pub fn new(first: Point3, second: Point3) -> Self {
+        Self {
+            min: Point3::new(first.x.min(second.x), first.y.min(second.y), first.z.min(second.z)),
+            max: Point3::new(first.x.max(second.x), first.y.max(second.y), first.z.max(second.z))
+        }
+    }
+
+    pub fn new_from_points(points: &[Point3]) -> Option<Self> {
+        match points.split_first() {
+            Some((first, rest)) => {
+                Some(rest.iter().fold(Self::new(*first, *first), |acc, point| acc.get_extended(point)))
+            },
+            None => None
+        }
+    }
+
+    pub fn get_min(&self) -> &Point3 {
+        &self.min
+    }
+
+    pub fn get_max(&self) -> &Point3 {
+        &self.max
+    }
+
+    pub fn get_center(&self) -> Point3 {
+        Point3::from((self.min.coords + self.max.coords) * 0.5)
+    }
+
+    pub fn get_extent(&self) -> Vector3 {
+        self.max - self.min
+    }
+
+    pub fn get_surface_area(&self) -> FloatType {
+        let extent = self.get_extent();
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+
+    pub fn get_longest_axis(&self) -> usize {
+        let extent = self.get_extent();
+        if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn get_corners(&self) -> [Point3; 8] {
+        [Point3::new(self.min.x, self.min.y, self.min.z),
+         Point3::new(self.max.x, self.min.y, self.min.z),
+         Point3::new(self.min.x, self.max.y, self.min.z),
+         Point3::new(self.max.x, self.max.y, self.min.z),
+         Point3::new(self.min.x, self.min.y, self.max.z),
+         Point3::new(self.max.x, self.min.y, self.max.z),
+         Point3::new(self.min.x, self.max.y, self.max.z),
+         Point3::new(self.max.x, self.max.y, self.max.z)]
+    }
+
+    pub fn get_extended(&self, point: &Point3) -> Self {
+        Self {
+            min: Point3::new(self.min.x.min(point.x), self.min.y.min(point.y), self.min.z.min(point.z)),
+            max: Point3::new(self.max.x.max(point.x), self.max.y.max(point.y), self.max.z.max(point.z))
+        }
+    }
+
+    pub fn get_union(&self, other: &BoundingBox) -> Self {
+        self.get_extended(&other.min).get_extended(&other.max)
+    }
+
+    pub fn contains_point(&self, point: &Point3) -> bool {
+        self.min.x <= point.x && point.x <= self.max.x &&
+        self.min.y <= point.y && point.y <= self.max.y &&
+        self.min.z <= point.z && point.z <= self.max.z
+    }
+
+    /// Axis aligned box enclosing this box after the transformation
+    pub fn get_transformed(&self, transformation_matrix: &Matrix4) -> Self {
+        let corners: Vec<Point3> = self.get_corners().iter().map(|corner| {
+            Point3::from_homogeneous(transformation_matrix * corner.to_homogeneous()).expect("Unhomogeneous transformed point")
+        }).collect();
+
+        Self::new_from_points(&corners).unwrap()
+    }
+
+    /// Entry and exit distances of the ray along its direction, None if the box is missed or behind the ray
+    pub fn get_ray_distances(&self, ray: &Ray) -> Option<(FloatType, FloatType)> {
+        let origin = ray.get_origin();
+        let direction = ray.get_direction();
+        let mut near: FloatType = FloatType::NEG_INFINITY;
+        let mut far: FloatType = FloatType::INFINITY;
+
+        for axis in 0..3 {
+            let inverse_direction = direction[axis].recip();
+            let mut t0 = (self.min[axis] - origin[axis]) * inverse_direction;
+            let mut t1 = (self.max[axis] - origin[axis]) * inverse_direction;
+            if t0 > t1 {
+                ::std::mem::swap(&mut t0, &mut t1);
+            }
+            if !t0.is_nan() {
+                near = near.max(t0);
+            }
+            if !t1.is_nan() {
+                far = far.min(t1);
+            }
+            if near > far {
+                return None;
+            }
+        }
+
+        if far >= 0.0 {
+            Some((near.max(0.0), far))
+        } else {
+            None
+        }
+    }
+
+    pub fn is_intersected_by(&self, ray: &Ray) -> bool {
+        self.get_ray_distances(ray).is_some()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounding_box_ray_hit_and_miss() {
+        let bounding_box = BoundingBox::new(Point3::new(1.0, 1.0, 1.0), Point3::new(-1.0, -1.0, -1.0));
+        let hitting_ray = Ray::new(Point3::new(-5.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0));
+        let missing_ray = Ray::new(Point3::new(-5.0, 2.0, 0.0), Vector3::new(1.0, 0.0, 0.0));
+        let behind_ray = Ray::new(Point3::new(5.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0));
+
+        let (near, far) = bounding_box.get_ray_distances(&hitting_ray).expect("Ray should hit the box");
+        assert_relative_eq!(near, 4.0);
+        assert_relative_eq!(far, 6.0);
+        assert!(!bounding_box.is_intersected_by(&missing_ray));
+        assert!(!bounding_box.is_intersected_by(&behind_ray));
+    }
+
+    #[test]
+    fn bounding_box_transformed() {
+        let bounding_box = BoundingBox::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 2.0, 3.0));
+        let mut transformation = Matrix4::new_scaling(2.0);
+        transformation[(0, 3)] = 1.0;
+
+        let transformed = bounding_box.get_transformed(&transformation);
+
+        assert_relative_eq!(transformed.get_min(), &Point3::new(1.0, 0.0, 0.0));
+        assert_relative_eq!(transformed.get_max(), &Point3::new(3.0, 4.0, 6.0));
+        assert_relative_eq!(transformed.get_surface_area(), 2.0 * (2.0 * 4.0 + 4.0 * 6.0 + 6.0 * 2.0));
+    }
+}
@@ -5,6 +5,7 @@ use defs::FloatType;
 use tools::CompareWithTolerance;
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
 pub enum ColorComponent {
     Red,
     Green,
@@ -13,6 +14,7 @@ pub enum ColorComponent {
 
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
 pub struct Color {
     r: FloatType,
     g: FloatType,
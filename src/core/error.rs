@@ -0,0 +1,238 @@
+use std::error::{Error};
+use std::fmt;
+
+use core::{ScreenError, ViewError, RayError, RayIntersectionError, RayPropagatorError, ModelError, SceneError, SceneBufferError, BasicSceneBufferError};
+
+
+/// Every error of the core, so callers running long renders can handle them in one place instead of panicking
+#[derive(Debug)]
+pub enum RenderError {
+    ScreenRelated(ScreenError),
+    ViewRelated(ViewError),
+    RayRelated(RayError),
+    RayIntersectionRelated(RayIntersectionError),
+    RayPropagatorRelated(RayPropagatorError),
+    ModelRelated(ModelError),
+    SceneRelated(SceneError),
+    SceneBufferRelated(SceneBufferError),
+    BasicSceneBufferRelated(BasicSceneBufferError)
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RenderError::ScreenRelated(ref error) => write!(f, "Screen error: {}", error),
+            RenderError::ViewRelated(ref error) => write!(f, "View error: {}", error),
+            RenderError::RayRelated(ref error) => write!(f, "Ray error: {}", error),
+            RenderError::RayIntersectionRelated(ref error) => write!(f, "Ray intersection error: {}", error),
+            RenderError::RayPropagatorRelated(ref error) => write!(f, "Ray propagation error: {}", error),
+            RenderError::ModelRelated(ref error) => write!(f, "Model error: {}", error),
+            RenderError::SceneRelated(ref error) => write!(f, "Scene error: {}", error),
+            RenderError::SceneBufferRelated(ref error) => write!(f, "Scene buffer error: {}", error),
+            RenderError::BasicSceneBufferRelated(ref error) => write!(f, "Scene buffer error: {}", error)
+        }
+    }
+}
+
+impl Error for RenderError {
+    fn source(&self) -> Option<&(Error + 'static)> {
+        match *self {
+            RenderError::ScreenRelated(ref error) => Some(error),
+            RenderError::ViewRelated(ref error) => Some(error),
+            RenderError::RayRelated(ref error) => Some(error),
+            RenderError::RayIntersectionRelated(ref error) => Some(error),
+            RenderError::RayPropagatorRelated(ref error) => Some(error),
+            RenderError::ModelRelated(ref error) => Some(error),
+            RenderError::SceneRelated(ref error) => Some(error),
+            RenderError::SceneBufferRelated(ref error) => Some(error),
+            RenderError::BasicSceneBufferRelated(ref error) => Some(error)
+        }
+    }
+}
+
+impl From<ScreenError> for RenderError {
+    fn from(error: ScreenError) -> Self {
+        RenderError::ScreenRelated(error)
+    }
+}
+
+impl From<ViewError> for RenderError {
+    fn from(error: ViewError) -> Self {
+        RenderError::ViewRelated(error)
+    }
+}
+
+impl From<RayError> for RenderError {
+    fn from(error: RayError) -> Self {
+        RenderError::RayRelated(error)
+    }
+}
+
+impl From<RayIntersectionError> for RenderError {
+    fn from(error: RayIntersectionError) -> Self {
+        RenderError::RayIntersectionRelated(error)
+    }
+}
+
+impl From<RayPropagatorError> for RenderError {
+    fn from(error: RayPropagatorError) -> Self {
+        RenderError::RayPropagatorRelated(error)
+    }
+}
+
+impl From<ModelError> for RenderError {
+    fn from(error: ModelError) -> Self {
+        RenderError::ModelRelated(error)
+    }
+}
+
+impl From<SceneError> for RenderError {
+    fn from(error: SceneError) -> Self {
+        RenderError::SceneRelated(error)
+    }
+}
+
+impl From<SceneBufferError> for RenderError {
+    fn from(error: SceneBufferError) -> Self {
+        RenderError::SceneBufferRelated(error)
+    }
+}
+
+impl From<BasicSceneBufferError> for RenderError {
+    fn from(error: BasicSceneBufferError) -> Self {
+        RenderError::BasicSceneBufferRelated(error)
+    }
+}
+
+
+impl fmt::Display for ScreenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ScreenError::PixelOutOfBoundsError => write!(f, "Pixel is outside of the screen"),
+            ScreenError::InvalidDimensions => write!(f, "Screen size and resolution have to be positive"),
+            ScreenError::ParallelNormalAndUp => write!(f, "Screen normal and up vectors are pointing in the same direction"),
+            ScreenError::UninvertibleTransformation => write!(f, "Screen transformation matrix is not invertible")
+        }
+    }
+}
+
+impl Error for ScreenError {}
+
+impl fmt::Display for ViewError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ViewError::ScreenRelated(ref error) => write!(f, "{}", error)
+        }
+    }
+}
+
+impl Error for ViewError {
+    fn source(&self) -> Option<&(Error + 'static)> {
+        match *self {
+            ViewError::ScreenRelated(ref error) => Some(error)
+        }
+    }
+}
+
+impl fmt::Display for RayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RayError::DepthLimitReached => write!(f, "Ray depth limit reached"),
+            RayError::InvalidContinuationDirection => write!(f, "Ray can not continue in the given direction")
+        }
+    }
+}
+
+impl Error for RayError {}
+
+impl fmt::Display for RayIntersectionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RayIntersectionError::NoRayTravelDistance => write!(f, "Intersection is at the origin of the ray"),
+            RayIntersectionError::NoModelIdentifierPresent => write!(f, "Intersection has no model identifier")
+        }
+    }
+}
+
+impl Error for RayIntersectionError {}
+
+impl fmt::Display for RayPropagatorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RayPropagatorError::RayRelated(ref error) => write!(f, "{}", error),
+            RayPropagatorError::NoRefraction => write!(f, "Total internal reflection, there is no refracted ray"),
+            RayPropagatorError::NotRefractiveMaterial => write!(f, "Material is not refractive")
+        }
+    }
+}
+
+impl Error for RayPropagatorError {
+    fn source(&self) -> Option<&(Error + 'static)> {
+        match *self {
+            RayPropagatorError::RayRelated(ref error) => Some(error),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ModelError::UninvertibleTransformation => write!(f, "Model view matrix is not invertible")
+        }
+    }
+}
+
+impl Error for ModelError {}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SceneError::NothingIntersected => write!(f, "Nothing was intersected"),
+            SceneError::InvalidInputCoord => write!(f, "Pixel is outside of the scene")
+        }
+    }
+}
+
+impl Error for SceneError {}
+
+impl fmt::Display for SceneBufferError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SceneBufferError::InvalidInputCoord => write!(f, "Pixel is outside of the buffer"),
+            SceneBufferError::MutexLockError => write!(f, "Buffer lock is poisoned"),
+            SceneBufferError::OtherBufferNotSameSize => write!(f, "Buffers are not the same size")
+        }
+    }
+}
+
+impl Error for SceneBufferError {}
+
+impl fmt::Display for BasicSceneBufferError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BasicSceneBufferError::BufferNotCorrectSize => write!(f, "Buffer size does not match the screen resolution")
+        }
+    }
+}
+
+impl Error for BasicSceneBufferError {}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use defs::{Point3, Vector3};
+    use core::{Screen};
+
+    #[test]
+    fn render_error_wraps_sources() {
+        let screen_error = Screen::new(Point3::origin(), Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 0.0, 2.0), 1.0, 1.0, 4, 4).err().expect("Parallel normal and up should be rejected");
+        let error = RenderError::from(ViewError::ScreenRelated(screen_error));
+
+        assert_eq!(error.to_string(), "View error: Screen normal and up vectors are pointing in the same direction");
+        assert!(error.source().and_then(|source| source.source()).is_some());
+        assert!(Screen::new(Point3::origin(), Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 1.0, 0.0), 1.0, 1.0, 0, 4).is_err());
+        assert_eq!(RenderError::from(RayPropagatorError::RayRelated(RayError::DepthLimitReached)).to_string(), "Ray propagation error: Ray depth limit reached");
+    }
+}
@@ -1,4 +1,8 @@
-use std::sync::{Mutex};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+
+use defs::{Point2Int};
 
 pub trait ThreadSafeIterator: Send + Sync {
     type Item;
@@ -8,10 +12,18 @@ pub trait ThreadSafeIterator: Send + Sync {
 
 pub trait RenderingTask: Send + Sync {
     fn execute(self: Box<Self>);
+
+    fn get_pixel_coord(&self) -> Option<Point2Int> {
+        None
+    }
 }
 
 pub trait RenderingTaskProducer: Send + Sync {
     fn create_task_iterator(self: Box<Self>) -> Box<ThreadSafeIterator<Item=Box<RenderingTask>>>;
+
+    fn get_task_count(&self) -> Option<usize> {
+        None
+    }
 }
 
 pub struct OrderedTaskProducers {
@@ -30,6 +42,19 @@ impl RenderingTaskProducer for OrderedTaskProducers {
     fn create_task_iterator(mut self: Box<Self>) -> Box<ThreadSafeIterator<Item=Box<RenderingTask>>> {
         Box::new(OrderedTaskIterator::new(self.producers.take().expect("OrderedTaskProducers should have not been created empty")))
     }
+
+    fn get_task_count(&self) -> Option<usize> {
+        if let Some(ref producers) = self.producers {
+            producers.iter().fold(Some(0), |acc, producer| {
+                match (acc, producer.get_task_count()) {
+                    (Some(accumulated), Some(count)) => Some(accumulated + count),
+                    _ => None
+                }
+            })
+        } else {
+            None
+        }
+    }
 }
 
 
@@ -99,3 +124,301 @@ impl ThreadSafeIterator for OrderedTaskIterator {
         }
     }
 }
+
+
+/// Executes every task of the producer on the given number of threads and returns when all of them finished
+pub fn execute_rendering_tasks(producer: Box<RenderingTaskProducer>, thread_count: usize) {
+    let iterator = Arc::new(producer.create_task_iterator());
+    let workers: Vec<thread::JoinHandle<()>> = (0..thread_count.max(1)).map(|_| {
+        let worker_iterator = Arc::clone(&iterator);
+        thread::spawn(move || {
+            while let Some(task) = worker_iterator.next() {
+                task.execute();
+            }
+        })
+    }).collect();
+
+    for worker in workers {
+        worker.join().expect("Rendering worker thread panicked");
+    }
+}
+
+
+#[derive(Clone)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self {
+            cancelled: Arc::new(AtomicBool::new(false))
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+pub struct RenderingProgress {
+    total: Mutex<Option<usize>>,
+    completed: AtomicUsize
+}
+
+impl RenderingProgress {
+    pub fn new() -> Self {
+        Self {
+            total: Mutex::new(None),
+            completed: AtomicUsize::new(0)
+        }
+    }
+
+    fn set_total_task_count(&self, total: Option<usize>) {
+        if let Ok(ref mut unlocked_total) = self.total.lock() {
+            **unlocked_total = total;
+        } else {
+            panic!("Mutex lock error inside RenderingProgress");
+        }
+    }
+
+    fn increment_completed(&self) {
+        self.completed.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn get_total_task_count(&self) -> Option<usize> {
+        if let Ok(unlocked_total) = self.total.lock() {
+            *unlocked_total
+        } else {
+            panic!("Mutex lock error inside RenderingProgress");
+        }
+    }
+
+    pub fn get_completed_task_count(&self) -> usize {
+        self.completed.load(Ordering::SeqCst)
+    }
+
+    pub fn get_completed_ratio(&self) -> Option<f64> {
+        match self.get_total_task_count() {
+            Some(0) => Some(1.0),
+            Some(total) => Some((self.get_completed_task_count() as f64 / total as f64).min(1.0)),
+            None => None
+        }
+    }
+}
+
+pub trait RenderingObserver: Send + Sync {
+    fn task_finished(&self, pixel: Option<Point2Int>, progress: &RenderingProgress);
+}
+
+pub struct ObservedTaskProducer {
+    producer: Box<RenderingTaskProducer>,
+    progress: Arc<RenderingProgress>,
+    observer: Option<Arc<RenderingObserver>>,
+    cancellation: CancellationToken
+}
+
+impl ObservedTaskProducer {
+    pub fn new(producer: Box<RenderingTaskProducer>,
+               progress: Arc<RenderingProgress>,
+               observer: Option<Arc<RenderingObserver>>,
+               cancellation: CancellationToken) -> Box<RenderingTaskProducer> {
+        progress.set_total_task_count(producer.get_task_count());
+        Box::new(Self {
+            producer: producer,
+            progress: progress,
+            observer: observer,
+            cancellation: cancellation
+        })
+    }
+}
+
+impl RenderingTaskProducer for ObservedTaskProducer {
+    fn create_task_iterator(self: Box<Self>) -> Box<ThreadSafeIterator<Item=Box<RenderingTask>>> {
+        let unboxed = *self;
+        Box::new(ObservedTaskIterator {
+            iterator: unboxed.producer.create_task_iterator(),
+            progress: unboxed.progress,
+            observer: unboxed.observer,
+            cancellation: unboxed.cancellation
+        })
+    }
+
+    fn get_task_count(&self) -> Option<usize> {
+        self.producer.get_task_count()
+    }
+}
+
+struct ObservedTaskIterator {
+    iterator: Box<ThreadSafeIterator<Item=Box<RenderingTask>>>,
+    progress: Arc<RenderingProgress>,
+    observer: Option<Arc<RenderingObserver>>,
+    cancellation: CancellationToken
+}
+
+impl ThreadSafeIterator for ObservedTaskIterator {
+    type Item = Box<RenderingTask>;
+
+    fn next(&self) -> Option<Box<RenderingTask>> {
+        if self.cancellation.is_cancelled() {
+            None
+        } else {
+            match self.iterator.next() {
+                Some(task) => Some(Box::new(ObservedTask {
+                    task: task,
+                    progress: Arc::clone(&self.progress),
+                    observer: self.observer.clone()
+                })),
+                None => None
+            }
+        }
+    }
+}
+
+struct ObservedTask {
+    task: Box<RenderingTask>,
+    progress: Arc<RenderingProgress>,
+    observer: Option<Arc<RenderingObserver>>
+}
+
+impl RenderingTask for ObservedTask {
+    fn execute(self: Box<Self>) {
+        let pixel = self.task.get_pixel_coord();
+        self.task.execute();
+        self.progress.increment_completed();
+        if let Some(ref observer) = self.observer {
+            observer.task_finished(pixel, &self.progress);
+        }
+    }
+
+    fn get_pixel_coord(&self) -> Option<Point2Int> {
+        self.task.get_pixel_coord()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use defs::{IntType};
+
+    struct CountingTask {
+        coord: Point2Int,
+        executed: Arc<AtomicUsize>
+    }
+
+    impl RenderingTask for CountingTask {
+        fn execute(self: Box<Self>) {
+            self.executed.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn get_pixel_coord(&self) -> Option<Point2Int> {
+            Some(self.coord)
+        }
+    }
+
+    struct CountingTaskProducer {
+        task_count: usize,
+        executed: Arc<AtomicUsize>
+    }
+
+    impl RenderingTaskProducer for CountingTaskProducer {
+        fn create_task_iterator(self: Box<Self>) -> Box<ThreadSafeIterator<Item=Box<RenderingTask>>> {
+            Box::new(CountingTaskIterator {
+                remaining: Mutex::new(self.task_count),
+                executed: Arc::clone(&self.executed)
+            })
+        }
+
+        fn get_task_count(&self) -> Option<usize> {
+            Some(self.task_count)
+        }
+    }
+
+    struct CountingTaskIterator {
+        remaining: Mutex<usize>,
+        executed: Arc<AtomicUsize>
+    }
+
+    impl ThreadSafeIterator for CountingTaskIterator {
+        type Item = Box<RenderingTask>;
+
+        fn next(&self) -> Option<Box<RenderingTask>> {
+            let mut remaining = self.remaining.lock().unwrap();
+            if *remaining > 0 {
+                *remaining -= 1;
+                Some(Box::new(CountingTask {
+                    coord: Point2Int::new(*remaining as IntType, 0),
+                    executed: Arc::clone(&self.executed)
+                }))
+            } else {
+                None
+            }
+        }
+    }
+
+    struct RecordingObserver {
+        pixels: Mutex<Vec<Point2Int>>
+    }
+
+    impl RenderingObserver for RecordingObserver {
+        fn task_finished(&self, pixel: Option<Point2Int>, _progress: &RenderingProgress) {
+            self.pixels.lock().unwrap().push(pixel.unwrap());
+        }
+    }
+
+    fn counting_producer(task_count: usize, executed: &Arc<AtomicUsize>) -> Box<RenderingTaskProducer> {
+        Box::new(CountingTaskProducer {
+            task_count: task_count,
+            executed: Arc::clone(executed)
+        })
+    }
+
+    #[test]
+    fn observed_producer_reports_progress() {
+        let executed = Arc::new(AtomicUsize::new(0));
+        let progress = Arc::new(RenderingProgress::new());
+        let observer = Arc::new(RecordingObserver { pixels: Mutex::new(Vec::new()) });
+        let producer = OrderedTaskProducers::new(vec![counting_producer(2, &executed), counting_producer(3, &executed)]);
+        let observed = ObservedTaskProducer::new(producer, Arc::clone(&progress), Some(observer.clone()), CancellationToken::new());
+
+        assert_eq!(progress.get_total_task_count(), Some(5));
+
+        let iterator = observed.create_task_iterator();
+        while let Some(task) = iterator.next() {
+            task.execute();
+        }
+
+        assert_eq!(progress.get_completed_task_count(), 5);
+        assert_eq!(executed.load(Ordering::SeqCst), 5);
+        assert_eq!(observer.pixels.lock().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn execute_rendering_tasks_runs_every_task() {
+        let executed = Arc::new(AtomicUsize::new(0));
+        execute_rendering_tasks(counting_producer(100, &executed), 4);
+
+        assert_eq!(executed.load(Ordering::SeqCst), 100);
+    }
+
+    #[test]
+    fn cancelled_producer_stops_handing_out_tasks() {
+        let executed = Arc::new(AtomicUsize::new(0));
+        let progress = Arc::new(RenderingProgress::new());
+        let cancellation = CancellationToken::new();
+        let observed = ObservedTaskProducer::new(counting_producer(10, &executed), Arc::clone(&progress), None, cancellation.clone());
+
+        let iterator = observed.create_task_iterator();
+        iterator.next().unwrap().execute();
+        cancellation.cancel();
+
+        assert!(iterator.next().is_none());
+        assert_eq!(progress.get_completed_task_count(), 1);
+        assert_eq!(executed.load(Ordering::SeqCst), 1);
+    }
+}
@@ -1,12 +1,10 @@
-use defs::{FloatType, Point3, Vector3, Matrix4};
+use defs::{FloatType, Point3, Vector3, Matrix4, FLOAT_DISTANCE_TOLERANCE};
 use core::{Ray, Material};
-use tools::{CompareWithTolerance};
+use tools::{CompareWithTolerance, get_rounding_error_bound};
 use na::{Unit};
 use na;
 use uuid::{Uuid};
 
-static MIMIMUM_INTERSECTION_DISTANCE: FloatType = 0.000000001;
-
 #[derive(Debug)]
 pub enum RayIntersectionError {
     NoRayTravelDistance,
@@ -27,7 +25,9 @@ pub struct RayIntersection {
 impl RayIntersection {
     pub fn new(normal: Vector3, point: Point3, ray: &Ray, material: Material, was_inside: bool) -> Result<Self, RayIntersectionError> {
         let distance_to_intersection = na::distance(ray.get_origin(), &point);
-        if distance_to_intersection.greater_eq_eps(&MIMIMUM_INTERSECTION_DISTANCE) {
+        let largest_coordinate = ray.get_origin().iter().chain(point.iter()).fold(0.0, |acc: FloatType, coordinate| acc.max(coordinate.abs()));
+        let minimum_distance = FLOAT_DISTANCE_TOLERANCE.max(get_rounding_error_bound(16) * largest_coordinate);
+        if distance_to_intersection.greater_eq_eps(&minimum_distance) {
             Ok (Self {  normal:  Unit::new_normalize(normal), 
                         point: point, 
                         ray: ray.clone(),
@@ -63,6 +63,15 @@ impl RayIntersection {
         self.distance_to_intersection
     }
 
+    /// Per component bound of the rounding error of the intersection point, conservative for points computed along the intersector ray
+    pub fn get_intersection_error(&self) -> Vector3 {
+        let origin = self.ray.get_origin();
+        let error_bound = get_rounding_error_bound(7);
+        Vector3::new((self.point.x.abs() + origin.x.abs()) * error_bound,
+                     (self.point.y.abs() + origin.y.abs()) * error_bound,
+                     (self.point.z.abs() + origin.z.abs()) * error_bound)
+    }
+
     pub fn get_intersector_ray(&self) -> &Ray {
         &self.ray
     }
@@ -91,6 +100,15 @@ impl RayIntersection {
         Self::new(normal, point, &ray, self.material_at_intersection, self.was_inside)
     }
 
+    pub fn set_material_mut(&mut self, material: Material) {
+        self.material_at_intersection = material;
+    }
+
+    pub fn set_material(&self, material: Material) -> Self {
+        Self {  material_at_intersection: material,
+                ..self.clone() }
+    }
+
     pub fn get_model_identifier(&self) -> Option<&Uuid> {
         self.model_identifier.as_ref()
     }
@@ -1,6 +1,9 @@
-use core::{Ray, RayIntersection, Color, RayCaster};
-use defs::Vector3;
+use core::{Ray, RayIntersection, Color, RayCaster, BoundingBox};
+use defs::{Vector3, FloatType};
 use na::{Unit};
+use std::collections::{HashSet};
+use std::sync::{Arc};
+use uuid::{Uuid};
 
 pub struct LightIntersection {
     illumination: Color,
@@ -27,12 +30,150 @@ impl LightIntersection {
     }
 }
 
+/// Models a light interacts with, keyed by model identifier. Models without identifier are only part of All and Exclude sets
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
+pub enum LightLinkSet {
+    All,
+    Include(HashSet<Uuid>),
+    Exclude(HashSet<Uuid>)
+}
+
+impl LightLinkSet {
+    pub fn contains(&self, model_identifier: Option<&Uuid>) -> bool {
+        match *self {
+            LightLinkSet::All => true,
+            LightLinkSet::Include(ref identifiers) => model_identifier.map_or(false, |identifier| identifiers.contains(identifier)),
+            LightLinkSet::Exclude(ref identifiers) => model_identifier.map_or(true, |identifier| !identifiers.contains(identifier))
+        }
+    }
+
+    pub fn is_all(&self) -> bool {
+        *self == LightLinkSet::All
+    }
+}
+
+/// Models a light illuminates and models casting shadows of it
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
+pub struct LightLinking {
+    pub illuminated: LightLinkSet,
+    pub shadow_casters: LightLinkSet
+}
+
+impl LightLinking {
+    pub fn new(illuminated: LightLinkSet, shadow_casters: LightLinkSet) -> Self {
+        Self {
+            illuminated: illuminated,
+            shadow_casters: shadow_casters
+        }
+    }
+
+    pub fn new_unlinked() -> Self {
+        Self::new(LightLinkSet::All, LightLinkSet::All)
+    }
+}
+
+/// Extent of a light and its intensity at unit distance, used to estimate its contribution when sampling among many lights
+#[derive(Debug, Clone, Copy)]
+pub struct LightBounds {
+    pub bounding_box: BoundingBox,
+    pub intensity: FloatType
+}
+
+impl LightBounds {
+    pub fn new(bounding_box: BoundingBox, intensity: FloatType) -> Self {
+        Self {
+            bounding_box: bounding_box,
+            intensity: intensity
+        }
+    }
+}
+
+/// Ray leaving a light source, the power is the radiant intensity in the ray direction divided by the pdf of sampling it
+pub struct LightEmission {
+    pub ray: Ray,
+    pub power: Color
+}
+
+impl LightEmission {
+    pub fn new(ray: Ray, power: Color) -> Self {
+        Self {
+            ray: ray,
+            power: power
+        }
+    }
+}
+
 pub trait LightSource: Send + Sync {
     fn get_ray_to_intersection(&self, intersection: &RayIntersection) -> Option<Ray>;
     fn get_illumination_at(&self, intersection: &RayIntersection) -> Option<LightIntersection>;
     fn get_intersection(&self, ray: &Ray) -> Option<LightIntersection>;
+
+    /// Shadow rays towards the intersection paired with the unshadowed illumination they carry, area and environment lights return several
+    fn get_illumination_samples(&self, intersection: &RayIntersection) -> Vec<(Ray, LightIntersection)> {
+        match (self.get_ray_to_intersection(intersection), self.get_illumination_at(intersection)) {
+            (Some(ray), Some(illumination)) => vec![(ray, illumination)],
+            _ => Vec::new()
+        }
+    }
+
+    /// Ray leaving the light drawn with two uniform random numbers, for light tracing integrators.
+    /// Radiant intensities are pi times the color and intensity, matching the direct illumination of diffuse surfaces
+    fn sample_emission(&self, _u1: FloatType, _u2: FloatType) -> Option<LightEmission> {
+        None
+    }
+
+    /// Probability density per solid angle of sample_emission choosing the direction, None if the light does not emit rays
+    fn get_emission_direction_pdf(&self, _direction: &Vector3) -> Option<FloatType> {
+        None
+    }
+
+    /// None for lights without position like environment and directional lights, those are evaluated at every shading point
+    fn get_bounds(&self) -> Option<LightBounds> {
+        None
+    }
+}
+
+impl<LightT: LightSource + ?Sized> LightSource for Arc<LightT> {
+    fn get_ray_to_intersection(&self, intersection: &RayIntersection) -> Option<Ray> {
+        (**self).get_ray_to_intersection(intersection)
+    }
+
+    fn get_illumination_at(&self, intersection: &RayIntersection) -> Option<LightIntersection> {
+        (**self).get_illumination_at(intersection)
+    }
+
+    fn get_intersection(&self, ray: &Ray) -> Option<LightIntersection> {
+        (**self).get_intersection(ray)
+    }
+
+    fn get_illumination_samples(&self, intersection: &RayIntersection) -> Vec<(Ray, LightIntersection)> {
+        (**self).get_illumination_samples(intersection)
+    }
+
+    fn sample_emission(&self, u1: FloatType, u2: FloatType) -> Option<LightEmission> {
+        (**self).sample_emission(u1, u2)
+    }
+
+    fn get_emission_direction_pdf(&self, direction: &Vector3) -> Option<FloatType> {
+        (**self).get_emission_direction_pdf(direction)
+    }
+
+    fn get_bounds(&self) -> Option<LightBounds> {
+        (**self).get_bounds()
+    }
 }
 
 pub trait Illuminator: Send + Sync {
     fn get_illumination_at(&self, intersection: &RayIntersection, illumination_caster: &RayCaster) -> Vec<LightIntersection>;
+
+    /// Color seen by a ray leaving the scene without intersecting any model
+    fn get_escaped_ray_color(&self, _ray: &Ray) -> Option<Color> {
+        None
+    }
+
+    fn get_light_sources(&self) -> Vec<&LightSource> {
+        Vec::new()
+    }
 }
\ No newline at end of file
@@ -3,7 +3,10 @@ use core::{Color, ColorComponent, FresnelIndex, RayIntersection, LightIntersecti
 use defs::FloatType;
 use tools::CompareWithTolerance;
 
+/// Only the indices are serialized, the cached values are calculated again when loading
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde-serialize", serde(from = "FresnelParameters", into = "FresnelParameters"))]
 struct FresnelData {
     pub n: FresnelIndex,
     pub n_inverse: FresnelIndex,
@@ -22,8 +25,12 @@ impl FresnelData {
         let mut f0 = ((real - FresnelIndex::one()) * (real - FresnelIndex::one())) + imaginary * imaginary;
         f0 *=  (((real + FresnelIndex::one()) * (real + FresnelIndex::one())) + imaginary * imaginary).recip();
 
-        let mut f0_inverse = ((imaginary - FresnelIndex::one()) * (imaginary - FresnelIndex::one())) + imaginary * imaginary;
-        f0_inverse *=  (((imaginary + FresnelIndex::one()) * (imaginary + FresnelIndex::one())) + imaginary * imaginary).recip(); 
+        // Reflectance from inside uses the reciprocal of the complex index n + ik
+        let magnitude_inverse = ((real * real) + (imaginary * imaginary)).recip();
+        let real_inverse = real * magnitude_inverse;
+        let imaginary_inverse = imaginary * magnitude_inverse;
+        let mut f0_inverse = ((real_inverse - FresnelIndex::one()) * (real_inverse - FresnelIndex::one())) + imaginary_inverse * imaginary_inverse;
+        f0_inverse *=  (((real_inverse + FresnelIndex::one()) * (real_inverse + FresnelIndex::one())) + imaginary_inverse * imaginary_inverse).recip();
 
         Self {  n: real,
                 n_inverse: real.recip(),
@@ -42,13 +49,18 @@ impl FresnelData {
         let view_and_normal_angle_cosine = intersection.get_view_direction().dot(intersection.get_normal_vector());
 
         if view_and_normal_angle_cosine.greater_eq_eps(&0.0) {
-            let f = if !intersection.was_inside() {
-                self.f0
+            let (f, cosine) = if !intersection.was_inside() {
+                (self.f0, view_and_normal_angle_cosine)
             } else {
-                self.f0_inverse
+                // Leaving the denser medium the approximation uses the angle of the transmitted ray
+                let transmitted_sine_squared = self.n_avg.powi(2) * (1.0 - view_and_normal_angle_cosine.powi(2));
+                if transmitted_sine_squared >= 1.0 {
+                    return Some(Color::one());
+                }
+                (self.f0_inverse, (1.0 - transmitted_sine_squared).sqrt())
             };
 
-            let f1 = (Color::one()-f) * Color::one().mul_scalar(&(1.0 - view_and_normal_angle_cosine).powi(5));
+            let f1 = (Color::one()-f) * Color::one().mul_scalar(&(1.0 - cosine).powi(5));
             
             Some(f + f1)
         } else {
@@ -65,8 +77,33 @@ impl FresnelData {
     }
 }
 
+#[cfg(feature = "serde-serialize")]
+#[derive(Serialize, Deserialize)]
+struct FresnelParameters {
+    n: FresnelIndex,
+    k: FresnelIndex
+}
+
+#[cfg(feature = "serde-serialize")]
+impl From<FresnelParameters> for FresnelData {
+    fn from(parameters: FresnelParameters) -> Self {
+        FresnelData::new(parameters.n, parameters.k)
+    }
+}
+
+#[cfg(feature = "serde-serialize")]
+impl From<FresnelData> for FresnelParameters {
+    fn from(fresnel: FresnelData) -> Self {
+        Self {
+            n: fresnel.n,
+            k: fresnel.n_imaginary
+        }
+    }
+}
+
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
 pub struct Material {
     ambient: Option<Color>,
     diffuse: Option<Color>,
@@ -74,6 +111,9 @@ pub struct Material {
     fresnel: Option<FresnelData>,
     reflective: bool,
     refractive: bool,
+    /// Radiance leaving the outer side of the surface
+    #[cfg_attr(feature = "serde-serialize", serde(default))]
+    emission: Option<Color>,
 }
 
 impl Material {
@@ -83,7 +123,8 @@ impl Material {
                specular: None,
                fresnel: None,
                reflective: false,
-               refractive: false
+               refractive: false,
+               emission: None
         }
     }
 
@@ -93,7 +134,8 @@ impl Material {
                specular: None,
                fresnel: None,
                reflective: false,
-               refractive: false}
+               refractive: false,
+               emission: None}
     }
 
     pub fn new_shiny(diffuse: Color, specular: (Color, FloatType), ambient: Option<Color>) -> Self {
@@ -102,7 +144,8 @@ impl Material {
                specular: Some(specular),
                fresnel: None,
                reflective: false,
-               refractive: false}
+               refractive: false,
+               emission: None}
     }
 
     pub fn new_reflective(fresnel_real: FresnelIndex, fresnel_imagninary: FresnelIndex, diffuse: Option<Color>, specular: Option<(Color, FloatType)>, ambient: Option<Color>) -> Self {
@@ -111,7 +154,8 @@ impl Material {
                specular: specular,
                fresnel: Some(FresnelData::new(fresnel_real, fresnel_imagninary)),
                reflective: true,
-               refractive: false}
+               refractive: false,
+               emission: None}
     }
 
     pub fn new_refractive(fresnel_real: FresnelIndex, fresnel_imagninary: FresnelIndex, diffuse: Option<Color>, specular: Option<(Color, FloatType)>, ambient: Option<Color>) -> Self {
@@ -120,7 +164,8 @@ impl Material {
                specular: specular,
                fresnel: Some(FresnelData::new(fresnel_real, fresnel_imagninary)),
                reflective: false,
-               refractive: true}
+               refractive: true,
+               emission: None}
     }
 
     pub fn new_reflective_and_refractive(fresnel_real: FresnelIndex, fresnel_imagninary: FresnelIndex, diffuse: Option<Color>, specular: Option<(Color, FloatType)>, ambient: Option<Color>) -> Self {
@@ -129,7 +174,8 @@ impl Material {
                specular: specular,
                fresnel: Some(FresnelData::new(fresnel_real, fresnel_imagninary)),
                reflective: true,
-               refractive: true}
+               refractive: true,
+               emission: None}
     }
 
     pub fn new_light_source(diffuse: Color, ambient: Option<Color>) -> Self {
@@ -139,10 +185,36 @@ impl Material {
             specular: None,
             fresnel: Some(FresnelData::new(FresnelIndex::one(), FresnelIndex::one())),
             reflective: false,
-            refractive: true
+            refractive: true,
+            emission: None
+        }
+    }
+
+    /// Opaque surface emitting the radiance, spheres made of it are picked up as light sources by SimpleIlluminator::new_with_emitters
+    pub fn new_emissive(emission: Color) -> Self {
+        Self { emission: Some(emission),
+               ..Self::new_useless()
         }
     }
 
+    pub fn get_emission(&self) -> Option<&Color> {
+        self.emission.as_ref()
+    }
+
+    pub fn set_emission(&self, emission: Option<Color>) -> Self {
+        Self { emission: emission,
+               ..*self
+        }
+    }
+
+    pub fn set_emission_mut(&mut self, emission: Option<Color>) {
+        self.emission = emission;
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        self.emission.is_some()
+    }
+
     pub fn get_ambient_color(&self) -> Option<&Color> {
         self.ambient.as_ref()
     }
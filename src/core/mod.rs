@@ -10,6 +10,12 @@ pub mod propagation;
 pub mod worldview;
 pub mod execution;
 pub mod scene;
+pub mod statistics;
+pub mod boundingbox;
+pub mod query;
+pub mod sampling;
+pub mod packet;
+pub mod error;
 
 pub use self::model::*;
 pub use self::ray::*;
@@ -23,4 +29,10 @@ pub use self::view::*;
 pub use self::propagation::*;
 pub use self::worldview::*;
 pub use self::execution::*;
-pub use self::scene::*;
\ No newline at end of file
+pub use self::scene::*;
+pub use self::statistics::*;
+pub use self::boundingbox::*;
+pub use self::query::*;
+pub use self::sampling::*;
+pub use self::packet::*;
+pub use self::error::*;
\ No newline at end of file
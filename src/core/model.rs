@@ -1,77 +1,178 @@
-use defs::{Matrix4, Vector3, FloatType};
-use core::{Ray, RayIntersection};
+use defs::{Matrix3, Matrix4, Vector3, FloatType};
+use core::{Ray, RayIntersection, BoundingBox, LightSource, RayPacket, RenderStatistics};
 use na::{Similarity3, Rotation3, Translation3, Unit};
+use tools::{CompareWithTolerance, get_rounding_error_bound};
+use std::sync::{Arc};
 
 pub trait Model: Send + Sync {
     fn get_intersection(&self, ray: &Ray) -> Option<RayIntersection>;
+
+    fn get_bounding_box(&self) -> Option<BoundingBox> { //None for unbounded models
+        None
+    }
+
+    /// Light source sampling the emissive surface of the model, None for models that do not emit
+    fn get_light_source(&self) -> Option<Box<LightSource>> {
+        None
+    }
+
+    /// Intersection of every active lane of the packet in lane order, models override it to intersect the lanes together
+    fn get_packet_intersections(&self, packet: &RayPacket) -> Vec<Option<RayIntersection>> {
+        packet.iter().map(|ray| self.get_intersection(ray)).collect()
+    }
+}
+
+#[derive(Debug)]
+pub enum ModelError {
+    UninvertibleTransformation
+}
+
+/// Model View matrix at the end of the motion, the matrices are interpolated linearly by ray time in between
+struct TransformMotion {
+    end_tf_matrix: Matrix4,
+    end_inverse_tf_matrix: Matrix4,
+    time_begin: FloatType,
+    time_end: FloatType
+}
+
+/// Inverse of an affine Model View matrix from the adjugate of its linear part, cheaper than inverting a general matrix.
+/// None when the determinant is within the rounding error of the cube of the longest row, so uniformly small scales are still invertible
+pub fn get_affine_inverse(matrix: &Matrix4) -> Option<Matrix4> {
+    let get_row = |index: usize| Vector3::new(matrix[(index, 0)], matrix[(index, 1)], matrix[(index, 2)]);
+    let (row_0, row_1, row_2) = (get_row(0), get_row(1), get_row(2));
+    let adjugate_column_0 = row_1.cross(&row_2);
+    let determinant = row_0.dot(&adjugate_column_0);
+    let longest_row = row_0.norm().max(row_1.norm()).max(row_2.norm());
+    if determinant.near_zero_eps() || determinant.abs() <= get_rounding_error_bound(16) * longest_row.powi(3) {
+        return None;
+    }
+
+    let linear_inverse = Matrix3::from_columns(&[adjugate_column_0, row_2.cross(&row_0), row_0.cross(&row_1)]) / determinant;
+    let translation_inverse = -(linear_inverse * Vector3::new(matrix[(0, 3)], matrix[(1, 3)], matrix[(2, 3)]));
+    let mut result = linear_inverse.to_homogeneous();
+    for index in 0..3 {
+        result[(index, 3)] = translation_inverse[index];
+    }
+    Some(result)
 }
 
 pub struct ModelViewModelWrapper<T: Model> {
     wrapped_model: T,
     tf_matrix: Matrix4,
-    inverse_tf_matrix: Matrix4
+    inverse_tf_matrix: Matrix4,
+    motion: Option<TransformMotion>
 }
 
 impl<T: Model> ModelViewModelWrapper<T> {
-    pub fn new(model: T, model_view_matrix: Matrix4) -> Self {
-        Self {  wrapped_model: model,
-                inverse_tf_matrix: model_view_matrix.try_inverse().expect("Uninvertable Model View Matrix"),
-                tf_matrix: model_view_matrix
-        }
+    pub fn new(model: T, model_view_matrix: Matrix4) -> Result<Self, ModelError> {
+        Ok(Self {  wrapped_model: model,
+                   inverse_tf_matrix: get_affine_inverse(&model_view_matrix).ok_or(ModelError::UninvertibleTransformation)?,
+                   tf_matrix: model_view_matrix,
+                   motion: None
+        })
     }
 
     pub fn new_identity(model: T) -> Self {
         Self {  wrapped_model: model,
                 inverse_tf_matrix: Matrix4::identity(),
-                tf_matrix: Matrix4::identity()
+                tf_matrix: Matrix4::identity(),
+                motion: None
+        }
+    }
+
+    pub fn new_motion(model: T, begin_model_view_matrix: Matrix4, end_model_view_matrix: Matrix4, time_begin: FloatType, time_end: FloatType) -> Result<Self, ModelError> {
+        let mut result = Self::new(model, begin_model_view_matrix)?;
+        result.set_motion(end_model_view_matrix, time_begin, time_end)?;
+        Ok(result)
+    }
+
+    pub fn set_motion(&mut self, end_model_view_matrix: Matrix4, time_begin: FloatType, time_end: FloatType) -> Result<(), ModelError> {
+        self.motion = Some(TransformMotion {
+            end_inverse_tf_matrix: get_affine_inverse(&end_model_view_matrix).ok_or(ModelError::UninvertibleTransformation)?,
+            end_tf_matrix: end_model_view_matrix,
+            time_begin: time_begin,
+            time_end: time_end
+        });
+        Ok(())
+    }
+
+    pub fn clear_motion(&mut self) {
+        self.motion = None;
+    }
+
+    /// The inverse is given by the caller, so the cached inverse never has to be computed from a possibly singular matrix
+    fn apply_transformation(&mut self, transformation: Matrix4, inverse_transformation: Matrix4) {
+        self.tf_matrix = transformation * self.tf_matrix;
+        self.inverse_tf_matrix = self.inverse_tf_matrix * inverse_transformation;
+        if let Some(ref mut motion) = self.motion {
+            motion.end_tf_matrix = transformation * motion.end_tf_matrix;
+            motion.end_inverse_tf_matrix = motion.end_inverse_tf_matrix * inverse_transformation;
         }
     }
 
-    fn recalculate_cached_matrices(&mut self) {
-        self.inverse_tf_matrix = self.tf_matrix.try_inverse().expect("Uninvertable Model View Matrix");
+    /// Rays outside of the motion use the cached inverses of its ends
+    fn get_tf_matrices_at(&self, time: FloatType) -> Option<(Matrix4, Matrix4)> {
+        match self.motion {
+            Some(ref motion) => {
+                let duration = motion.time_end - motion.time_begin;
+                let ratio = if duration > 0.0 { (time - motion.time_begin) / duration } else { 0.0 };
+                if ratio <= 0.0 {
+                    Some((self.tf_matrix, self.inverse_tf_matrix))
+                } else if ratio >= 1.0 {
+                    Some((motion.end_tf_matrix, motion.end_inverse_tf_matrix))
+                } else {
+                    let tf_matrix = self.tf_matrix + (motion.end_tf_matrix - self.tf_matrix) * ratio;
+                    get_affine_inverse(&tf_matrix).map(|inverse_tf_matrix| (tf_matrix, inverse_tf_matrix))
+                }
+            },
+            None => Some((self.tf_matrix, self.inverse_tf_matrix))
+        }
     }
 
     pub fn load_identity(&mut self) {
         self.tf_matrix = Matrix4::identity();
         self.inverse_tf_matrix = Matrix4::identity();
+        self.motion = None;
     }
 
-    pub fn scale_uniform(&mut self, scaling: FloatType) {
+    pub fn scale_uniform(&mut self, scaling: FloatType) -> Result<(), ModelError> {
+        if scaling.near_zero_eps() {
+            return Err(ModelError::UninvertibleTransformation);
+        }
         let similarity = Similarity3::from_scaling(scaling);
+        let inverse_similarity = Similarity3::from_scaling(scaling.recip());
 
-        self.tf_matrix = similarity.to_homogeneous() * self.tf_matrix;
-
-        self.recalculate_cached_matrices(); 
+        self.apply_transformation(similarity.to_homogeneous(), inverse_similarity.to_homogeneous());
+        Ok(())
     }
 
-    pub fn scale_non_uniform(&mut self, scaling: Vector3) {
-        let diagonal = {
+    pub fn scale_non_uniform(&mut self, scaling: Vector3) -> Result<(), ModelError> {
+        if scaling.iter().any(|component| component.near_zero_eps()) {
+            return Err(ModelError::UninvertibleTransformation);
+        }
+        let get_diagonal = |scaling: Vector3| {
             let mut result = scaling.to_homogeneous();
             result[(3, 0)] = 1.0;
             result
         };
 
-        let similarity = Matrix4::from_diagonal(&diagonal);
-        
-        self.tf_matrix = similarity * self.tf_matrix;
+        let similarity = Matrix4::from_diagonal(&get_diagonal(scaling));
+        let inverse_similarity = Matrix4::from_diagonal(&get_diagonal(scaling.map(|component| component.recip())));
 
-        self.recalculate_cached_matrices();
+        self.apply_transformation(similarity, inverse_similarity);
+        Ok(())
     }
 
     pub fn translate(&mut self, translation: Vector3) {
         let translate = Translation3::from_vector(translation);
 
-        self.tf_matrix = translate.to_homogeneous() * self.tf_matrix;
-
-        self.recalculate_cached_matrices();
+        self.apply_transformation(translate.to_homogeneous(), translate.inverse().to_homogeneous());
     }
 
     pub fn rotate(&mut self, axis: Vector3, angle: FloatType) {
         let rotation = Rotation3::from_axis_angle(&Unit::new_normalize(axis), angle);
 
-        self.tf_matrix = rotation.to_homogeneous() * self.tf_matrix;
-
-        self.recalculate_cached_matrices();
+        self.apply_transformation(rotation.to_homogeneous(), rotation.inverse().to_homogeneous());
     }
 
     #[cfg(test)]
@@ -82,19 +183,64 @@ impl<T: Model> ModelViewModelWrapper<T> {
 
 impl<T: Model> Model for ModelViewModelWrapper<T> {
     fn get_intersection(&self, ray: & Ray) -> Option<RayIntersection<>> {
-        let transformed_ray = ray.get_transformed(&self.inverse_tf_matrix);
-
-        match self.wrapped_model.get_intersection(&transformed_ray) {
-            None => None,
-            Some(transformed_intersection) => transformed_intersection.get_transformed(&self.tf_matrix).ok()
+        match self.get_tf_matrices_at(ray.get_time()) {
+            Some((tf_matrix, inverse_tf_matrix)) => {
+                let transformed_ray = ray.get_transformed(&inverse_tf_matrix);
+
+                match self.wrapped_model.get_intersection(&transformed_ray) {
+                    None => None,
+                    Some(transformed_intersection) => transformed_intersection.get_transformed(&tf_matrix).ok()
+                }
+            },
+            None => None
         }
     }
+
+    fn get_bounding_box(&self) -> Option<BoundingBox> {
+        self.wrapped_model.get_bounding_box().map(|bounding_box| {
+            let begin_bounding_box = bounding_box.get_transformed(&self.tf_matrix);
+            match self.motion {
+                Some(ref motion) => begin_bounding_box.get_union(&bounding_box.get_transformed(&motion.end_tf_matrix)),
+                None => begin_bounding_box
+            }
+        })
+    }
 }
 
 
+/// Result of an any-hit query along a ray
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Occlusion {
+    Unoccluded,
+    Opaque,
+    /// Only transparent models were hit, their ordered intersections are needed for the color of the light
+    Transparent
+}
+
 pub trait Intersector: Send + Sync {    
     fn get_intersections_reverse_ordered(&self, ray: &Ray) -> Vec<RayIntersection>;
     fn get_nearest_intersection(&self, ray: &Ray) -> Option<RayIntersection>;
+
+    /// Nearest intersection of every active lane of the packet in lane order
+    fn get_nearest_intersections_packet(&self, packet: &RayPacket) -> Vec<Option<RayIntersection>> {
+        packet.iter().map(|ray| self.get_nearest_intersection(ray)).collect()
+    }
+
+    /// Whether anything intersects the ray closer than max_distance, implementations should return as soon as an opaque model is hit
+    fn get_occlusion(&self, ray: &Ray, max_distance: FloatType) -> Occlusion {
+        self.get_intersections_reverse_ordered(ray).iter().rev()
+            .take_while(|intersection| intersection.get_distance_to_intersection().less_eps(&max_distance))
+            .fold(Occlusion::Unoccluded, |acc, intersection| {
+                if acc == Occlusion::Opaque || intersection.get_material().is_opaque() {
+                    Occlusion::Opaque
+                } else {
+                    Occlusion::Transparent
+                }
+            })
+    }
+
+    /// Statistics to count the model intersection tests in
+    fn set_statistics(&mut self, _statistics: Option<Arc<RenderStatistics>>) {}
 }
 
 
@@ -102,8 +248,7 @@ pub trait Intersector: Send + Sync {
 mod tests {
     use super::*;
     use core::{Material};
-    use defs::{Point3};
-    use std;
+    use defs::{Point3, float_consts, TEST_TOLERANCE};
 
     struct DummyModel {
 
@@ -126,7 +271,7 @@ mod tests {
     #[test]
     fn mvo_wrapper_scale_uniform() {
         let mut wrapper = ModelViewModelWrapper::new_identity(DummyModel::new());
-        wrapper.scale_uniform(2.0);
+        wrapper.scale_uniform(2.0).unwrap();
 
         let expected = Matrix4::new(2.0,    0.0,    0.0,    0.0,
                                     0.0,    2.0,    0.0,    0.0,
@@ -139,7 +284,7 @@ mod tests {
     #[test]
     fn mvo_wrapper_scale_non_uniform() {
         let mut wrapper = ModelViewModelWrapper::new_identity(DummyModel::new());
-        wrapper.scale_non_uniform(Vector3::new(2.0, 3.0, 4.0));
+        wrapper.scale_non_uniform(Vector3::new(2.0, 3.0, 4.0)).unwrap();
 
         let expected = Matrix4::new(2.0,    0.0,    0.0,    0.0,
                                     0.0,    3.0,    0.0,    0.0,
@@ -166,7 +311,7 @@ mod tests {
     fn mvo_wrapper_scale_translate() {
         let mut wrapper = ModelViewModelWrapper::new_identity(DummyModel::new());
 
-        wrapper.scale_non_uniform(Vector3::new(2.0, 2.0, 2.0));
+        wrapper.scale_non_uniform(Vector3::new(2.0, 2.0, 2.0)).unwrap();
         wrapper.translate(Vector3::new(1.0, 1.0, 1.0));
 
         let expected = Matrix4::new(2.0,    0.0,    0.0,    1.0,
@@ -212,7 +357,7 @@ mod tests {
     fn mvo_wrapper_complete_rotate_translate() {
         let mut test_model = ModelViewModelWrapper::new_identity(ModelMock::new(Point3::new(0.0, 0.0, 1.0), Vector3::new(1.0, -1.0, 1.0)));
         test_model.translate(Vector3::new(0.0, 0.0, 1.0));
-        test_model.rotate(Vector3::new(0.0, 0.0, 1.0), std::f64::consts::FRAC_PI_2);
+        test_model.rotate(Vector3::new(0.0, 0.0, 1.0), float_consts::FRAC_PI_2);
 
         let intersector_ray = Ray::new_single_shot(Point3::new(0.0, -5.0, 2.0), Vector3::new(0.0, 1.0, 0.0));
         let transformed_intersection = test_model.get_intersection(&intersector_ray).expect("There was no intersection returned when ModelMock always returns");
@@ -222,10 +367,59 @@ mod tests {
         assert_relative_eq!(transformed_intersection.get_distance_to_intersection(), &5.0);
     }
 
+    #[test]
+    fn mvo_wrapper_rejects_uninvertible_transformations() {
+        let mut wrapper = ModelViewModelWrapper::new_identity(DummyModel::new());
+
+        assert!(ModelViewModelWrapper::new(DummyModel::new(), Matrix4::zeros()).is_err());
+        assert!(wrapper.scale_uniform(0.0).is_err());
+        assert!(wrapper.scale_non_uniform(Vector3::new(1.0, 0.0, 1.0)).is_err());
+        assert_relative_eq!(wrapper.get_tf_matrix(), &Matrix4::identity());
+    }
+
+    #[test]
+    fn mvo_wrapper_motion_interpolates_by_ray_time() {
+        let mut end_matrix = Matrix4::identity();
+        end_matrix[(1, 3)] = 2.0;
+        let test_model = ModelViewModelWrapper::new_motion(ModelMock::new(Point3::new(0.0, 0.0, 1.0), Vector3::new(0.0, -1.0, 0.0)),
+                                                           Matrix4::identity(), end_matrix, 0.0, 1.0).unwrap();
+
+        let intersector_ray = Ray::new_single_shot(Point3::new(0.0, -5.0, 2.0), Vector3::new(0.0, 1.0, 0.0));
+        let begin_intersection = test_model.get_intersection(&intersector_ray).expect("There was no intersection returned when ModelMock always returns");
+        let middle_intersection = test_model.get_intersection(&intersector_ray.set_time(0.5)).expect("There was no intersection returned when ModelMock always returns");
+
+        assert_relative_eq!(begin_intersection.get_intersection_point(), &Point3::new(0.0, 0.0, 1.0));
+        assert_relative_eq!(middle_intersection.get_intersection_point(), &Point3::new(0.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn mvo_wrapper_motion_inverses_match_interpolated_matrices() {
+        let mut test_model = ModelViewModelWrapper::new_identity(DummyModel::new());
+        test_model.scale_non_uniform(Vector3::new(1.0, 2.0, 3.0)).unwrap();
+        test_model.set_motion(Matrix4::new_nonuniform_scaling(&Vector3::new(2.0, 1.0, 0.5)).append_translation(&Vector3::new(0.0, 4.0, 0.0)), 0.0, 1.0).unwrap();
+        test_model.rotate(Vector3::new(1.0, 1.0, 0.0), 0.3);
+        test_model.translate(Vector3::new(1.0, -2.0, 0.5));
+
+        for &time in [-1.0, 0.0, 0.25, 0.5, 1.0, 2.0].iter() {
+            let (tf_matrix, inverse_tf_matrix) = test_model.get_tf_matrices_at(time).unwrap();
+            assert_relative_eq!(inverse_tf_matrix, tf_matrix.try_inverse().unwrap(), epsilon = TEST_TOLERANCE);
+        }
+    }
+
+    #[test]
+    fn affine_inverse_rejects_nearly_singular_scale() {
+        let nearly_flat = Matrix4::new_nonuniform_scaling(&Vector3::new(1.0, 1.0, 1e-20)).append_translation(&Vector3::new(1.0, 2.0, 3.0));
+        let tiny = Matrix4::new_scaling(1e-6).append_translation(&Vector3::new(1.0, 2.0, 3.0));
+
+        assert!(get_affine_inverse(&Matrix4::new_scaling(0.0)).is_none());
+        assert!(get_affine_inverse(&nearly_flat).is_none());
+        assert_relative_eq!(get_affine_inverse(&tiny).unwrap() * tiny, Matrix4::identity(), epsilon = TEST_TOLERANCE);
+    }
+
     #[test]
     fn mvo_wrapper_complete_scale_translate() {
         let mut test_model = ModelViewModelWrapper::new_identity(ModelMock::new(Point3::new(0.0, 0.0, 1.0), Vector3::new(1.0, -1.0, 1.0)));
-        test_model.scale_uniform(2.0);
+        test_model.scale_uniform(2.0).unwrap();
         test_model.translate(Vector3::new(0.0, 1.0, 0.0));        
 
         let intersector_ray = Ray::new_single_shot(Point3::new(0.0, -5.0, 2.0), Vector3::new(0.0, 1.0, 0.0));
@@ -0,0 +1,141 @@
+use defs::{FloatType};
+use core::{Ray, BoundingBox};
+
+/// Lane count of a ray packet, the packet8 feature widens it for eight lane vector units
+#[cfg(not(feature = "packet8"))]
+pub const PACKET_SIZE: usize = 4;
+#[cfg(feature = "packet8")]
+pub const PACKET_SIZE: usize = 8;
+
+/// Up to PACKET_SIZE coherent rays with origins and directions stored per component, so the lanes are intersected together
+pub struct RayPacket<'ray> {
+    rays: [Option<&'ray Ray>; PACKET_SIZE],
+    pub origin_x: [FloatType; PACKET_SIZE],
+    pub origin_y: [FloatType; PACKET_SIZE],
+    pub origin_z: [FloatType; PACKET_SIZE],
+    pub direction_x: [FloatType; PACKET_SIZE],
+    pub direction_y: [FloatType; PACKET_SIZE],
+    pub direction_z: [FloatType; PACKET_SIZE],
+    pub inverse_direction_x: [FloatType; PACKET_SIZE],
+    pub inverse_direction_y: [FloatType; PACKET_SIZE],
+    pub inverse_direction_z: [FloatType; PACKET_SIZE]
+}
+
+impl<'ray> RayPacket<'ray> {
+    /// Rays past PACKET_SIZE are ignored, missing lanes repeat the first ray and stay inactive
+    pub fn new(rays: &'ray [Ray]) -> Self {
+        let mut result = Self {
+            rays: [None; PACKET_SIZE],
+            origin_x: [0.0; PACKET_SIZE],
+            origin_y: [0.0; PACKET_SIZE],
+            origin_z: [0.0; PACKET_SIZE],
+            direction_x: [1.0; PACKET_SIZE],
+            direction_y: [1.0; PACKET_SIZE],
+            direction_z: [1.0; PACKET_SIZE],
+            inverse_direction_x: [1.0; PACKET_SIZE],
+            inverse_direction_y: [1.0; PACKET_SIZE],
+            inverse_direction_z: [1.0; PACKET_SIZE]
+        };
+
+        for lane in 0..PACKET_SIZE {
+            let ray = match rays.get(lane) {
+                Some(ray) => {
+                    result.rays[lane] = Some(ray);
+                    ray
+                },
+                None => match rays.first() {
+                    Some(ray) => ray,
+                    None => break
+                }
+            };
+
+            let (origin, direction) = (ray.get_origin(), ray.get_direction());
+            result.origin_x[lane] = origin.x;
+            result.origin_y[lane] = origin.y;
+            result.origin_z[lane] = origin.z;
+            result.direction_x[lane] = direction.x;
+            result.direction_y[lane] = direction.y;
+            result.direction_z[lane] = direction.z;
+            result.inverse_direction_x[lane] = direction.x.recip();
+            result.inverse_direction_y[lane] = direction.y.recip();
+            result.inverse_direction_z[lane] = direction.z.recip();
+        }
+
+        result
+    }
+
+    /// Count of active lanes
+    pub fn len(&self) -> usize {
+        self.rays.iter().take_while(|ray| ray.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rays[0].is_none()
+    }
+
+    pub fn get_ray(&self, lane: usize) -> Option<&'ray Ray> {
+        self.rays.get(lane).and_then(|ray| *ray)
+    }
+
+    pub fn iter<'packet>(&'packet self) -> Box<Iterator<Item=&'ray Ray> + 'packet> {
+        Box::new(self.rays.iter().filter_map(|ray| *ray))
+    }
+
+    /// Slab test of every lane, a lane hits if it is active and enters the box before its maximum distance
+    pub fn get_box_hits(&self, bounding_box: &BoundingBox, max_distances: &[FloatType; PACKET_SIZE]) -> [bool; PACKET_SIZE] {
+        let (min, max) = (bounding_box.get_min(), bounding_box.get_max());
+        let mut near = [0.0 as FloatType; PACKET_SIZE];
+        let mut far = *max_distances;
+
+        {
+            let mut clip_axis = |origin: &[FloatType; PACKET_SIZE], inverse_direction: &[FloatType; PACKET_SIZE], slab_min: FloatType, slab_max: FloatType| {
+                for lane in 0..PACKET_SIZE {
+                    let t0 = (slab_min - origin[lane]) * inverse_direction[lane];
+                    let t1 = (slab_max - origin[lane]) * inverse_direction[lane];
+                    let (t_near, t_far) = if t0 > t1 { (t1, t0) } else { (t0, t1) };
+                    if !t_near.is_nan() {
+                        near[lane] = near[lane].max(t_near);
+                    }
+                    if !t_far.is_nan() {
+                        far[lane] = far[lane].min(t_far);
+                    }
+                }
+            };
+            clip_axis(&self.origin_x, &self.inverse_direction_x, min.x, max.x);
+            clip_axis(&self.origin_y, &self.inverse_direction_y, min.y, max.y);
+            clip_axis(&self.origin_z, &self.inverse_direction_z, min.z, max.z);
+        }
+
+        let mut result = [false; PACKET_SIZE];
+        for lane in 0..PACKET_SIZE {
+            result[lane] = self.rays[lane].is_some() && near[lane] <= far[lane];
+        }
+        result
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use defs::{Point3, Vector3};
+
+    #[test]
+    fn packet_box_hits_match_single_rays() {
+        let bounding_box = BoundingBox::new(Point3::new(-1.0, -1.0, 4.0), Point3::new(1.0, 1.0, 6.0));
+        let rays = vec![Ray::new(Point3::origin(), Vector3::new(0.0, 0.0, 1.0)),
+                        Ray::new(Point3::origin(), Vector3::new(1.0, 0.0, 1.0)),
+                        Ray::new(Point3::new(0.5, 0.5, 0.0), Vector3::new(0.0, 0.0, -1.0))];
+        let packet = RayPacket::new(&rays);
+
+        assert_eq!(packet.len(), 3);
+        assert!(packet.get_ray(3).is_none());
+        let mut expected_hits = [false; PACKET_SIZE];
+        expected_hits[0] = true;
+        assert_eq!(packet.get_box_hits(&bounding_box, &[100.0; PACKET_SIZE]), expected_hits);
+        assert_eq!(packet.get_box_hits(&bounding_box, &[3.0; PACKET_SIZE]), [false; PACKET_SIZE]);
+        for (lane, ray) in packet.iter().enumerate() {
+            assert_eq!(bounding_box.is_intersected_by(ray), packet.get_box_hits(&bounding_box, &[100.0; PACKET_SIZE])[lane]);
+        }
+    }
+}
@@ -36,12 +36,7 @@ impl<'intersection> RayPropagator<'intersection> {
 
         let mirror_direction = -view + (normal * 2.0);
 
-        Ray::continue_ray_from_intersection(self.intersection, mirror_direction).map_err(|ray_error| {
-            match ray_error {
-                RayError::DepthLimitReached => RayPropagatorError::RayRelated(ray_error),
-                _ => panic!("RayPropagator encountered unhandleable RayError")
-            }
-        })
+        Ray::continue_ray_from_intersection(self.intersection, mirror_direction).map_err(RayPropagatorError::RayRelated)
     }
 
     pub fn get_refracted_ray_custom_index_ratio(&self, refractive_index_ratio: FloatType) -> Result<Ray, RayPropagatorError> {
@@ -54,12 +49,7 @@ impl<'intersection> RayPropagator<'intersection> {
         if rooted.greater_eq_eps(&0.0) {
             let refraction_direction = view * (-refractive_index_ratio.recip()) + normal * (cosa/refractive_index_ratio - rooted.sqrt());
 
-            Ray::continue_ray_from_intersection_into_medium(self.intersection, refraction_direction).map_err(|ray_error| {
-                match ray_error {
-                    RayError::DepthLimitReached => RayPropagatorError::RayRelated(ray_error),
-                    _ => panic!("RayPropagator encountered unhandleable RayError")
-                }
-            })
+            Ray::continue_ray_from_intersection_into_medium(self.intersection, refraction_direction).map_err(RayPropagatorError::RayRelated)
         } else {
             Err(RayPropagatorError::NoRefraction)
         }
@@ -123,12 +113,7 @@ impl<'intersection> RayPropagator<'intersection> {
     }
 
     pub fn get_diffuse_direction_ray(&self, pitch: FloatType, yaw: FloatType) -> Result<Ray, RayPropagatorError> {
-        Ray::continue_ray_from_intersection(self.intersection, self.get_diffuse_direction_vector(pitch, yaw)).map_err(|ray_error| {
-            match ray_error {
-                RayError::DepthLimitReached => RayPropagatorError::RayRelated(ray_error),
-                _ => panic!("RayPropagator encountered unhandleable RayError")
-            }
-        })
+        Ray::continue_ray_from_intersection(self.intersection, self.get_diffuse_direction_vector(pitch, yaw)).map_err(RayPropagatorError::RayRelated)
     }
 }
 
@@ -139,7 +124,7 @@ mod tests {
     use defs::{Point3};
     use core::{Material};
     use na::{Unit};
-    use std::f64::consts::{PI};
+    use defs::float_consts::{PI};
 
     #[test]
     fn diffuse_direction_vector() {
@@ -0,0 +1,286 @@
+use std::collections::{HashMap};
+use std::sync::{Mutex};
+
+use defs::{FloatType, IntType, Point2Int, Point3, Vector3};
+use core::{Color, Ray, RayError, RayIntersection, Material, LightIntersection, View,
+           RayCaster, IlluminationCaster, TraceableWorld, SceneError, LightLinkSet};
+use uuid::{Uuid};
+
+pub type ScenePathMap = HashMap<Uuid, Vec<String>>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
+pub enum RayTraceKind {
+    Camera,
+    Secondary,
+    Model,
+    Shadow
+}
+
+fn point_to_array(point: &Point3) -> [FloatType; 3] {
+    [point.x, point.y, point.z]
+}
+
+fn vector_to_array(vector: &Vector3) -> [FloatType; 3] {
+    [vector.x, vector.y, vector.z]
+}
+
+fn color_to_array(color: &Color) -> [FloatType; 3] {
+    let (r, g, b) = color.get();
+    [r, g, b]
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
+pub struct RayHitRecord {
+    pub point: [FloatType; 3],
+    pub normal: [FloatType; 3],
+    /// Distance travelled along the path from its first ray, so hits of continued rays stay ordered
+    pub distance: FloatType,
+    pub was_inside: bool,
+    pub model_identifier: Option<Uuid>,
+    pub scene_path: Option<Vec<String>>
+}
+
+impl RayHitRecord {
+    pub fn new(intersection: &RayIntersection, scene_paths: Option<&ScenePathMap>) -> Self {
+        let model_identifier = intersection.get_model_identifier().cloned();
+        Self {
+            point: point_to_array(intersection.get_intersection_point()),
+            normal: vector_to_array(intersection.get_normal_vector()),
+            distance: intersection.get_intersector_ray().get_distance_to_origin() + intersection.get_distance_to_intersection(),
+            was_inside: intersection.was_inside(),
+            model_identifier: model_identifier,
+            scene_path: match (model_identifier, scene_paths) {
+                (Some(identifier), Some(paths)) => paths.get(&identifier).cloned(),
+                _ => None
+            }
+        }
+    }
+}
+
+/// A ray spawned while shading a pixel, parent is the index of the ray whose shading spawned it
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
+pub struct RayTraceRecord {
+    pub index: usize,
+    pub parent: Option<usize>,
+    pub kind: RayTraceKind,
+    pub origin: [FloatType; 3],
+    pub direction: [FloatType; 3],
+    pub depth: i32,
+    pub time: FloatType,
+    pub hit: Option<RayHitRecord>,
+    pub color: Option<[FloatType; 3]>
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
+pub struct PixelQueryResult {
+    pub pixel: [IntType; 2],
+    pub color: Option<[FloatType; 3]>,
+    pub intersections: Vec<RayHitRecord>,
+    pub material: Option<Material>,
+    pub rays: Vec<RayTraceRecord>
+}
+
+struct TracingState {
+    pub records: Vec<RayTraceRecord>,
+    pub parents: Vec<usize>
+}
+
+/// RayCaster recording every ray cast through it while the wrapped world shades with it
+pub struct TracingRayCaster<'world, WorldT: 'world> {
+    world: &'world WorldT,
+    scene_paths: Option<&'world ScenePathMap>,
+    state: Mutex<TracingState>
+}
+
+impl<'world, WorldT> TracingRayCaster<'world, WorldT>
+    where WorldT: TraceableWorld + 'world
+{
+    pub fn new(world: &'world WorldT, scene_paths: Option<&'world ScenePathMap>) -> Self {
+        Self {
+            world: world,
+            scene_paths: scene_paths,
+            state: Mutex::new(TracingState {
+                records: Vec::new(),
+                parents: Vec::new()
+            })
+        }
+    }
+
+    fn begin_record(&self, kind: RayTraceKind, ray: &Ray) -> usize {
+        if let Ok(ref mut unlocked_state) = self.state.lock() {
+            let index = unlocked_state.records.len();
+            let parent = unlocked_state.parents.last().cloned();
+            unlocked_state.records.push(RayTraceRecord {
+                index: index,
+                parent: parent,
+                kind: kind,
+                origin: point_to_array(ray.get_origin()),
+                direction: vector_to_array(ray.get_direction()),
+                depth: ray.get_depth_counter(),
+                time: ray.get_time(),
+                hit: None,
+                color: None
+            });
+            unlocked_state.parents.push(index);
+            index
+        } else {
+            panic!("Mutex lock error inside TracingRayCaster");
+        }
+    }
+
+    fn end_record(&self, index: usize, intersection: Option<&RayIntersection>, color: Option<&Color>) {
+        let hit = intersection.map(|intersection| RayHitRecord::new(intersection, self.scene_paths));
+        if let Ok(ref mut unlocked_state) = self.state.lock() {
+            unlocked_state.parents.pop();
+            let record = &mut unlocked_state.records[index];
+            record.hit = hit;
+            record.color = color.map(color_to_array);
+        } else {
+            panic!("Mutex lock error inside TracingRayCaster");
+        }
+    }
+
+    pub fn get_records(self) -> Vec<RayTraceRecord> {
+        self.state.into_inner().expect("Mutex lock error inside TracingRayCaster").records
+    }
+}
+
+impl<'world, WorldT> RayCaster for TracingRayCaster<'world, WorldT>
+    where WorldT: TraceableWorld + 'world
+{
+    fn cast_ray(&self, ray: &Ray) -> Option<Color> {
+        let kind = if ray.get_depth_counter() == 0 { RayTraceKind::Camera } else { RayTraceKind::Secondary };
+        let index = self.begin_record(kind, ray);
+
+        let intersection = self.world.cast_model_ray(ray);
+        let color = match intersection {
+            Some(ref intersection) => self.world.get_color_with(intersection, self, self),
+            None => self.world.get_escaped_ray_color(ray)
+        };
+
+        self.end_record(index, intersection.as_ref(), color.as_ref());
+        color
+    }
+
+    fn cast_colored_light_ray(&self, ray: &Ray, intersection: &RayIntersection) -> Option<Color> {
+        let index = self.begin_record(RayTraceKind::Shadow, ray);
+        let color = self.world.cast_colored_light_ray(ray, intersection);
+        self.end_record(index, None, color.as_ref());
+        color
+    }
+
+    fn cast_linked_light_ray(&self, ray: &Ray, intersection: &RayIntersection, shadow_casters: &LightLinkSet) -> Option<Color> {
+        let index = self.begin_record(RayTraceKind::Shadow, ray);
+        let color = self.world.cast_linked_light_ray(ray, intersection, shadow_casters);
+        self.end_record(index, None, color.as_ref());
+        color
+    }
+
+    fn cast_model_ray(&self, ray: &Ray) -> Option<RayIntersection> {
+        let index = self.begin_record(RayTraceKind::Model, ray);
+        let intersection = self.world.cast_model_ray(ray);
+        self.end_record(index, intersection.as_ref(), None);
+        intersection
+    }
+
+    fn report_ray_error(&self, error: &RayError) {
+        self.world.report_ray_error(error)
+    }
+}
+
+impl<'world, WorldT> IlluminationCaster for TracingRayCaster<'world, WorldT>
+    where WorldT: TraceableWorld + 'world
+{
+    fn get_illumination_at(&self, intersection: &RayIntersection) -> Vec<LightIntersection> {
+        self.world.get_illumination_with(intersection, self)
+    }
+}
+
+/// Shades a pixel of the view while recording every intersection along its ray and every ray spawned
+pub fn query_pixel<WorldT>(world: &WorldT, view: &View, pixel: Point2Int, scene_paths: Option<&ScenePathMap>) -> Result<PixelQueryResult, SceneError>
+    where WorldT: TraceableWorld
+{
+    match view.get_ray_to_screen_coordinate(pixel) {
+        Ok(ray) => {
+            let intersections = world.get_intersections_along(&ray);
+            let tracer = TracingRayCaster::new(world, scene_paths);
+            let color = tracer.cast_ray(&ray);
+
+            Ok(PixelQueryResult {
+                pixel: [pixel.x, pixel.y],
+                color: color.as_ref().map(color_to_array),
+                intersections: intersections.iter().map(|intersection| RayHitRecord::new(intersection, scene_paths)).collect(),
+                material: intersections.first().map(|intersection| *intersection.get_material()),
+                rays: tracer.get_records()
+            })
+        },
+        Err(_) => Err(SceneError::InvalidInputCoord)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use defs::{TEST_TOLERANCE};
+    use core::{World};
+    use basic::{SimpleIntersector, SimpleColorCalculator, SimpleIlluminator};
+    use basic::model::{SolidSphere};
+    use basic::lightsource::{DotLightSource};
+
+    #[test]
+    fn query_pixel_records_ray_tree() {
+        let identifier = Uuid::new_v4();
+        let mut sphere = SolidSphere::new(Material::new_diffuse(Color::one(), None));
+        sphere.set_custom_identifier(identifier);
+        let light = DotLightSource::new_natural(Color::one(), 1.0, Point3::new(0.0, 0.0, -10.0));
+        let world = World::new(SimpleIntersector::new(vec![Box::new(sphere)]),
+                               SimpleColorCalculator::new(),
+                               SimpleIlluminator::new(vec![Box::new(light)]),
+                               3);
+        // The ray of the center pixel goes through the middle of the sphere
+        let view = View::new_unit(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 1.0, 0.0), 1.0, 1.0, 2).unwrap();
+        let mut scene_paths = ScenePathMap::new();
+        scene_paths.insert(identifier, vec!["root".to_string(), "ball".to_string()]);
+
+        let result = query_pixel(&world, &view, Point2Int::new(1, 1), Some(&scene_paths)).expect("Pixel should be on screen");
+
+        assert_eq!(result.intersections.len(), 2);
+        assert_relative_eq!(result.intersections[0].distance, 4.0, epsilon = TEST_TOLERANCE);
+        assert_relative_eq!(result.intersections[1].distance, 6.0, epsilon = TEST_TOLERANCE);
+        assert!(!result.intersections[0].was_inside && result.intersections[1].was_inside);
+        assert!(result.intersections.iter().all(|hit| hit.model_identifier == Some(identifier)));
+        assert!(result.material.is_some());
+        assert_eq!(result.rays[0].kind, RayTraceKind::Camera);
+        assert_eq!(result.rays[0].parent, None);
+        let camera_hit = result.rays[0].hit.as_ref().expect("Camera ray should hit the sphere");
+        assert_eq!(camera_hit.model_identifier, Some(identifier));
+        assert_eq!(camera_hit.scene_path, Some(vec!["root".to_string(), "ball".to_string()]));
+        assert!(result.rays.iter().any(|record| record.kind == RayTraceKind::Shadow && record.parent == Some(0)));
+        assert!(query_pixel(&world, &view, Point2Int::new(5, 5), None).is_err());
+    }
+
+    #[cfg(feature = "serde-serialize")]
+    #[test]
+    fn query_pixel_result_serializes() {
+        use serde_json;
+
+        let world = World::new(SimpleIntersector::new(vec![Box::new(SolidSphere::new(Material::new_diffuse(Color::one(), None)))]),
+                               SimpleColorCalculator::new(),
+                               SimpleIlluminator::new(vec![Box::new(DotLightSource::new_natural(Color::one(), 1.0, Point3::new(0.0, 0.0, -10.0)))]),
+                               3);
+        let view = View::new_unit(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 1.0, 0.0), 1.0, 1.0, 2).unwrap();
+        let result = query_pixel(&world, &view, Point2Int::new(1, 1), None).unwrap();
+
+        let json = serde_json::to_string(&result).expect("Query result should serialize");
+        let restored: PixelQueryResult = serde_json::from_str(&json).expect("Query result should deserialize");
+
+        assert_eq!(restored.rays.len(), result.rays.len());
+        assert_eq!(restored.intersections.len(), 2);
+        assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+    }
+}
@@ -64,6 +64,7 @@ pub struct Ray {
     origin : Point3,
     state : RayState,
     mediums : Option<VecDeque<Material>>, 
+    time : FloatType,
 }
 
 impl Ray {
@@ -73,7 +74,8 @@ impl Ray {
                   mediums: None,
                   state: RayState { distance_to_origin: 0.0,
                                     depth_counter: 0,
-                                    depth_limit: None }
+                                    depth_limit: None },
+                  time: 0.0
         }
     }
 
@@ -83,7 +85,8 @@ impl Ray {
                   mediums: None,
                   state: RayState { distance_to_origin: 0.0,
                                     depth_counter: 0,
-                                    depth_limit: Some(depth_limit) }
+                                    depth_limit: Some(depth_limit) },
+                  time: 0.0
         }
     }
 
@@ -91,6 +94,13 @@ impl Ray {
         Self::new_depth_limited(origin, dir, 1)
     }
 
+    /// Single shot ray testing the visibility of the intersection, taken at the time of the ray which found it
+    pub fn new_shadow(origin: Point3, dir: Vector3, intersection: &RayIntersection) -> Self {
+        let mut result = Self::new_single_shot(origin, dir);
+        result.time = intersection.get_intersector_ray().get_time();
+        result
+    }
+
     pub fn set_maximum_depth_limited(&self, maximum_depth_limit: u32) -> Self {
         Self {
             state: self.state.get_maximum_depth_limited(maximum_depth_limit),
@@ -102,6 +112,17 @@ impl Ray {
         self.state = self.state.get_maximum_depth_limited(maximum_depth_limit);
     }
 
+    pub fn set_time(&self, time: FloatType) -> Self {
+        Self {
+            time: time,
+            ..self.clone()
+        }
+    }
+
+    pub fn set_time_mut(&mut self, time: FloatType) {
+        self.time = time;
+    }
+
     fn push_medium(&mut self, material: Material) {
         if let Some(ref mut mediums) = self.mediums {
             mediums.push_back(material);
@@ -122,15 +143,26 @@ impl Ray {
         }
     }
 
+    /// Intersection point pushed along the geometric normal past its rounding error, to the side the direction leaves to, so the continued ray cannot hit the same surface again
+    pub fn get_offset_origin(intersection: &RayIntersection, direction: &Vector3) -> Point3 {
+        let normal = intersection.get_normal_vector();
+        let error = intersection.get_intersection_error();
+        let offset_distance = normal.x.abs() * error.x + normal.y.abs() * error.y + normal.z.abs() * error.z;
+        let offset = if direction.dot(normal) < 0.0 { -normal * offset_distance } else { normal * offset_distance };
+
+        intersection.get_intersection_point() + offset
+    }
+
     pub fn continue_ray_from_intersection_into_medium(intersection: &RayIntersection, direction: Vector3) -> Result<Self, RayError> {
         let original_ray = intersection.get_intersector_ray();
 
         match RayState::get_continuation(original_ray.get_state(), intersection.get_distance_to_intersection()) {
             Ok (continued_state) => {
                 let mut result = Self {  direction: Unit::new_normalize(direction),
-                                         origin: *intersection.get_intersection_point(),
+                                         origin: Self::get_offset_origin(intersection, &direction),
                                          mediums: original_ray.mediums.clone(),
-                                         state: continued_state};
+                                         state: continued_state,
+                                         time: original_ray.time};
                 if intersection.was_inside() {
                     result.pop_medium();
                 } else {
@@ -147,7 +179,7 @@ impl Ray {
         match RayState::get_continuation(intersection.get_intersector_ray().get_state(), intersection.get_distance_to_intersection()) {
             Ok (continued_state) => {
                 Ok (Self {  direction: Unit::new_normalize(direction),
-                            origin: *intersection.get_intersection_point(),
+                            origin: Self::get_offset_origin(intersection, &direction),
                             state: continued_state,
                             ..intersection.get_intersector_ray().clone()})
             },
@@ -204,6 +236,10 @@ impl Ray {
         self.state.get_depth_counter()
     }
 
+    pub fn get_time(&self) -> FloatType {
+        self.time
+    }
+
     pub fn get_medium(&self) -> Option<Material> {
         if let Some(ref mediums) = self.mediums {
             if let Some(last_item) = mediums.back() {
@@ -221,7 +257,9 @@ impl Ray {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use core::{RayIntersection, Material};
+    use core::{RayIntersection, Material, FresnelIndex, Color, Model};
+    use basic::model::{SolidSphere, SolidPlane};
+    use tools::{get_rounding_error_bound};
 
     #[test]
     fn test_ray_new1_assignment() {
@@ -245,6 +283,74 @@ mod tests {
         assert_eq!(continued_ray.get_distance_to_origin(), 1.0);
     }
 
+    #[test]
+    fn test_continued_rays_keep_time() {
+        let initial_ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0)).set_time(0.25);
+        let intersection = RayIntersection::new(Vector3::new(-1.0, 0.0, 0.0),
+                                                Point3::new(1.0, 0.0, 0.0),
+                                                &initial_ray,
+                                                Material::new_useless(),
+                                                false).unwrap();
+        let continued_ray = Ray::continue_ray_from_intersection(&intersection, Vector3::new(0.0, 1.0, 0.0)).unwrap();
+        let medium_ray = Ray::continue_ray_from_intersection_into_medium(&intersection, Vector3::new(1.0, 0.0, 0.0)).unwrap();
+
+        assert_eq!(continued_ray.get_time(), 0.25);
+        assert_eq!(medium_ray.get_time(), 0.25);
+    }
+
+    /// Unit sized geometry far from the origin is where shadow acne shows, tiny geometry near it is where contact shadows get lost
+    #[cfg(not(feature = "f32"))]
+    const SCENE_SCALES: [(FloatType, FloatType); 5] = [(0.0, 1e-6), (0.0, 1.0), (1e3, 1e-3), (1e6, 1.0), (1e7, 1e3)];
+    /// Single precision only resolves the contact gaps of geometry within about ten times its size from the origin
+    #[cfg(feature = "f32")]
+    const SCENE_SCALES: [(FloatType, FloatType); 5] = [(0.0, 1e-6), (0.0, 1.0), (1e1, 1.0), (1e2, 1e1), (1e4, 1e3)];
+
+    /// Relative error of distances measured between surfaces of the given size at the given distance from the origin
+    fn get_scale_error_bound(position_scale: FloatType, size: FloatType, distance: FloatType) -> FloatType {
+        get_rounding_error_bound(64) * (position_scale + size) / distance
+    }
+
+    #[test]
+    fn test_continued_rays_leave_surface_at_all_scales() {
+        let glass = Material::new_refractive(FresnelIndex::new(1.5, 1.5, 1.5), FresnelIndex::zero(), None, None, None);
+        for &(position_scale, size) in SCENE_SCALES.iter() {
+            let center = Point3::new(0.7, -0.3, 0.2) * position_scale;
+            let sphere = SolidSphere::new_positioned(glass, center, size);
+            let initial_ray = Ray::new(center + Vector3::new(0.1, 0.2, 3.0) * size, Vector3::new(0.0, 0.0, -1.0));
+            let intersection = sphere.get_intersection(&initial_ray).expect("Ray should hit the sphere");
+            let normal = *intersection.get_normal_vector();
+
+            for tangent_weight in [0.0, 10.0, 1000.0].iter() {
+                let leaving_direction = normal + normal.cross(&Vector3::new(1.0, 0.0, 0.0)) * *tangent_weight;
+                let leaving_ray = Ray::continue_ray_from_intersection(&intersection, leaving_direction).unwrap();
+                assert!(sphere.get_intersection(&leaving_ray).is_none(), "Self intersection at scale {} {}", position_scale, size);
+            }
+
+            let entering_ray = Ray::continue_ray_from_intersection_into_medium(&intersection, -normal).unwrap();
+            let far_side = sphere.get_intersection(&entering_ray).expect("Ray inside should hit the far side");
+            assert!(far_side.was_inside());
+            assert_relative_eq!(far_side.get_distance_to_intersection(), 2.0 * size, max_relative = get_scale_error_bound(position_scale, size, size));
+        }
+    }
+
+    #[test]
+    fn test_continued_rays_keep_contact_shadows_at_all_scales() {
+        let material = Material::new_diffuse(Color::one(), None);
+        for &(position_scale, size) in SCENE_SCALES.iter() {
+            let base = Point3::new(0.7, -0.3, 0.2) * position_scale;
+            let gap = size * 1e-4;
+            let floor = SolidPlane::new_positioned(material, base, Vector3::z_axis());
+            let blocker = SolidPlane::new_positioned(material, base + Vector3::new(0.0, 0.0, gap), -Vector3::z_axis());
+
+            let initial_ray = Ray::new(base + Vector3::new(0.0, 0.0, -size), Vector3::new(0.0, 0.0, 1.0));
+            let floor_intersection = floor.get_intersection(&initial_ray).expect("Ray should hit the floor");
+            let shadow_ray = Ray::continue_ray_from_intersection(&floor_intersection, Vector3::new(0.3, 0.0, 1.0)).unwrap();
+
+            let blocker_intersection = blocker.get_intersection(&shadow_ray).expect("Contact shadow should not be missed");
+            assert_relative_eq!(blocker_intersection.get_distance_to_intersection(), gap * (1.09 as FloatType).sqrt(), max_relative = get_scale_error_bound(position_scale, size, gap));
+        }
+    }
+
     #[test]
     fn test_depth_limits() {
         let initial_ray = Ray::new_depth_limited(Point3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), 1);
@@ -0,0 +1,61 @@
+use defs::{FloatType, Vector3, float_consts};
+
+/// Two unit vectors perpendicular to each other and to the unit input vector
+pub fn get_orthonormal_basis(axis: &Vector3) -> (Vector3, Vector3) {
+    let helper = if axis.x.abs() < 0.9 { Vector3::new(1.0, 0.0, 0.0) } else { Vector3::new(0.0, 1.0, 0.0) };
+    let tangent = axis.cross(&helper).normalize();
+    let bitangent = axis.cross(&tangent);
+    (tangent, bitangent)
+}
+
+fn get_direction_around_axis(axis: &Vector3, cos_theta: FloatType, phi: FloatType) -> Vector3 {
+    let (tangent, bitangent) = get_orthonormal_basis(axis);
+    let sin_theta = (1.0 - cos_theta.powi(2)).max(0.0).sqrt();
+    tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + axis * cos_theta
+}
+
+/// Direction drawn uniformly over the sphere from two uniform random numbers in [0, 1), its pdf is 1 / 4pi
+pub fn get_uniform_sphere_direction(u1: FloatType, u2: FloatType) -> Vector3 {
+    get_direction_around_axis(&Vector3::new(0.0, 0.0, 1.0), 1.0 - 2.0 * u1, 2.0 * float_consts::PI * u2)
+}
+
+/// Direction drawn uniformly inside the cone around the unit axis, its pdf is get_uniform_cone_pdf
+pub fn get_uniform_cone_direction(axis: &Vector3, cos_max_angle: FloatType, u1: FloatType, u2: FloatType) -> Vector3 {
+    get_direction_around_axis(axis, 1.0 - u1 * (1.0 - cos_max_angle), 2.0 * float_consts::PI * u2)
+}
+
+pub fn get_uniform_cone_pdf(cos_max_angle: FloatType) -> FloatType {
+    (2.0 * float_consts::PI * (1.0 - cos_max_angle)).recip()
+}
+
+/// Direction drawn over the hemisphere around the unit normal with density cos / pi
+pub fn get_cosine_weighted_direction(normal: &Vector3, u1: FloatType, u2: FloatType) -> Vector3 {
+    get_direction_around_axis(normal, (1.0 - u1).sqrt(), 2.0 * float_consts::PI * u2)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use defs::{TEST_TOLERANCE};
+
+    #[test]
+    fn sampled_directions_are_unit_and_inside_domain() {
+        let axis = Vector3::new(1.0, 2.0, -1.0).normalize();
+        let cos_max_angle = 0.8;
+
+        for &(u1, u2) in [(0.0, 0.0), (0.3, 0.7), (0.999, 0.5), (0.5, 0.999)].iter() {
+            let sphere_direction = get_uniform_sphere_direction(u1, u2);
+            let cone_direction = get_uniform_cone_direction(&axis, cos_max_angle, u1, u2);
+            let hemisphere_direction = get_cosine_weighted_direction(&axis, u1, u2);
+
+            assert_relative_eq!(sphere_direction.norm(), 1.0, epsilon = TEST_TOLERANCE);
+            assert!(cone_direction.dot(&axis) >= cos_max_angle - TEST_TOLERANCE);
+            assert!(hemisphere_direction.dot(&axis) >= 0.0);
+        }
+
+        let (tangent, bitangent) = get_orthonormal_basis(&axis);
+        assert_relative_eq!(tangent.dot(&axis), 0.0, epsilon = TEST_TOLERANCE);
+        assert_relative_eq!(bitangent.dot(&tangent), 0.0, epsilon = TEST_TOLERANCE);
+    }
+}
@@ -15,6 +15,11 @@ pub trait Scene: Send + Sync {
     fn get_view(&self) -> &View;
     fn get_ray_caster(&self) -> &RayCaster;
     fn get_illumination_caster(&self) -> &IlluminationCaster;
+
+    /// Colors of neighbouring pixels, implementations may trace their camera rays together
+    fn get_pixel_colors(&self, pixels: &[Point2Int]) -> Vec<Result<Color, SceneError>> {
+        pixels.iter().map(|pixel| self.get_pixel_color(*pixel)).collect()
+    }
 }
 
 #[derive(Debug)]
@@ -158,14 +163,10 @@ impl BasicSceneBuffer {
     }
 
     fn map_pixel_to_buffer(&self, pixel: Point2Int) -> Option<usize> {
-        if let Ok(result) = self.screen.get_pixel_index_by_screen_coord(&pixel) {
-            if result < 0 {
-                panic!("Negative value as screen pixel index");
-            }
-            Some(result as usize)
-        } else {
-            None
-        } 
+        match self.screen.get_pixel_index_by_screen_coord(&pixel) {
+            Ok(result) if result >= 0 => Some(result as usize),
+            _ => None
+        }
     }
 
     pub fn with_buffer(screen: Screen, input_buffer: Vec<Option<Color>>) -> Result<Self, BasicSceneBufferError> {
@@ -0,0 +1,260 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::fmt;
+
+use defs::{Point2Int};
+use core::{Ray, RayError, RenderingTask, RenderingTaskProducer, ThreadSafeIterator};
+
+
+#[derive(Debug, Clone, Copy)]
+pub struct TaskTimingSummary {
+    pub task_count: usize,
+    pub total: Duration,
+    pub minimum: Option<Duration>,
+    pub maximum: Option<Duration>
+}
+
+impl TaskTimingSummary {
+    pub fn new() -> Self {
+        Self {
+            task_count: 0,
+            total: Duration::new(0, 0),
+            minimum: None,
+            maximum: None
+        }
+    }
+
+    fn add(&mut self, duration: Duration) {
+        self.task_count += 1;
+        self.total += duration;
+        self.minimum = Some(self.minimum.map_or(duration, |minimum| minimum.min(duration)));
+        self.maximum = Some(self.maximum.map_or(duration, |maximum| maximum.max(duration)));
+    }
+
+    pub fn get_average(&self) -> Option<Duration> {
+        if self.task_count > 0 {
+            Some(self.total / self.task_count as u32)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RenderStatisticsSummary {
+    pub primary_rays: usize,
+    pub secondary_rays: usize,
+    pub shadow_rays: usize,
+    pub model_rays: usize,
+    pub model_tests: usize,
+    pub depth_limit_reached: usize,
+    pub maximum_depth: usize,
+    pub task_timing: TaskTimingSummary
+}
+
+impl fmt::Display for RenderStatisticsSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Render statistics")?;
+        writeln!(f, "  Primary rays:          {}", self.primary_rays)?;
+        writeln!(f, "  Secondary rays:        {}", self.secondary_rays)?;
+        writeln!(f, "  Shadow rays:           {}", self.shadow_rays)?;
+        writeln!(f, "  Model rays:            {}", self.model_rays)?;
+        writeln!(f, "  Model tests:           {}", self.model_tests)?;
+        writeln!(f, "  Depth limit reached:   {}", self.depth_limit_reached)?;
+        writeln!(f, "  Maximum depth:         {}", self.maximum_depth)?;
+        writeln!(f, "  Tasks executed:        {}", self.task_timing.task_count)?;
+        writeln!(f, "  Total task time:       {:?}", self.task_timing.total)?;
+        if let (Some(minimum), Some(maximum), Some(average)) = (self.task_timing.minimum, self.task_timing.maximum, self.task_timing.get_average()) {
+            writeln!(f, "  Task time min/avg/max: {:?} / {:?} / {:?}", minimum, average, maximum)?;
+        }
+        Ok(())
+    }
+}
+
+/// Thread safe counters which can optionally be attached to a World and an Intersector
+pub struct RenderStatistics {
+    primary_rays: AtomicUsize,
+    secondary_rays: AtomicUsize,
+    shadow_rays: AtomicUsize,
+    model_rays: AtomicUsize,
+    model_tests: AtomicUsize,
+    depth_limit_reached: AtomicUsize,
+    maximum_depth: AtomicUsize,
+    task_timing: Mutex<TaskTimingSummary>
+}
+
+impl RenderStatistics {
+    pub fn new() -> Self {
+        Self {
+            primary_rays: AtomicUsize::new(0),
+            secondary_rays: AtomicUsize::new(0),
+            shadow_rays: AtomicUsize::new(0),
+            model_rays: AtomicUsize::new(0),
+            model_tests: AtomicUsize::new(0),
+            depth_limit_reached: AtomicUsize::new(0),
+            maximum_depth: AtomicUsize::new(0),
+            task_timing: Mutex::new(TaskTimingSummary::new())
+        }
+    }
+
+    fn record_depth(&self, ray: &Ray) {
+        self.maximum_depth.fetch_max(ray.get_depth_counter().max(0) as usize, Ordering::Relaxed);
+    }
+
+    pub fn record_cast_ray(&self, ray: &Ray) {
+        if ray.get_depth_counter() == 0 {
+            self.primary_rays.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.secondary_rays.fetch_add(1, Ordering::Relaxed);
+        }
+        self.record_depth(ray);
+    }
+
+    pub fn record_model_ray(&self, ray: &Ray) {
+        self.model_rays.fetch_add(1, Ordering::Relaxed);
+        self.record_depth(ray);
+    }
+
+    pub fn record_shadow_ray(&self) {
+        self.shadow_rays.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_model_tests(&self, count: usize) {
+        self.model_tests.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn record_ray_error(&self, error: &RayError) {
+        match *error {
+            RayError::DepthLimitReached => { self.depth_limit_reached.fetch_add(1, Ordering::Relaxed); },
+            _ => ()
+        }
+    }
+
+    pub fn record_task_duration(&self, duration: Duration) {
+        if let Ok(ref mut task_timing) = self.task_timing.lock() {
+            task_timing.add(duration);
+        } else {
+            panic!("Mutex lock error inside RenderStatistics");
+        }
+    }
+
+    pub fn get_summary(&self) -> RenderStatisticsSummary {
+        RenderStatisticsSummary {
+            primary_rays: self.primary_rays.load(Ordering::Relaxed),
+            secondary_rays: self.secondary_rays.load(Ordering::Relaxed),
+            shadow_rays: self.shadow_rays.load(Ordering::Relaxed),
+            model_rays: self.model_rays.load(Ordering::Relaxed),
+            model_tests: self.model_tests.load(Ordering::Relaxed),
+            depth_limit_reached: self.depth_limit_reached.load(Ordering::Relaxed),
+            maximum_depth: self.maximum_depth.load(Ordering::Relaxed),
+            task_timing: if let Ok(task_timing) = self.task_timing.lock() { *task_timing } else { panic!("Mutex lock error inside RenderStatistics") }
+        }
+    }
+}
+
+
+pub struct TimedTaskProducer {
+    producer: Box<RenderingTaskProducer>,
+    statistics: Arc<RenderStatistics>
+}
+
+impl TimedTaskProducer {
+    pub fn new(producer: Box<RenderingTaskProducer>, statistics: Arc<RenderStatistics>) -> Box<RenderingTaskProducer> {
+        Box::new(Self {
+            producer: producer,
+            statistics: statistics
+        })
+    }
+}
+
+impl RenderingTaskProducer for TimedTaskProducer {
+    fn create_task_iterator(self: Box<Self>) -> Box<ThreadSafeIterator<Item=Box<RenderingTask>>> {
+        let statistics = self.statistics;
+        Box::new(TimedTaskIterator {
+            iterator: self.producer.create_task_iterator(),
+            statistics: statistics
+        })
+    }
+
+    fn get_task_count(&self) -> Option<usize> {
+        self.producer.get_task_count()
+    }
+}
+
+struct TimedTaskIterator {
+    iterator: Box<ThreadSafeIterator<Item=Box<RenderingTask>>>,
+    statistics: Arc<RenderStatistics>
+}
+
+impl ThreadSafeIterator for TimedTaskIterator {
+    type Item = Box<RenderingTask>;
+
+    fn next(&self) -> Option<Box<RenderingTask>> {
+        match self.iterator.next() {
+            Some(task) => Some(Box::new(TimedTask {
+                task: task,
+                statistics: Arc::clone(&self.statistics)
+            })),
+            None => None
+        }
+    }
+}
+
+struct TimedTask {
+    task: Box<RenderingTask>,
+    statistics: Arc<RenderStatistics>
+}
+
+impl RenderingTask for TimedTask {
+    fn execute(self: Box<Self>) {
+        let start = Instant::now();
+        self.task.execute();
+        self.statistics.record_task_duration(start.elapsed());
+    }
+
+    fn get_pixel_coord(&self) -> Option<Point2Int> {
+        self.task.get_pixel_coord()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use defs::{Point3, Vector3};
+    use core::{RayIntersection, Material};
+
+    #[test]
+    fn statistics_separates_primary_and_secondary_rays() {
+        let statistics = RenderStatistics::new();
+        let primary_ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0));
+        let intersection = RayIntersection::new(Vector3::new(-1.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0),
+                                                &primary_ray, Material::new_useless(), false).unwrap();
+        let secondary_ray = Ray::continue_ray_from_intersection(&intersection, Vector3::new(-1.0, 0.0, 0.0)).unwrap();
+
+        statistics.record_cast_ray(&primary_ray);
+        statistics.record_cast_ray(&secondary_ray);
+        statistics.record_ray_error(&RayError::DepthLimitReached);
+        statistics.record_ray_error(&RayError::InvalidContinuationDirection);
+
+        let summary = statistics.get_summary();
+        assert_eq!(summary.primary_rays, 1);
+        assert_eq!(summary.secondary_rays, 1);
+        assert_eq!(summary.maximum_depth, 1);
+        assert_eq!(summary.depth_limit_reached, 1);
+    }
+
+    #[test]
+    fn statistics_task_timing() {
+        let statistics = RenderStatistics::new();
+        statistics.record_task_duration(Duration::from_millis(2));
+        statistics.record_task_duration(Duration::from_millis(4));
+
+        let timing = statistics.get_summary().task_timing;
+        assert_eq!(timing.task_count, 2);
+        assert_eq!(timing.minimum, Some(Duration::from_millis(2)));
+        assert_eq!(timing.maximum, Some(Duration::from_millis(4)));
+        assert_eq!(timing.get_average(), Some(Duration::from_millis(3)));
+    }
+}
@@ -2,14 +2,24 @@ use defs::{Point3, Vector3, Matrix3, Point2Int, FloatType, IntType};
 use core::{Ray};
 use tools::{CompareWithTolerance, Vector3Extensions};
 use na::{Unit};
+use rand;
+use rand::{Rng};
+#[cfg(feature = "serde-serialize")]
+use std::convert::{TryFrom};
 
 #[derive(Debug)]
 pub enum ScreenError {
-    PixelOutOfBoundsError
+    PixelOutOfBoundsError,
+    InvalidDimensions,
+    ParallelNormalAndUp,
+    UninvertibleTransformation
 }
 
 
+/// Serialized by its constructor parameters, loading goes through Screen::new so invalid screens are rejected
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde-serialize", serde(try_from = "ScreenParameters", into = "ScreenParameters"))]
 pub struct Screen {
     center: Point3,
     up: Unit<Vector3>,
@@ -23,12 +33,12 @@ pub struct Screen {
 }
 
 impl Screen {
-    pub fn new(center: Point3, normal: Vector3, up: Vector3, width: FloatType, height: FloatType, h_res: IntType, v_res: IntType) -> Self {
+    pub fn new(center: Point3, normal: Vector3, up: Vector3, width: FloatType, height: FloatType, h_res: IntType, v_res: IntType) -> Result<Self, ScreenError> {
         if h_res <= 0 || v_res <= 0 || width.less_eq_eps(&0.0) || height.less_eq_eps(&0.0) {
-            panic!("Invalid screen input values");
+            return Err(ScreenError::InvalidDimensions);
         }
         if normal.same_direction_as(&up) {
-            panic!("Screen normal and up vectors are pointing in the same direction");
+            return Err(ScreenError::ParallelNormalAndUp);
         }
 
         let normal_normalized = Unit::new_normalize(normal);
@@ -36,11 +46,11 @@ impl Screen {
         let left_normlized = Unit::new_unchecked(up_normalized.cross(&normal_normalized));
         let up_corrected = Unit::new_unchecked(normal_normalized.cross(&left_normlized));
         let transform_matrix = {
-            let mut result = Matrix3::from_columns(&[left_normlized.unwrap().clone(), up_normalized.unwrap().clone(), normal_normalized.unwrap().clone()]);
-            result.try_inverse().expect("Uninvertible screen transformation matrix")
+            let result = Matrix3::from_columns(&[left_normlized.unwrap().clone(), up_normalized.unwrap().clone(), normal_normalized.unwrap().clone()]);
+            result.try_inverse().ok_or(ScreenError::UninvertibleTransformation)?
         };
 
-        Self {  center: center,
+        Ok(Self {  center: center,
                 up: up_corrected,
                 left: left_normlized,
                 normal: normal_normalized,
@@ -48,11 +58,11 @@ impl Screen {
                 width: width,
                 height: height,
                 horizontal_resolution: h_res,
-                vertical_resolution: v_res}
+                vertical_resolution: v_res})
     }
 
-    pub fn new_unit(center: Point3, normal: Vector3, up: Vector3, width_to_height_ratio: FloatType, height: FloatType, v_res: IntType) -> Self {
-        let vertical_resolution = v_res as f64;
+    pub fn new_unit(center: Point3, normal: Vector3, up: Vector3, width_to_height_ratio: FloatType, height: FloatType, v_res: IntType) -> Result<Self, ScreenError> {
+        let vertical_resolution = v_res as FloatType;
         Self::new(center, normal, up, width_to_height_ratio * height, height, (vertical_resolution * width_to_height_ratio).round() as IntType, v_res)
     }
 
@@ -178,7 +188,45 @@ impl Iterator for ScreenIterator {
     }
 }
 
+#[cfg(feature = "serde-serialize")]
+#[derive(Serialize, Deserialize)]
+struct ScreenParameters {
+    center: Point3,
+    normal: Vector3,
+    up: Vector3,
+    width: FloatType,
+    height: FloatType,
+    horizontal_resolution: IntType,
+    vertical_resolution: IntType
+}
+
+#[cfg(feature = "serde-serialize")]
+impl TryFrom<ScreenParameters> for Screen {
+    type Error = ScreenError;
+
+    fn try_from(parameters: ScreenParameters) -> Result<Self, ScreenError> {
+        Screen::new(parameters.center, parameters.normal, parameters.up, parameters.width, parameters.height, parameters.horizontal_resolution, parameters.vertical_resolution)
+    }
+}
+
+#[cfg(feature = "serde-serialize")]
+impl From<Screen> for ScreenParameters {
+    fn from(screen: Screen) -> Self {
+        Self {
+            center: screen.center,
+            normal: *screen.normal.as_ref(),
+            up: *screen.up.as_ref(),
+            width: screen.width,
+            height: screen.height,
+            horizontal_resolution: screen.horizontal_resolution,
+            vertical_resolution: screen.vertical_resolution
+        }
+    }
+}
+
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde-serialize", serde(from = "EyeParameters", into = "EyeParameters"))]
 pub struct Eye {
     position: Point3,
     direction: Unit<Vector3>,
@@ -199,33 +247,81 @@ impl Eye {
     }
 }
 
+#[cfg(feature = "serde-serialize")]
+#[derive(Serialize, Deserialize)]
+struct EyeParameters {
+    position: Point3,
+    direction: Vector3
+}
+
+#[cfg(feature = "serde-serialize")]
+impl From<EyeParameters> for Eye {
+    fn from(parameters: EyeParameters) -> Self {
+        Eye::new(parameters.position, parameters.direction)
+    }
+}
+
+#[cfg(feature = "serde-serialize")]
+impl From<Eye> for EyeParameters {
+    fn from(eye: Eye) -> Self {
+        Self {
+            position: eye.position,
+            direction: *eye.direction.as_ref()
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ViewError {
     ScreenRelated(ScreenError)
 }
 
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde-serialize", derive(Serialize, Deserialize))]
 pub struct View {
     screen: Screen,
-    eye: Eye
+    eye: Eye,
+    shutter_interval: Option<(FloatType, FloatType)>
 }
 
 impl View {
     pub fn new(screen: Screen, eye: Eye) -> Self {
         Self {  screen: screen,
-                eye: eye}
+                eye: eye,
+                shutter_interval: None}
     }
 
-    pub fn new_unit(eye_position: Point3, eye_direction: Vector3, screen_up: Vector3, screen_width_to_height_ratio: FloatType, screen_height: FloatType, screen_v_res: IntType) -> Self{
+    pub fn new_unit(eye_position: Point3, eye_direction: Vector3, screen_up: Vector3, screen_width_to_height_ratio: FloatType, screen_height: FloatType, screen_v_res: IntType) -> Result<Self, ViewError> {
         let eye_unit_direction = Unit::new_normalize(eye_direction);
-        Self {  screen: Screen::new_unit(eye_position + eye_unit_direction.as_ref(),
-                                         *eye_unit_direction.as_ref(), 
-                                         screen_up,
-                                         screen_width_to_height_ratio,
-                                         screen_height,
-                                         screen_v_res),
-                eye: Eye::new(eye_position, 
-                              *eye_unit_direction.as_ref())
+        let screen = Screen::new_unit(eye_position + eye_unit_direction.as_ref(),
+                                      *eye_unit_direction.as_ref(),
+                                      screen_up,
+                                      screen_width_to_height_ratio,
+                                      screen_height,
+                                      screen_v_res).map_err(ViewError::ScreenRelated)?;
+        Ok(Self {  screen: screen,
+                   eye: Eye::new(eye_position,
+                                 *eye_unit_direction.as_ref()),
+                   shutter_interval: None
+        })
+    }
+
+    pub fn set_shutter_interval(&mut self, shutter_interval: Option<(FloatType, FloatType)>) {
+        self.shutter_interval = shutter_interval;
+    }
+
+    pub fn get_shutter_interval(&self) -> Option<(FloatType, FloatType)> {
+        self.shutter_interval
+    }
+
+    fn create_primary_ray(&self, ray_direction: Vector3) -> Ray {
+        let ray = Ray::new(*self.eye.get_position(), ray_direction);
+        match self.shutter_interval {
+            Some((open, close)) => {
+                let mut random_generator = rand::thread_rng();
+                ray.set_time(open + (close - open) * random_generator.gen::<FloatType>())
+            },
+            None => ray
         }
     }
 
@@ -234,7 +330,7 @@ impl View {
             Ok(point) => {
                 let eye_coord = self.eye.get_position();
                 let ray_direction = point - eye_coord;
-                Ok(Ray::new(*self.eye.get_position(), ray_direction))
+                Ok(self.create_primary_ray(ray_direction))
             },
             Err(err) => Err(ViewError::ScreenRelated(err))
         }
@@ -245,7 +341,7 @@ impl View {
             Ok(point) => {
                 let eye_coord = self.eye.get_position();
                 let ray_direction = point - eye_coord;
-                Ok(Ray::new(*self.eye.get_position(), ray_direction))
+                Ok(self.create_primary_ray(ray_direction))
             },
             Err(err) => Err(ViewError::ScreenRelated(err))
         }
@@ -294,7 +390,7 @@ impl<'view> Iterator for ViewIterator<'view> {
     type Item = (Ray, Point2Int);
     fn next(&mut self) -> Option<(Ray, Point2Int)> {
         let result = match self.get_screen_coord(&self.state) {
-            Some(coordinate) => Some((self.view.get_ray_to_screen_coordinate(coordinate).unwrap(), coordinate)),
+            Some(coordinate) => self.view.get_ray_to_screen_coordinate(coordinate).ok().map(|ray| (ray, coordinate)),
             None => None
         };
         self.state += 1;
@@ -310,7 +406,7 @@ mod tests {
 
     #[test]
     fn screen_get_intersected_pixel_hit_origin() {
-        let test_screen = Screen::new_unit(Point3::origin(), Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 1.0, 0.0), 1.0, 1.0, 1000);
+        let test_screen = Screen::new_unit(Point3::origin(), Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 1.0, 0.0), 1.0, 1.0, 1000).unwrap();
         let test_ray = Ray::new(Point3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 0.0, -1.0));
 
         let result = test_screen.get_intersected_pixel(&test_ray).expect("Should have hit screen");
@@ -320,7 +416,7 @@ mod tests {
 
     #[test]
     fn screen_get_intersected_pixel_hit_to_not_origin() {
-        let test_screen = Screen::new_unit(Point3::origin(), Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 1.0, 0.0), 1.0, 1.0, 1000);
+        let test_screen = Screen::new_unit(Point3::origin(), Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 1.0, 0.0), 1.0, 1.0, 1000).unwrap();
         let test_ray = Ray::new(Point3::new(0.25, 0.25, 1.0), Vector3::new(0.0, 0.0, -1.0));
 
         let result = test_screen.get_intersected_pixel(&test_ray).expect("Should have hit screen");
@@ -334,7 +430,7 @@ mod tests {
         let eye_point = Point3::new(0.0, 0.0, -1.0);
         let eye_ray_direction = (target_on_screen_point - eye_point).normalize();
 
-        let test_screen = Screen::new_unit(Point3::origin(), Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 1.0, 0.0), 1.0, 1.0, 1000);
+        let test_screen = Screen::new_unit(Point3::origin(), Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 1.0, 0.0), 1.0, 1.0, 1000).unwrap();
         let test_ray = Ray::new(eye_point + (eye_ray_direction * 5.0), -eye_ray_direction);
 
         let result = test_screen.get_intersected_pixel(&test_ray).expect("Should have hit screen");
@@ -344,7 +440,7 @@ mod tests {
 
     #[test]
     fn screen_get_intersected_pixel_miss() {
-        let test_screen = Screen::new_unit(Point3::origin(), Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 1.0, 0.0), 1.0, 1.0, 1000);
+        let test_screen = Screen::new_unit(Point3::origin(), Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 1.0, 0.0), 1.0, 1.0, 1000).unwrap();
         let test_ray = Ray::new(Point3::new(0.6, 0.6, 1.0), Vector3::new(0.0, 0.0, -1.0));
 
         assert!(test_screen.get_intersected_pixel(&test_ray).is_none());
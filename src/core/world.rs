@@ -1,11 +1,27 @@
-use core::{Color, Ray, RayIntersection, Illuminator, Intersector, LightIntersection};
+use core::{Color, Ray, RayError, RayIntersection, Illuminator, Intersector, LightSource, LightIntersection, LightLinkSet, Occlusion, RenderStatistics, RayPacket, PACKET_SIZE};
 use tools::{Vector3Extensions, CompareWithTolerance};
+use std::sync::{Arc};
+
+/// Bound of the surface crossings collected along a ray, in case the continued rays keep hitting the same surface
+const MAX_INTERSECTIONS_ALONG_RAY: usize = 1024;
 
 
 pub trait RayCaster: Send + Sync {
     fn cast_ray(&self, ray: &Ray) -> Option<Color>;
     fn cast_colored_light_ray(&self, ray: &Ray, intersection: &RayIntersection) -> Option<Color>;
     fn cast_model_ray(&self, ray: &Ray) -> Option<RayIntersection>;
+
+    /// Shadow ray blocked only by the models in the set of shadow casters
+    fn cast_linked_light_ray(&self, ray: &Ray, intersection: &RayIntersection, _shadow_casters: &LightLinkSet) -> Option<Color> {
+        self.cast_colored_light_ray(ray, intersection)
+    }
+
+    /// Colors of coherent rays, implementations may intersect them together in packets
+    fn cast_ray_packet(&self, rays: &[Ray]) -> Vec<Option<Color>> {
+        rays.iter().map(|ray| self.cast_ray(ray)).collect()
+    }
+
+    fn report_ray_error(&self, _error: &RayError) {}
 }
 
 pub trait IlluminationCaster: Send + Sync {
@@ -16,11 +32,22 @@ pub trait ColorCalculator: Send + Sync {
     fn get_color(&self, itersection: &RayIntersection, ray_caster: &RayCaster, illumination_caster: &IlluminationCaster) -> Option<Color>;
 }
 
+/// World whose shading can be driven by an external caster, used to inspect the rays a pixel spawns
+pub trait TraceableWorld: RayCaster + IlluminationCaster {
+    fn get_color_with(&self, intersection: &RayIntersection, ray_caster: &RayCaster, illumination_caster: &IlluminationCaster) -> Option<Color>;
+    fn get_illumination_with(&self, intersection: &RayIntersection, ray_caster: &RayCaster) -> Vec<LightIntersection>;
+    /// Every surface the ray crosses nearest first, the ray continues through each of them so solid models give both their entry and exit
+    fn get_intersections_along(&self, ray: &Ray) -> Vec<RayIntersection>;
+    fn get_escaped_ray_color(&self, ray: &Ray) -> Option<Color>;
+    fn get_light_sources(&self) -> Vec<&LightSource>;
+}
+
 pub struct World<IntersectorType, ColorCalculatorType, IlluminatorType> {
     intersector : IntersectorType,
     color_calculator : ColorCalculatorType,
     illuminator: IlluminatorType,
     depth_limit : i32,
+    statistics: Option<Arc<RenderStatistics>>,
 }
 
 impl<IntersectorType: Intersector + Send + Sync,
@@ -32,7 +59,18 @@ impl<IntersectorType: Intersector + Send + Sync,
         Self {intersector: intersector,
               color_calculator: colorcalc,
               illuminator: illuminator,
-              depth_limit: ray_depth_limit}
+              depth_limit: ray_depth_limit,
+              statistics: None}
+    }
+
+    /// The statistics are shared with the intersector, so it can count the model tests
+    pub fn set_statistics(&mut self, statistics: Option<Arc<RenderStatistics>>) {
+        self.intersector.set_statistics(statistics.clone());
+        self.statistics = statistics;
+    }
+
+    pub fn get_statistics(&self) -> Option<&Arc<RenderStatistics>> {
+        self.statistics.as_ref()
     }
 }
 
@@ -40,20 +78,66 @@ impl<IntersectorType: Intersector + Send + Sync,
      ColorCalculatorType: ColorCalculator + Send + Sync,
      IlluminatorType : Illuminator + Send + Sync> RayCaster for World<IntersectorType, ColorCalculatorType, IlluminatorType> {
     fn cast_ray(&self, ray: &Ray) -> Option<Color> {
+        if let Some(ref statistics) = self.statistics {
+            statistics.record_cast_ray(ray);
+        }
+
         if ray.get_depth_counter() <= self.depth_limit {
             match self.intersector.get_nearest_intersection(ray) {
                 Some(nearest_intersection) => self.color_calculator.get_color(&nearest_intersection, self, self),
-                None => None,
+                None => self.illuminator.get_escaped_ray_color(ray),
             }
         } else {
+            self.report_ray_error(&RayError::DepthLimitReached);
             None
         }
     }
 
+    fn cast_ray_packet(&self, rays: &[Ray]) -> Vec<Option<Color>> {
+        let mut result = Vec::with_capacity(rays.len());
+        for chunk in rays.chunks(PACKET_SIZE) {
+            if chunk.iter().any(|ray| ray.get_depth_counter() > self.depth_limit) {
+                result.extend(chunk.iter().map(|ray| self.cast_ray(ray)));
+                continue;
+            }
+
+            if let Some(ref statistics) = self.statistics {
+                for ray in chunk.iter() {
+                    statistics.record_cast_ray(ray);
+                }
+            }
+
+            let intersections = self.intersector.get_nearest_intersections_packet(&RayPacket::new(chunk));
+            result.extend(chunk.iter().zip(intersections.iter()).map(|(ray, intersection)| {
+                match *intersection {
+                    Some(ref nearest_intersection) => self.color_calculator.get_color(nearest_intersection, self, self),
+                    None => self.illuminator.get_escaped_ray_color(ray),
+                }
+            }));
+        }
+        result
+    }
+
     fn cast_colored_light_ray(&self, ray: &Ray, intersection: &RayIntersection) -> Option<Color> {
+        self.cast_linked_light_ray(ray, intersection, &LightLinkSet::All)
+    }
+
+    fn cast_linked_light_ray(&self, ray: &Ray, intersection: &RayIntersection, shadow_casters: &LightLinkSet) -> Option<Color> {
+        if let Some(ref statistics) = self.statistics {
+            statistics.record_shadow_ray();
+        }
+
         let origin_to_intersection_vector = intersection.get_intersection_point() - ray.get_origin();
         
         let max_length = origin_to_intersection_vector.length();
+        if shadow_casters.is_all() {
+            match self.intersector.get_occlusion(ray, max_length) {
+                Occlusion::Unoccluded => return Some(Color::one()),
+                Occlusion::Opaque => return None,
+                Occlusion::Transparent => {}
+            }
+        }
+
         let mut resulting_color = Color::one();
 
         for intersection in self.intersector.get_intersections_reverse_ordered(ray).iter().rev() {
@@ -61,6 +145,10 @@ impl<IntersectorType: Intersector + Send + Sync,
                 return Some(resulting_color)
             }
             
+            if !shadow_casters.contains(intersection.get_model_identifier()) {
+                continue;
+            }
+
             match intersection.get_material().get_transparency_to_light() {
                 None => return None,
                 Some(transparency) => resulting_color *= transparency
@@ -71,12 +159,23 @@ impl<IntersectorType: Intersector + Send + Sync,
     }
 
     fn cast_model_ray(&self, ray: &Ray) -> Option<RayIntersection> {
+        if let Some(ref statistics) = self.statistics {
+            statistics.record_model_ray(ray);
+        }
+
         if ray.get_depth_counter() <= self.depth_limit {
             self.intersector.get_nearest_intersection(ray)
         } else {
+            self.report_ray_error(&RayError::DepthLimitReached);
             None
         }
     }
+
+    fn report_ray_error(&self, error: &RayError) {
+        if let Some(ref statistics) = self.statistics {
+            statistics.record_ray_error(error);
+        }
+    }
 }
 
 impl<IntersectorType: Intersector + Send + Sync,
@@ -85,4 +184,37 @@ impl<IntersectorType: Intersector + Send + Sync,
     fn get_illumination_at(&self, intersection: &RayIntersection) -> Vec<LightIntersection> {
         self.illuminator.get_illumination_at(intersection, self)
     }
-}
\ No newline at end of file
+}
+
+impl<IntersectorType: Intersector + Send + Sync,
+     ColorCalculatorType: ColorCalculator + Send + Sync,
+     IlluminatorType : Illuminator + Send + Sync> TraceableWorld for World<IntersectorType, ColorCalculatorType, IlluminatorType> {
+    fn get_color_with(&self, intersection: &RayIntersection, ray_caster: &RayCaster, illumination_caster: &IlluminationCaster) -> Option<Color> {
+        self.color_calculator.get_color(intersection, ray_caster, illumination_caster)
+    }
+
+    fn get_illumination_with(&self, intersection: &RayIntersection, ray_caster: &RayCaster) -> Vec<LightIntersection> {
+        self.illuminator.get_illumination_at(intersection, ray_caster)
+    }
+
+    fn get_intersections_along(&self, ray: &Ray) -> Vec<RayIntersection> {
+        let mut result: Vec<RayIntersection> = Vec::new();
+        let mut current_ray = Some(ray.clone());
+        while let Some(intersection) = current_ray.take().and_then(|current_ray| self.intersector.get_nearest_intersection(&current_ray)) {
+            current_ray = Ray::continue_ray_from_intersection_into_medium(&intersection, *ray.get_direction()).ok();
+            result.push(intersection);
+            if result.len() >= MAX_INTERSECTIONS_ALONG_RAY {
+                break;
+            }
+        }
+        result
+    }
+
+    fn get_escaped_ray_color(&self, ray: &Ray) -> Option<Color> {
+        self.illuminator.get_escaped_ray_color(ray)
+    }
+
+    fn get_light_sources(&self) -> Vec<&LightSource> {
+        self.illuminator.get_light_sources()
+    }
+}
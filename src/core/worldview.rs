@@ -1,17 +1,19 @@
 use defs::{Point2Int};
-use core::{RayCaster, IlluminationCaster, View, Color, RayIntersection, Screen, Ray, LightIntersection, 
+use core::{RayCaster, IlluminationCaster, ColorCalculator, TraceableWorld, PixelQueryResult, ScenePathMap, query_pixel, View, Color, RayIntersection, Screen, Ray, RayError, LightIntersection, LightLinkSet,
            Scene, SceneError, BasicSceneBuffer, SceneBuffer, MutableSceneBuffer, ImmutableSceneBuffer, SceneBufferError};
 use std::sync::{Arc};
 
 
 pub trait WorldViewTrait: Scene + SceneBuffer {
-
+    /// Buffer the view is rendered into
+    fn get_scene_buffer(&self) -> &ImmutableSceneBuffer;
 }
 
 pub struct WorldView<WorldT> {
     world: Arc<WorldT>,
     view: View,
     result_buffer: BasicSceneBuffer,
+    color_calculator_override: Option<Box<ColorCalculator>>,
 }
 
 impl<WorldT> WorldView<WorldT>
@@ -22,7 +24,26 @@ impl<WorldT> WorldView<WorldT>
         let screen_clone = view.get_screen().clone();
         Self {  world: Arc::new(world),
                 view: view,
-                result_buffer: BasicSceneBuffer::new(screen_clone) }
+                result_buffer: BasicSceneBuffer::new(screen_clone),
+                color_calculator_override: None }
+    }
+
+    /// Shades the intersections of the view with the given calculator instead of the world's, e.g. for debug render modes
+    pub fn set_color_calculator_override(&mut self, color_calculator: Option<Box<ColorCalculator>>) {
+        self.color_calculator_override = color_calculator;
+    }
+
+    pub fn get_color_calculator_override(&self) -> Option<&ColorCalculator> {
+        self.color_calculator_override.as_ref().map(|color_calculator| &**color_calculator)
+    }
+}
+
+impl<WorldT> WorldView<WorldT>
+    where WorldT: TraceableWorld + Send + Sync
+{
+    /// Traces the pixel recording its intersections and spawned rays, scene paths are looked up by model identifier
+    pub fn query_pixel(&self, pixel: Point2Int, scene_paths: Option<&ScenePathMap>) -> Result<PixelQueryResult, SceneError> {
+        query_pixel(&*self.world, &self.view, pixel, scene_paths)
     }
 }
 
@@ -30,16 +51,39 @@ impl<WorldT> RayCaster for WorldView<WorldT>
     where WorldT: RayCaster + IlluminationCaster + Send + Sync
 {
     fn cast_ray(&self, ray: &Ray) -> Option<Color> {
-        self.world.cast_ray(ray)
+        match self.color_calculator_override {
+            Some(ref color_calculator) => {
+                match self.world.cast_model_ray(ray) {
+                    Some(intersection) => color_calculator.get_color(&intersection, &*self.world, &*self.world),
+                    None => None
+                }
+            },
+            None => self.world.cast_ray(ray)
+        }
+    }
+
+    fn cast_ray_packet(&self, rays: &[Ray]) -> Vec<Option<Color>> {
+        match self.color_calculator_override {
+            Some(_) => rays.iter().map(|ray| self.cast_ray(ray)).collect(),
+            None => self.world.cast_ray_packet(rays)
+        }
     }
 
     fn cast_colored_light_ray(&self, ray: &Ray, intersection: &RayIntersection) -> Option<Color> {
         self.world.cast_colored_light_ray(ray, intersection)
     }
 
+    fn cast_linked_light_ray(&self, ray: &Ray, intersection: &RayIntersection, shadow_casters: &LightLinkSet) -> Option<Color> {
+        self.world.cast_linked_light_ray(ray, intersection, shadow_casters)
+    }
+
     fn cast_model_ray(&self, ray: &Ray) -> Option<RayIntersection> {
         self.world.cast_model_ray(ray)
     }
+
+    fn report_ray_error(&self, error: &RayError) {
+        self.world.report_ray_error(error)
+    }
 }
 
 impl<WorldT> IlluminationCaster for WorldView<WorldT>
@@ -55,7 +99,7 @@ impl<WorldT> Scene for WorldView<WorldT>
 {
     fn get_pixel_color(&self, pixel: Point2Int) -> Result<Color, SceneError> {
         if let Ok(ray) = self.view.get_ray_to_screen_coordinate(pixel) {
-            match self.world.cast_ray(&ray) {
+            match self.cast_ray(&ray) {
                 Some(color) => Ok(color),
                 None => Err(SceneError::NothingIntersected),
             }
@@ -64,6 +108,19 @@ impl<WorldT> Scene for WorldView<WorldT>
         }
     }
 
+    fn get_pixel_colors(&self, pixels: &[Point2Int]) -> Vec<Result<Color, SceneError>> {
+        let rays: Vec<Option<Ray>> = pixels.iter().map(|pixel| self.view.get_ray_to_screen_coordinate(*pixel).ok()).collect();
+        let valid_rays: Vec<Ray> = rays.iter().filter_map(|ray| ray.clone()).collect();
+        let mut colors = self.cast_ray_packet(&valid_rays).into_iter();
+
+        rays.iter().map(|ray| {
+            match *ray {
+                Some(_) => colors.next().and_then(|color| color).ok_or(SceneError::NothingIntersected),
+                None => Err(SceneError::InvalidInputCoord)
+            }
+        }).collect()
+    }
+
     fn get_pixel_intersection(&self, pixel: Point2Int) -> Result<RayIntersection, SceneError> {
         if let Ok(ray) = self.view.get_ray_to_screen_coordinate(pixel) {
             match self.world.cast_model_ray(&ray) {
@@ -124,5 +181,7 @@ impl<WorldT> SceneBuffer for WorldView<WorldT>
 impl<WorldT> WorldViewTrait for WorldView<WorldT>
     where WorldT: RayCaster + IlluminationCaster + Send + Sync
 {
-
+    fn get_scene_buffer(&self) -> &ImmutableSceneBuffer {
+        &self.result_buffer
+    }
 }
\ No newline at end of file
@@ -1,4 +1,7 @@
+#[cfg(not(feature = "f32"))]
 pub type FloatType = f64;
+#[cfg(feature = "f32")]
+pub type FloatType = f32;
 pub type IntType = i32;
 
 pub type VectorRow4 = super::na::core::Matrix1x4<FloatType>;
@@ -16,4 +19,22 @@ pub type Vector2 = super::na::Vector2<FloatType>;
 pub type Point2Int = super::na::Point2<IntType>;
 pub type Vector2Int = super::na::Vector2<IntType>;
 
-pub static FLOAT_ULPS_TOLERANCE: i64 = 65536;
\ No newline at end of file
+#[cfg(not(feature = "f32"))]
+pub use std::f64::consts as float_consts;
+#[cfg(feature = "f32")]
+pub use std::f32::consts as float_consts;
+
+/// Tolerance of the float comparisons in units of the last place, around 1e-11 relative for f64 and 4e-6 for f32
+#[cfg(not(feature = "f32"))]
+pub static FLOAT_ULPS_TOLERANCE: i64 = 65536;
+#[cfg(feature = "f32")]
+pub static FLOAT_ULPS_TOLERANCE: i32 = 32;
+
+/// Shortest distance a ray has to travel to hit anything near the origin, larger coordinates raise it by their rounding error
+pub static FLOAT_DISTANCE_TOLERANCE: FloatType = FloatType::MIN_POSITIVE;
+
+/// Absolute tolerance of the tests comparing computed values
+#[cfg(all(test, not(feature = "f32")))]
+pub static TEST_TOLERANCE: FloatType = 1e-9;
+#[cfg(all(test, feature = "f32"))]
+pub static TEST_TOLERANCE: FloatType = 1e-3;
\ No newline at end of file
@@ -8,6 +8,11 @@ extern crate num;
 extern crate num_traits as numt;
 extern crate uuid;
 extern crate rand;
+#[cfg(feature = "serde-serialize")]
+#[macro_use]
+extern crate serde_derive;
+#[cfg(all(test, feature = "serde-serialize"))]
+extern crate serde_json;
 
 pub mod defs;
 pub mod tools;
@@ -55,6 +55,14 @@ impl CompareWithTolerance<FloatType> for FloatType {
 }
 
 
+/// Bound of the relative rounding error accumulated over the given count of floating point operations
+pub fn get_rounding_error_bound(operation_count: IntType) -> FloatType {
+    let unit_roundoff = FloatType::EPSILON * 0.5;
+    let accumulated = operation_count as FloatType * unit_roundoff;
+    accumulated / (1.0 - accumulated)
+}
+
+
 pub trait Vector3Extensions {
     fn same_direction_as(&self, rhs: &Vector3) -> bool;
     fn length(&self) -> FloatType;